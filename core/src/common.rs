@@ -6,19 +6,35 @@
 
 //! This file contains common structures shared by GGC and GGA.
 
-use std::{iter, mem, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    iter, mem,
+    path::{Path, PathBuf},
+};
 
-use elf_rs::{Elf, ElfFile};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
-    components::storage::Storage,
-    gga::GameGirlAdv,
+    components::{
+        cart_info::{CartInfo, CheckKind},
+        firmware::{Firmware, FirmwareError},
+        storage::Storage,
+    },
+    gga,
+    gga::{
+        cartridge::database::CartOverride, graphics::palette::ColourProfile, program::Program,
+        GameGirlAdv,
+    },
+    ggc,
     ggc::{
-        io::{cartridge::Cartridge, joypad::Joypad},
+        io::{camera::CameraSensor, cartridge::Cartridge, joypad::Joypad},
+        model::GgcModel,
         GameGirl,
     },
+    nds,
     nds::Nds,
+    psx,
     psx::PlayStation,
     Colour,
 };
@@ -78,7 +94,7 @@ macro_rules! common_functions {
                 return;
             }
 
-            let target = ($clock as f32 * delta * self.options.speed_multiplier as f32) as i32;
+            let target = ($clock as f32 * delta * self.options.speed_multiplier) as i32;
             self.scheduler.schedule($pause_event, target);
 
             self.ticking = true;
@@ -96,76 +112,94 @@ macro_rules! common_functions {
             self.ppu.last_frame.take()
         }
 
-        /// Produce the next audio samples and write them to the given buffer.
-        /// Writes zeroes if the system is not currently running
-        /// and no audio should be played.
-        pub fn produce_samples(&mut self, samples: &mut [f32]) {
-            if !self.options.running {
-                samples.fill(0.0);
-                return;
-            }
-
-            let target = samples.len() * self.options.speed_multiplier;
-            while self.apu.buffer.len() < target {
-                if !self.options.running {
-                    samples.fill(0.0);
-                    return;
-                }
-                self.advance();
-            }
-
-            let mut buffer = mem::take(&mut self.apu.buffer);
+        /// Take the audio samples produced since the last call, with volume
+        /// applied and in reverse order while rewinding.
+        /// Samples are interleaved stereo. They are produced at the speed the
+        /// system runs at, so they need to be resampled by
+        /// [EmulateOptions::speed_multiplier] to play at regular speed.
+        pub fn take_samples(&mut self) -> Vec<f32> {
+            let buffer = mem::take(&mut self.apu.buffer);
+            let volume = self.config.volume;
+            let frames = buffer.chunks_exact(2);
             if self.options.invert_audio_samples {
-                // If rewinding, truncate and get rid of any excess samples to prevent
-                // audio samples getting backed up
-                for (src, dst) in buffer.into_iter().zip(samples.iter_mut().rev()) {
-                    *dst = src * self.config.volume;
-                }
+                frames.rev().flatten().map(|s| s * volume).collect()
             } else {
-                // Otherwise, store any excess samples back in the buffer for next time
-                // while again not storing too many to avoid backing up.
-                // This way can cause clipping if the console produces audio too fast,
-                // however this is preferred to audio falling behind and eating
-                // a lot of memory.
-                for sample in buffer.drain(target..) {
-                    self.apu.buffer.push(sample);
-                }
-                self.apu.buffer.truncate(5_000);
-
-                for (src, dst) in buffer
-                    .into_iter()
-                    .step_by(self.options.speed_multiplier)
-                    .zip(samples.iter_mut())
-                {
-                    *dst = src * self.config.volume;
-                }
+                frames.flatten().map(|s| s * volume).collect()
             }
         }
 
-        /// Reset the console, while keeping the current cartridge inserted.
-        pub fn reset(&mut self) {
-            let old_self = mem::take(self);
-            self.restore_from(old_self);
-        }
-
         /// Create a save state that can be loaded with [load_state].
         pub fn save_state(&self) -> Vec<u8> {
             common::serialize(self, self.config.compress_savestates)
         }
 
+        /// Create a save state that is never compressed, for callers that
+        /// compress states themselves. Load it with [load_raw_state].
+        pub fn save_raw_state(&self) -> Vec<u8> {
+            common::serialize(self, false)
+        }
+
         /// Load a state produced by [save_state].
         /// Will restore the current cartridge and debugger.
         pub fn load_state(&mut self, state: &[u8]) {
+            self.load_state_inner(state, self.config.compress_savestates, true);
+        }
+
+        /// Load a state produced by [save_raw_state].
+        pub fn load_raw_state(&mut self, state: &[u8]) {
+            self.load_state_inner(state, false, true);
+        }
+
+        /// Load a state produced by [save_raw_state] that might be invalid,
+        /// like one from a file of an older version or another system.
+        /// Unlike [load_raw_state], fails instead of panicking, leaving the
+        /// system unchanged.
+        pub fn try_load_raw_state(&mut self, state: &[u8]) -> Result<(), common::InvalidState> {
+            if cfg!(target_arch = "wasm32") {
+                // Currently crashes...
+                return Ok(());
+            }
+
+            let new_self = common::try_deserialize(state).ok_or(common::InvalidState)?;
+            let old_self = mem::replace(self, new_self);
+            self.restore_from(old_self, true);
+            Ok(())
+        }
+
+        /// Load a state produced by [save_raw_state], keeping the save data
+        /// of the cart that is in the state instead of the current one.
+        /// For in-memory restores like run-ahead, rewinding and rollback,
+        /// where save data written since the state was made must be undone.
+        pub fn load_raw_state_with_save(&mut self, state: &[u8]) {
+            self.load_state_inner(state, false, false);
+        }
+
+        /// Hash the state of the system, leaving out options, configuration
+        /// and output that do not influence emulation.
+        /// Two systems with the same hash are in the same state.
+        pub fn state_hash(&mut self) -> u64 {
+            let options = mem::take(&mut self.options);
+            let config = mem::take(&mut self.config);
+            let samples = mem::take(&mut self.apu.buffer);
+            let frame = self.ppu.last_frame.take();
+
+            let hash = common::hash(&common::serialize(self, false));
+
+            self.options = options;
+            self.config = config;
+            self.apu.buffer = samples;
+            self.ppu.last_frame = frame;
+            hash
+        }
+
+        fn load_state_inner(&mut self, state: &[u8], compressed: bool, restore_save: bool) {
             if cfg!(target_arch = "wasm32") {
                 // Currently crashes...
                 return;
             }
 
-            let old_self = mem::replace(
-                self,
-                common::deserialize(state, self.config.compress_savestates),
-            );
-            self.restore_from(old_self);
+            let old_self = mem::replace(self, common::deserialize(state, compressed));
+            self.restore_from(old_self, restore_save);
         }
     };
 }
@@ -186,9 +220,14 @@ pub enum System {
 impl System {
     forward_fn!(advance_delta, (), f32);
     forward_fn!(produce_frame, Option<Vec<Colour>>);
-    forward_fn!(produce_samples, (), &mut [f32]);
+    forward_fn!(take_samples, Vec<f32>);
     forward_fn!(save_state, Vec<u8>);
     forward_fn!(load_state, (), &[u8]);
+    forward_fn!(save_raw_state, Vec<u8>);
+    forward_fn!(load_raw_state, (), &[u8]);
+    forward_fn!(load_raw_state_with_save, (), &[u8]);
+    forward_fn!(try_load_raw_state, Result<(), InvalidState>, &[u8]);
+    forward_fn!(state_hash, u64);
 
     forward_fn!(advance);
     forward_fn!(reset);
@@ -217,6 +256,65 @@ impl System {
         &mut _sys.config
     );
 
+    /// Advance the system by the given delta like [advance_delta], then run
+    /// ahead the given amount of frames and return the last frame produced
+    /// there, before restoring the state of the real timeline.
+    /// This hides input lag the game itself has, at the cost of emulating
+    /// every frame `frames + 1` times. Audio, callbacks, notifications and
+    /// breakpoints only come from the real timeline.
+    pub fn advance_run_ahead(&mut self, delta: f32, frames: usize) -> Option<Vec<Colour>> {
+        self.advance_delta(delta);
+        if frames == 0 || !self.options().running || cfg!(target_arch = "wasm32") {
+            return self.last_frame();
+        }
+
+        // The frame of the real timeline is never shown
+        self.last_frame();
+        let state = self.save_raw_state();
+        let frame_finished = mem::replace(&mut self.options().frame_finished, Box::new(|_| ()));
+        let rumble = mem::replace(&mut self.options().rumble, Box::new(|_| ()));
+        let notification = mem::replace(&mut self.options().notification, Box::new(|_| ()));
+        // Run ahead without breakpoints, so that they neither pause the real
+        // timeline early nor count hits of frames that are thrown away
+        let debugger = match self {
+            System::GGC(gg) => (Some(mem::take(&mut gg.debugger)), None),
+            System::GGA(gg) => (None, Some(mem::take(&mut gg.debugger))),
+            _ => (None, None),
+        };
+
+        let mut frame = None;
+        for _ in 0..frames {
+            frame = self.produce_frame().or(frame);
+        }
+
+        // Audio and save data produced while running ahead are part of the
+        // state and thus discarded here
+        self.load_raw_state_with_save(&state);
+        self.options().running = true;
+        self.options().frame_finished = frame_finished;
+        self.options().rumble = rumble;
+        self.options().notification = notification;
+        match (&mut *self, debugger) {
+            (System::GGC(gg), (Some(debugger), _)) => gg.debugger = debugger,
+            (System::GGA(gg), (_, Some(debugger))) => gg.debugger = debugger,
+            _ => (),
+        }
+        frame
+    }
+
+    /// Step until exactly one new frame was produced and return it, even if
+    /// the system is paused. Used for frame advance.
+    pub fn advance_frame(&mut self) -> Option<Vec<Colour>> {
+        if !self.options().rom_loaded {
+            return None;
+        }
+        self.last_frame();
+        let running = mem::replace(&mut self.options().running, true);
+        let frame = self.produce_frame();
+        self.options().running = running;
+        frame
+    }
+
     /// Set a button on the joypad.
     pub fn set_button(&mut self, btn: Button, pressed: bool) {
         match self {
@@ -226,6 +324,63 @@ impl System {
         }
     }
 
+    /// Set the tilt of the cartridge's accelerometer, if it has one.
+    /// Both axes range from -1.0 to 1.0.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        match self {
+            System::GGC(gg) => gg.cart.set_tilt(x, y),
+            System::GGA(gg) => gg.cart.set_tilt(x, y),
+            _ => (),
+        }
+    }
+
+    /// Set the light level seen by the cartridge's solar sensor, if it has
+    /// one. 0 is complete darkness.
+    pub fn set_light(&mut self, level: u8) {
+        if let System::GGA(gg) = self {
+            gg.cart.set_light(level);
+        }
+    }
+
+    /// Set the rotation speed seen by the cartridge's gyro sensor, if it has
+    /// one. Ranges from -1.0 to 1.0, positive is clockwise.
+    pub fn set_rotation(&mut self, rotation: f32) {
+        if let System::GGA(gg) = self {
+            gg.cart.set_rotation(rotation);
+        }
+    }
+
+    /// Parse and verify the header of the loaded cart.
+    /// `None` on systems without a cart header.
+    pub fn cart_info(&self) -> Option<CartInfo> {
+        match self {
+            System::GGC(gg) => Some(gg.cart.info.clone()),
+            System::GGA(gg) => Some(CartInfo::gga(&gg.cart.rom)),
+            System::NDS(ds) => Some(CartInfo::nds(ds.cart.rom())),
+            System::PSX(_) => None,
+        }
+    }
+
+    /// Returns the kind of the current system.
+    pub fn kind(&self) -> SystemKind {
+        match self {
+            System::GGC(_) => SystemKind::Ggc,
+            System::GGA(_) => SystemKind::Gga,
+            System::NDS(_) => SystemKind::Nds,
+            System::PSX(_) => SystemKind::Psx,
+        }
+    }
+
+    /// Returns the amount of frames the current system produces per second.
+    pub fn frame_rate(&self) -> f64 {
+        match self {
+            System::GGC(_) => ggc::FRAME_RATE,
+            System::GGA(_) => gga::FRAME_RATE,
+            System::NDS(_) => nds::FRAME_RATE,
+            System::PSX(_) => psx::FRAME_RATE,
+        }
+    }
+
     /// Returns the screen size for the current system.
     pub fn screen_size(&self) -> [usize; 2] {
         match self {
@@ -236,16 +391,18 @@ impl System {
         }
     }
 
-    /// Save the game to disk.
-    pub fn save_game(&self, path: Option<PathBuf>) {
+    /// Save the game to disk. Returns if the cart had save data to save.
+    pub fn save_game(&self, path: Option<PathBuf>) -> bool {
         let save = match self {
             System::GGC(gg) => gg.cart.make_save(),
             System::GGA(gg) => gg.cart.make_save(),
             _ => todo!(),
         };
+        let saved = save.is_some();
         if let Some(save) = save {
             Storage::save(path, save);
         }
+        saved
     }
 
     pub fn as_ggc(&self) -> &GameGirl {
@@ -269,23 +426,32 @@ impl System {
         }
     }
 
-    /// Load a cart. Automatically picks the right system kind.
-    pub fn load_cart(&mut self, cart: Vec<u8>, path: Option<PathBuf>, config: &SystemConfig) {
-        // We detect GG(C) carts by the first 2 bytes of the "Nintendo" logo header
-        // that is present on every cartridge.
-        let is_ggc = cart[0x0104] == 0xCE && cart[0x0105] == 0xED;
-        // We detect GGA carts by a zero-filled header region
-        let is_gga = cart.iter().skip(0xB5).take(6).all(|b| *b == 0);
-        // We detect NDS carts by a zero-filled header region
-        let is_nds = cart.iter().skip(0x15).take(6).all(|b| *b == 0);
-
-        match () {
-            _ if is_ggc => self.load_ggc(cart, path, config),
-            _ if is_gga => self.load_gga(cart, path, config),
-            _ if is_nds => self.load_nds(cart, path, config),
-            _ => {
-                log::error!("Failed to detect cart! Guessing GGA.");
-                self.load_gga(cart, path, config)
+    /// Load a cart. Picks the system to load it on by the given `kind`,
+    /// or detects it if `None`; see [SystemKind::detect].
+    /// Fails if the system could not be detected or is not supported, or if it
+    /// needs firmware that could not be loaded; the current system is kept in
+    /// that case.
+    pub fn load_cart(
+        &mut self,
+        cart: Vec<u8>,
+        path: Option<PathBuf>,
+        kind: Option<SystemKind>,
+        config: &SystemConfig,
+    ) -> Result<(), LoadError> {
+        let kind = match kind {
+            Some(kind) => kind,
+            None => SystemKind::detect(&cart, path.as_deref())?,
+        };
+        log::info!("Loading cart on {kind}");
+        match kind {
+            SystemKind::Ggc => self.load_ggc(cart, path, config)?,
+            SystemKind::Gga => self.load_gga(cart, path, config)?,
+            SystemKind::Nds => self.load_nds(cart, path, config)?,
+            SystemKind::Psx => {
+                // Executables cannot be run yet, but a missing BIOS is
+                // still reported first, like on the other systems
+                Firmware::PsxBios.load(config)?;
+                return Err(LoadError::Unsupported(kind));
             }
         }
 
@@ -295,29 +461,42 @@ impl System {
             self.options().running = false;
             self.skip_bootrom();
         }
+        Ok(())
     }
 
-    fn load_ggc(&mut self, cart: Vec<u8>, path: Option<PathBuf>, config: &SystemConfig) {
+    fn load_ggc(
+        &mut self,
+        cart: Vec<u8>,
+        path: Option<PathBuf>,
+        config: &SystemConfig,
+    ) -> Result<(), FirmwareError> {
         let mut cart = Cartridge::from_rom(cart);
         if let Some(save) = Storage::load(path, cart.title(true)) {
             cart.load_save(save);
         }
 
         let mut ggc = Box::new(GameGirl::default());
-        ggc.load_cart(cart, config, false);
-        ggc.options.frame_finished = mem::replace(
-            &mut self.options().frame_finished,
-            EmulateOptions::serde_frame_finished(),
-        );
+        ggc.load_cart(cart, config, false)?;
+        ggc.options.take_callbacks(self.options());
         *self = Self::GGC(ggc);
+        Ok(())
     }
 
-    fn load_gga(&mut self, cart: Vec<u8>, path: Option<PathBuf>, config: &SystemConfig) {
-        let (mut cart, is_elf) = if let Some(elf_read) = Self::decode_elf(&cart) {
-            (elf_read, true)
+    fn load_gga(
+        &mut self,
+        cart: Vec<u8>,
+        path: Option<PathBuf>,
+        config: &SystemConfig,
+    ) -> Result<(), FirmwareError> {
+        // Without a BIOS dump, the HLE BIOS is the only way to boot
+        let hle_bios = config.gga_hle_bios || !config.firmware.contains_key(&Firmware::GgaBios);
+        let bios = if hle_bios {
+            None
         } else {
-            (cart, false)
+            Some(Firmware::GgaBios.load(config)?)
         };
+        let mut program = Program::decode(cart, path.as_deref());
+        let mut cart = mem::take(&mut program.rom);
 
         // Paging implementation requires this to prevent reading unallocated memory
         let until_full_page = 0x7FFF - (cart.len() & 0x7FFF);
@@ -325,55 +504,49 @@ impl System {
 
         let mut gga = Box::new(GameGirlAdv::default());
         gga.config = config.clone();
-        gga.cart.load_rom(cart);
+        gga.config.gga_hle_bios = hle_bios;
+        if let Some(bios) = bios {
+            gga.memory.bios = bios;
+        }
+        gga.cart.load_rom(cart, config);
         if let Some(save) = Storage::load(path, gga.cart.title()) {
             gga.cart.load_save(save);
         }
         gga.init_memory();
-        gga.options.frame_finished = mem::replace(
-            &mut self.options().frame_finished,
-            EmulateOptions::serde_frame_finished(),
-        );
-
-        if is_elf {
+        gga.ppu().set_colour_profile(config.gga_colour_correction);
+        gga.options.take_callbacks(self.options());
+
+        program.load_ram(&mut gga);
+        if let Some(entry) = program.entry {
+            gga.skip_bootrom_to(entry);
+        } else if hle_bios {
+            // The HLE BIOS cannot boot the cart
             gga.skip_bootrom();
         }
 
         *self = Self::GGA(gga);
+        Ok(())
     }
 
-    fn decode_elf(cart: &[u8]) -> Option<Vec<u8>> {
-        let elf = Elf::from_bytes(cart).ok()?;
-        let mut buf = vec![0; 0x1FF_FFFF];
-
-        for header in elf
-            .section_header_iter()
-            .filter(|h| (0x800_0000..=0x9FF_FFFF).contains(&h.addr()))
-        {
-            let dst_offs = header.addr() - 0x800_0000;
-            for (src, dst) in header
-                .content()
-                .iter()
-                .zip(buf.iter_mut().skip(dst_offs as usize))
-            {
-                *dst = *src;
-            }
-        }
-
-        Some(buf)
-    }
-
-    fn load_nds(&mut self, cart: Vec<u8>, _path: Option<PathBuf>, config: &SystemConfig) {
+    fn load_nds(
+        &mut self,
+        cart: Vec<u8>,
+        _path: Option<PathBuf>,
+        config: &SystemConfig,
+    ) -> Result<(), FirmwareError> {
+        let (bios7, bios9) = (
+            Firmware::NdsBios7.load(config)?,
+            Firmware::NdsBios9.load(config)?,
+        );
         let mut nds = Box::new(Nds::default());
         nds.config = config.clone();
+        nds.set_bios(bios7, bios9);
         nds.cart.load_rom(cart);
         nds.init_memory();
-        nds.options.frame_finished = mem::replace(
-            &mut self.options().frame_finished,
-            EmulateOptions::serde_frame_finished(),
-        );
+        nds.options.take_callbacks(self.options());
 
         *self = Self::NDS(nds);
+        Ok(())
     }
 }
 
@@ -388,28 +561,69 @@ impl Default for System {
 /// These can be changed at runtime.
 #[derive(Deserialize, Serialize)]
 pub struct EmulateOptions {
-    /// If the system is running. If false, any calls to [advance_delta] and
-    /// [produce_samples] do nothing.
+    /// If the system is running. If false, any calls to [advance_delta]
+    /// do nothing.
     pub running: bool,
     /// If there is a ROM loaded / cartridge inserted.
     pub rom_loaded: bool,
-    /// If the audio samples produced by [produce_samples] should be in reversed
+    /// If the audio samples returned by [take_samples] should be in reversed
     /// order. `true` while rewinding.
     pub invert_audio_samples: bool,
     /// Speed multiplier the system should run at.
-    /// ex. 1x is regular speed, 2x is double speed.
-    /// Affects [advance_delta] and sound sample output.
-    pub speed_multiplier: usize,
+    /// ex. 1x is regular speed, 2x is double speed, 0.5x is half speed.
+    /// Affects [advance_delta]; audio has to be resampled by the frontend.
+    pub speed_multiplier: f32,
     /// Called when a frame is finished rendering. (End of VBlank)
     #[serde(skip)]
     #[serde(default = "EmulateOptions::serde_frame_finished")]
     pub frame_finished: Box<dyn Fn(BorrowedSystem) + Send>,
+    /// Called when the cartridge's camera captures an image, to get the
+    /// image seen by the sensor. Only used by the GGC Pocket Camera.
+    /// See [CameraSensor] for the expected format.
+    #[serde(skip)]
+    #[serde(default = "EmulateOptions::serde_camera_image")]
+    pub camera_image: Box<dyn Fn() -> Vec<u8> + Send>,
+    /// Called when the cartridge's rumble motor is turned on or off.
+    #[serde(skip)]
+    #[serde(default = "EmulateOptions::serde_rumble")]
+    pub rumble: Box<dyn Fn(bool) + Send>,
+    /// Called with a message for the user, like a breakpoint being hit.
+    /// Frontends can show these on screen.
+    #[serde(skip)]
+    #[serde(default = "EmulateOptions::serde_notification")]
+    pub notification: Box<dyn Fn(String) + Send>,
 }
 
 impl EmulateOptions {
+    /// Move the frontend callbacks of the given options into these,
+    /// leaving defaults behind. Used when switching systems.
+    pub(crate) fn take_callbacks(&mut self, other: &mut Self) {
+        self.frame_finished = mem::replace(&mut other.frame_finished, Self::serde_frame_finished());
+        self.camera_image = mem::replace(&mut other.camera_image, Self::serde_camera_image());
+        self.rumble = mem::replace(&mut other.rumble, Self::serde_rumble());
+        self.notification = mem::replace(&mut other.notification, Self::serde_notification());
+    }
+
+    /// Send a message to the user, see [Self::notification].
+    pub fn notify(&self, message: impl Into<String>) {
+        (self.notification)(message.into());
+    }
+
     fn serde_frame_finished() -> Box<dyn Fn(BorrowedSystem) + Send> {
         Box::new(|_| ())
     }
+
+    fn serde_camera_image() -> Box<dyn Fn() -> Vec<u8> + Send> {
+        Box::new(CameraSensor::test_pattern)
+    }
+
+    fn serde_rumble() -> Box<dyn Fn(bool) + Send> {
+        Box::new(|_| ())
+    }
+
+    fn serde_notification() -> Box<dyn Fn(String) + Send> {
+        Box::new(|_| ())
+    }
 }
 
 impl Default for EmulateOptions {
@@ -418,8 +632,11 @@ impl Default for EmulateOptions {
             running: false,
             rom_loaded: false,
             invert_audio_samples: false,
-            speed_multiplier: 1,
-            frame_finished: Box::new(|_| ()),
+            speed_multiplier: 1.0,
+            frame_finished: Self::serde_frame_finished(),
+            camera_image: Self::serde_camera_image(),
+            rumble: Self::serde_rumble(),
+            notification: Self::serde_notification(),
         }
     }
 }
@@ -430,6 +647,13 @@ impl Default for EmulateOptions {
 pub struct SystemConfig {
     /// How to handle CGB mode.
     pub mode: CgbMode,
+    /// Hardware model to emulate for GG carts not running in CGB mode.
+    pub dmg_model: GgcModel,
+    /// Hardware model to emulate for GG carts running in CGB mode.
+    pub cgb_model: GgcModel,
+    /// If DMG models without a boot ROM dump should run the bundled Bootix
+    /// boot ROM instead of skipping the boot ROM.
+    pub ggc_bootix: bool,
     /// If save states should be compressed.
     pub compress_savestates: bool,
     /// If CGB colours should be corrected.
@@ -438,16 +662,35 @@ pub struct SystemConfig {
     pub volume: f32,
     /// If the interpreter should cache
     pub cached_interpreter: bool,
+    /// If GGA carts should always have an RTC, even if not detected.
+    pub force_gga_rtc: bool,
+    /// If the GGA should use the built-in high-level BIOS instead of
+    /// a BIOS dump. The HLE BIOS is also used if no dump is set.
+    pub gga_hle_bios: bool,
+    /// Colour profile to correct GGA colours with.
+    pub gga_colour_correction: ColourProfile,
+    /// User overrides for GGA cart properties, by game code.
+    pub gga_overrides: HashMap<String, CartOverride>,
+    /// Paths to firmware images supplied by the user.
+    pub firmware: HashMap<Firmware, PathBuf>,
 }
 
 impl Default for SystemConfig {
     fn default() -> Self {
         Self {
             mode: CgbMode::Prefer,
+            dmg_model: GgcModel::Dmg,
+            cgb_model: GgcModel::Cgb,
+            ggc_bootix: false,
             compress_savestates: false,
             cgb_colour_correction: false,
             volume: 0.5,
             cached_interpreter: true,
+            force_gga_rtc: false,
+            gga_hle_bios: false,
+            gga_colour_correction: ColourProfile::None,
+            gga_overrides: HashMap::new(),
+            firmware: HashMap::new(),
         }
     }
 }
@@ -465,6 +708,130 @@ pub enum CgbMode {
     Never,
 }
 
+/// Kind of system a file can be loaded on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SystemKind {
+    Ggc,
+    Gga,
+    Nds,
+    Psx,
+}
+
+impl SystemKind {
+    pub const ALL: [Self; 4] = [Self::Ggc, Self::Gga, Self::Nds, Self::Psx];
+
+    /// Detect the system a file is meant for. The file's header is checked
+    /// first, since it is the most reliable; if none matches, the file
+    /// extension is used.
+    /// Fails instead of guessing if neither matches.
+    pub fn detect(file: &[u8], path: Option<&Path>) -> Result<Self, LoadError> {
+        Self::from_contents(file)
+            .or_else(|| path.and_then(Self::from_extension))
+            .ok_or(LoadError::UnknownFormat)
+    }
+
+    /// Detect the system by magic values and header fields in the file.
+    pub fn from_contents(file: &[u8]) -> Option<Self> {
+        if file.starts_with(b"\x7FELF") {
+            // e_machine: ARM or MIPS
+            return match file.get(0x12..0x14) {
+                Some([40, 0]) => Some(Self::Gga),
+                Some([8, 0]) => Some(Self::Psx),
+                _ => None,
+            };
+        }
+        if file.starts_with(b"PS-X EXE") {
+            return Some(Self::Psx);
+        }
+
+        // The NDS header CRC is checked before the GGA header, since a
+        // single GGA byte can match by chance; the CRC much less so
+        let gga = CartInfo::gga(file);
+        if CartInfo::ggc(file).passed(CheckKind::Logo) {
+            Some(Self::Ggc)
+        } else if CartInfo::nds(file).passed(CheckKind::HeaderChecksum) {
+            Some(Self::Nds)
+        } else if gga.passed(CheckKind::Logo)
+            || (gga.passed(CheckKind::FixedValue) && gga.passed(CheckKind::HeaderChecksum))
+        {
+            Some(Self::Gga)
+        } else {
+            None
+        }
+    }
+
+    /// Detect the system by the extension of the given path.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "gb" | "gbc" | "cgb" | "sgb" => Some(Self::Ggc),
+            "gba" | "agb" | "mb" | "elf" => Some(Self::Gga),
+            "nds" | "srl" => Some(Self::Nds),
+            "exe" | "psx" | "psexe" => Some(Self::Psx),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SystemKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SystemKind::Ggc => "GG(C)",
+            SystemKind::Gga => "GGA",
+            SystemKind::Nds => "NDS",
+            SystemKind::Psx => "PSX",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Error when a file could not be loaded onto a system.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The system the file is meant for could not be detected.
+    UnknownFormat,
+    /// The file is meant for a system that cannot load files yet.
+    Unsupported(SystemKind),
+    /// The system needs firmware that could not be loaded.
+    Firmware(FirmwareError),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::UnknownFormat => write!(
+                f,
+                "Could not detect the system this file is for. Select one in the options."
+            ),
+            LoadError::Unsupported(kind) => write!(f, "Loading {kind} files is not supported yet."),
+            LoadError::Firmware(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<FirmwareError> for LoadError {
+    fn from(err: FirmwareError) -> Self {
+        Self::Firmware(err)
+    }
+}
+
+/// Error when a save state could not be loaded.
+#[derive(Debug)]
+pub struct InvalidState;
+
+impl Display for InvalidState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The state is invalid, or made by another version of gamegirl."
+        )
+    }
+}
+
+impl std::error::Error for InvalidState {}
+
 /// Borrowed system enum used for "end of frame" callbacks on all cores.
 /// These are mainly used for rewinding savestates.
 pub enum BorrowedSystem<'s> {
@@ -503,6 +870,9 @@ impl Button {
     ];
 }
 
+/// Magic number at the start of zstd-compressed data.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
 /// Serialize an object that can be loaded with [deserialize].
 /// It is (optionally zstd-compressed) bincode.
 pub fn serialize<T: Serialize>(thing: &T, with_zstd: bool) -> Vec<u8> {
@@ -531,3 +901,111 @@ pub fn deserialize<T: DeserializeOwned>(state: &[u8], with_zstd: bool) -> T {
         bincode::deserialize(state).unwrap()
     }
 }
+
+/// Deserialize an object that was made with [serialize], detecting
+/// compression automatically. Unlike [deserialize], returns `None` if the
+/// data is invalid, for data that might come from an older version or
+/// be corrupted, like files on disk.
+pub fn try_deserialize<T: DeserializeOwned>(state: &[u8]) -> Option<T> {
+    if state.starts_with(&ZSTD_MAGIC) {
+        let decoder = zstd::stream::Decoder::new(state).ok()?;
+        bincode::deserialize_from(decoder).ok()
+    } else {
+        bincode::deserialize(state).ok()
+    }
+}
+
+/// Hash the given data using 64-bit FNV-1a. Unlike the hasher in `std`,
+/// this is guaranteed to give the same result on every platform and build.
+pub fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, thread};
+
+    use crate::{
+        common::{LoadError, SystemKind},
+        components::{cart_info::crc16, debugger::Breakpoint},
+        ggc::GameGirl,
+        System,
+    };
+
+    /// A GGA header with the fixed value and a matching complement,
+    /// but without a logo.
+    fn gga_header() -> Vec<u8> {
+        let mut rom = vec![0; 0x200];
+        rom[0xB2] = 0x96;
+        rom[0xBD] = 0u8.wrapping_sub(0x96).wrapping_sub(0x19);
+        rom
+    }
+
+    #[test]
+    fn detect_gga_by_complement() {
+        assert_eq!(
+            SystemKind::from_contents(&gga_header()),
+            Some(SystemKind::Gga)
+        );
+
+        let mut rom = gga_header();
+        rom[0xBD] = 0;
+        assert_eq!(SystemKind::from_contents(&rom), None);
+    }
+
+    #[test]
+    fn detect_nds_before_gga() {
+        // A valid NDS header that happens to have the GGA fixed value
+        let mut rom = vec![0; 0x200];
+        rom[0xB2] = 0x96;
+        let crc = crc16(&rom[..0x15E]);
+        rom[0x15E..0x160].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(SystemKind::from_contents(&rom), Some(SystemKind::Nds));
+    }
+
+    #[test]
+    fn detect_does_not_guess() {
+        let rom = vec![0; 0x200];
+        assert!(matches!(
+            SystemKind::detect(&rom, None),
+            Err(LoadError::UnknownFormat)
+        ));
+        assert_eq!(
+            SystemKind::detect(&rom, Some(Path::new("homebrew.gba"))).ok(),
+            Some(SystemKind::Gga)
+        );
+    }
+
+    #[test]
+    fn run_ahead_skips_breakpoints() {
+        // Loading states needs a lot of stack space
+        let inner = || {
+            // A ROM of NOPs reaches the breakpoint after more than a frame
+            let mut gg = GameGirl::with_cart(vec![0; 0x8000]);
+            gg.debugger.breakpoints.push(Breakpoint {
+                value: Some(0x6000),
+                pc: true,
+                ..Breakpoint::default()
+            });
+            let mut gg = System::GGC(Box::new(gg));
+
+            gg.advance_run_ahead(1.0 / 60.0, 2);
+            assert!(gg.options().running);
+            assert_eq!(gg.as_ggc().debugger.breakpoints[0].hit_count, 0);
+
+            while gg.options().running {
+                gg.advance_run_ahead(1.0 / 60.0, 2);
+            }
+            assert_eq!(gg.as_ggc().cpu.pc, 0x6000);
+            assert_eq!(gg.as_ggc().debugger.breakpoints[0].hit_count, 1);
+        };
+        thread::Builder::new()
+            .stack_size(32 << 20)
+            .spawn(inner)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+}
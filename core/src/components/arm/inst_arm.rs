@@ -80,8 +80,9 @@ impl<S: ArmSystem> SysWrapper<S> {
         }
     }
 
-    pub(crate) fn arm_swi(&mut self, _inst: ArmInst) {
-        self.swi();
+    pub(crate) fn arm_swi(&mut self, inst: ArmInst) {
+        // The BIOS only looks at the upper 8 bits of the comment
+        self.swi((inst.0 >> 16).u8());
     }
 
     pub(crate) fn arm_alu_mul_psr_reg<const OP: u16, const CPSR: bool>(&mut self, inst: ArmInst) {
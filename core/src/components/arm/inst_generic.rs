@@ -13,8 +13,10 @@ use crate::{
 };
 
 impl<S: ArmSystem> SysWrapper<S> {
-    pub fn swi(&mut self) {
-        Cpu::exception_occurred(self, Exception::Swi);
+    pub fn swi(&mut self, comment: u8) {
+        if !S::handle_swi(self, comment) {
+            Cpu::exception_occurred(self, Exception::Swi);
+        }
     }
 
     pub fn und_inst<T: UpperHex>(&mut self, code: T) {
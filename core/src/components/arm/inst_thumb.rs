@@ -417,8 +417,8 @@ impl<S: ArmSystem> SysWrapper<S> {
     }
 
     // THUMB.17
-    pub(crate) fn thumb_swi(&mut self, _inst: ThumbInst) {
-        self.swi();
+    pub(crate) fn thumb_swi(&mut self, inst: ThumbInst) {
+        self.swi(inst.0.u8());
     }
 
     // THUMB.18
@@ -8,7 +8,10 @@ use std::ops::{Deref, DerefMut, IndexMut};
 
 use super::Exception;
 use crate::{
-    components::arm::{inst_arm::ArmLut, inst_thumb::ThumbLut, Access, Cpu},
+    components::{
+        arm::{inst_arm::ArmLut, inst_thumb::ThumbLut, Access, Cpu},
+        debugger::AccessKind,
+    },
     numutil::NumExt,
 };
 
@@ -30,6 +33,12 @@ pub trait ArmSystem: IndexMut<u32, Output = u16> + Sized + 'static {
     fn exception_happened(&mut self, kind: Exception);
     fn pipeline_stalled(&mut self);
 
+    /// Handle a software interrupt with the given comment using high-level
+    /// emulation. Returns false if the BIOS should handle it instead.
+    fn handle_swi(_gg: &mut SysWrapper<Self>, _comment: u8) -> bool {
+        false
+    }
+
     fn get<T: RwType>(&mut self, addr: u32) -> T;
     fn set<T: RwType>(&mut self, addr: u32, value: T);
     fn wait_time<T: RwType>(&mut self, addr: u32, access: Access) -> u16;
@@ -38,6 +47,7 @@ pub trait ArmSystem: IndexMut<u32, Output = u16> + Sized + 'static {
         let time = self.wait_time::<T>(addr, access);
         self.add_sn_cycles(time);
         let value = self.get::<T>(addr).u32();
+        self.check_watchpoint(AccessKind::Read, addr, value);
         T::ReadOutput::from_u32(if !Self::IS_V5 && T::WIDTH == 2 {
             // Special handling for halfwords on ARMv4
             if addr.is_bit(0) {
@@ -53,6 +63,7 @@ pub trait ArmSystem: IndexMut<u32, Output = u16> + Sized + 'static {
     fn write<T: RwType>(&mut self, addr: u32, value: T, access: Access) {
         let time = self.wait_time::<T>(addr, access);
         self.add_sn_cycles(time);
+        self.check_watchpoint(AccessKind::Write, addr, value.u32());
         self.set(addr, value);
     }
 
@@ -63,7 +74,12 @@ pub trait ArmSystem: IndexMut<u32, Output = u16> + Sized + 'static {
         panic!("CP15 unsupported!");
     }
 
+    /// Check execute breakpoints at the current PC.
+    /// Returns if the next instruction should be executed.
     fn check_debugger(&mut self) -> bool;
+    /// Called on data reads and writes of the CPU (not instruction fetches),
+    /// to check watchpoints.
+    fn check_watchpoint(&mut self, _kind: AccessKind, _addr: u32, _value: u32) {}
     fn can_cache_at(addr: u32) -> bool;
 }
 
@@ -79,6 +95,7 @@ impl<S: ArmSystem> SysWrapper<S> {
         let time = self.wait_time::<u16>(addr, kind);
         self.add_sn_cycles(time);
         let val = self.get::<u16>(addr).u32();
+        self.check_watchpoint(AccessKind::Read, addr, val);
         if !S::IS_V5 && addr.is_bit(0) {
             // Unaligned on ARMv4
             (val >> 8) as i8 as i16 as u32
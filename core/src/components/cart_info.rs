@@ -0,0 +1,380 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::{Display, Formatter, UpperHex};
+
+use CheckKind::*;
+
+use crate::numutil::NumExt;
+
+/// Logo every GG(C) cart has in its header.
+const GGC_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+/// Logo every GGA cart has in its header.
+const GGA_LOGO: [u8; 156] = [
+    0x24, 0xFF, 0xAE, 0x51, 0x69, 0x9A, 0xA2, 0x21, 0x3D, 0x84, 0x82, 0x0A, 0x84, 0xE4, 0x09, 0xAD,
+    0x11, 0x24, 0x8B, 0x98, 0xC0, 0x81, 0x7F, 0x21, 0xA3, 0x52, 0xBE, 0x19, 0x93, 0x09, 0xCE, 0x20,
+    0x10, 0x46, 0x4A, 0x4A, 0xF8, 0x27, 0x31, 0xEC, 0x58, 0xC7, 0xE8, 0x33, 0x82, 0xE3, 0xCE, 0xBF,
+    0x85, 0xF4, 0xDF, 0x94, 0xCE, 0x4B, 0x09, 0xC1, 0x94, 0x56, 0x8A, 0xC0, 0x13, 0x72, 0xA7, 0xFC,
+    0x9F, 0x84, 0x4D, 0x73, 0xA3, 0xCA, 0x9A, 0x61, 0x58, 0x97, 0xA3, 0x27, 0xFC, 0x03, 0x98, 0x76,
+    0x23, 0x1D, 0xC7, 0x61, 0x03, 0x04, 0xAE, 0x56, 0xBF, 0x38, 0x84, 0x00, 0x40, 0xA7, 0x0E, 0xFD,
+    0xFF, 0x52, 0xFE, 0x03, 0x6F, 0x95, 0x30, 0xF1, 0x97, 0xFB, 0xC0, 0x85, 0x60, 0xD6, 0x80, 0x25,
+    0xA9, 0x63, 0xBE, 0x03, 0x01, 0x4E, 0x38, 0xE2, 0xF9, 0xA2, 0x34, 0xFF, 0xBB, 0x3E, 0x03, 0x44,
+    0x78, 0x00, 0x90, 0xCB, 0x88, 0x11, 0x3A, 0x94, 0x65, 0xC0, 0x7C, 0x63, 0x87, 0xF0, 0x3C, 0xAF,
+    0xD6, 0x25, 0xE4, 0x8B, 0x38, 0x0A, 0xAC, 0x72, 0x21, 0xD4, 0xF8, 0x07,
+];
+
+/// CRC16 of the logo in NDS cart headers.
+const NDS_LOGO_CRC: u16 = 0xCF56;
+
+/// Information read from a cart's header, together with the results of
+/// verifying it.
+#[derive(Debug, Clone, Default)]
+pub struct CartInfo {
+    /// Title of the game.
+    pub title: String,
+    /// Game code, on systems that have one.
+    pub game_code: Option<String>,
+    /// Code of the game's publisher.
+    pub maker: String,
+    /// Revision of the game.
+    pub version: u8,
+    /// Results of verifying the header.
+    pub checks: Vec<HeaderCheck>,
+}
+
+impl CartInfo {
+    /// Parse and verify a GG(C) cart header.
+    pub fn ggc(rom: &[u8]) -> Self {
+        let header = Self::header::<0x150>(rom);
+        let old_licensee = header[0x14B];
+        let new_licensee = &header[0x144..0x146];
+        let maker = if old_licensee == 0x33 {
+            String::from_utf8_lossy(new_licensee).to_string()
+        } else {
+            format!("{old_licensee:02X}")
+        };
+
+        let header_checksum = header[0x134..=0x14C]
+            .iter()
+            .fold(0u8, |sum, b| sum.wrapping_sub(*b).wrapping_sub(1));
+        let global_checksum = rom
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 0x14E && *i != 0x14F)
+            .fold(0u16, |sum, (_, b)| sum.wrapping_add(b.u16()));
+        let expected_global = u16::from_be_bytes([header[0x14E], header[0x14F]]);
+
+        // The SGB only accepts carts using the new licensee code
+        let sgb = header[0x146] == 0x03;
+        let licensee_valid = match old_licensee {
+            0x33 => new_licensee.iter().all(u8::is_ascii_alphanumeric),
+            _ => !sgb,
+        };
+
+        let rom_code = header[0x148];
+        let rom_size = (rom_code <= 8).then(|| 0x8000_usize << rom_code);
+        let ram_code = header[0x149];
+        // MBC2 and MBC7 have RAM/EEPROM built into the controller
+        let has_ram = matches!(
+            header[0x147],
+            0x02 | 0x03
+                | 0x08
+                | 0x09
+                | 0x0C
+                | 0x0D
+                | 0x10
+                | 0x12
+                | 0x13
+                | 0x1A
+                | 0x1B
+                | 0x1D
+                | 0x1E
+                | 0xFC
+                | 0xFE
+                | 0xFF
+        );
+
+        let checks = vec![
+            HeaderCheck::new(Logo, header[0x104..0x134] == GGC_LOGO, String::new()),
+            HeaderCheck::compare(HeaderChecksum, header_checksum, header[0x14D]),
+            HeaderCheck::compare(GlobalChecksum, global_checksum, expected_global),
+            HeaderCheck::new(
+                Licensee,
+                licensee_valid,
+                format!("Old code {old_licensee:02X}, SGB support: {sgb}"),
+            ),
+            HeaderCheck::new(
+                RomSize,
+                rom_size == Some(rom.len()),
+                format!(
+                    "Header: {} KiB, ROM: {} KiB",
+                    rom_size.unwrap_or(0) / 1024,
+                    rom.len() / 1024
+                ),
+            ),
+            HeaderCheck::new(
+                RamSize,
+                matches!(ram_code, 0 | 2..=5) && has_ram == (ram_code != 0),
+                format!("Size code {ram_code:02X}, cart type {:02X}", header[0x147]),
+            ),
+        ];
+
+        Self {
+            title: Self::string(&header[0x134..0x144]),
+            game_code: None,
+            maker,
+            version: header[0x14C],
+            checks,
+        }
+    }
+
+    /// Parse and verify a GGA cart header.
+    pub fn gga(rom: &[u8]) -> Self {
+        let header = Self::header::<0xC0>(rom);
+        let complement = header[0xA0..=0xBC]
+            .iter()
+            .fold(0u8, |sum, b| sum.wrapping_sub(*b))
+            .wrapping_sub(0x19);
+
+        let checks = vec![
+            HeaderCheck::new(Logo, header[0x04..0xA0] == GGA_LOGO, String::new()),
+            HeaderCheck::compare(FixedValue, 0x96, header[0xB2]),
+            HeaderCheck::compare(HeaderChecksum, complement, header[0xBD]),
+        ];
+
+        Self {
+            title: Self::string(&header[0xA0..0xAC]),
+            game_code: Some(Self::string(&header[0xAC..0xB0])),
+            maker: Self::string(&header[0xB0..0xB2]),
+            version: header[0xBC],
+            checks,
+        }
+    }
+
+    /// Parse and verify an NDS cart header.
+    pub fn nds(rom: &[u8]) -> Self {
+        let header = Self::header::<0x160>(rom);
+        let logo_crc = u16::from_le_bytes([header[0x15C], header[0x15D]]);
+        let header_crc = u16::from_le_bytes([header[0x15E], header[0x15F]]);
+
+        let checks = vec![
+            HeaderCheck::new(
+                Logo,
+                logo_crc == NDS_LOGO_CRC && crc16(&header[0xC0..0x15C]) == NDS_LOGO_CRC,
+                String::new(),
+            ),
+            HeaderCheck::compare(HeaderChecksum, crc16(&header[..0x15E]), header_crc),
+        ];
+
+        Self {
+            title: Self::string(&header[0x00..0x0C]),
+            game_code: Some(Self::string(&header[0x0C..0x10])),
+            maker: Self::string(&header[0x10..0x12]),
+            version: header[0x1E],
+            checks,
+        }
+    }
+
+    /// If all checks passed.
+    pub fn valid(&self) -> bool {
+        self.checks.iter().all(|c| c.passed)
+    }
+
+    /// If the given check was done and passed.
+    pub fn passed(&self, kind: CheckKind) -> bool {
+        self.checks.iter().any(|c| c.kind == kind && c.passed)
+    }
+
+    /// Copy the header out of the ROM, zero-filled if the ROM is too small.
+    fn header<const LEN: usize>(rom: &[u8]) -> [u8; LEN] {
+        let mut header = [0; LEN];
+        let len = rom.len().min(LEN);
+        header[..len].copy_from_slice(&rom[..len]);
+        header
+    }
+
+    /// Read a string from the header, which ends at the first
+    /// unprintable character.
+    fn string(bytes: &[u8]) -> String {
+        bytes
+            .iter()
+            .take_while(|b| b.is_ascii_graphic() || **b == b' ')
+            .map(|b| *b as char)
+            .collect()
+    }
+}
+
+/// Result of verifying a part of a cart header.
+#[derive(Debug, Clone)]
+pub struct HeaderCheck {
+    pub kind: CheckKind,
+    pub passed: bool,
+    /// Details about the checked values.
+    pub detail: String,
+}
+
+impl HeaderCheck {
+    fn new(kind: CheckKind, passed: bool, detail: String) -> Self {
+        Self {
+            kind,
+            passed,
+            detail,
+        }
+    }
+
+    /// Check a value in the header against the expected one.
+    fn compare<T: Copy + PartialEq + UpperHex>(kind: CheckKind, expected: T, found: T) -> Self {
+        let detail = format!("Expected {expected:02X}, found {found:02X}");
+        Self::new(kind, expected == found, detail)
+    }
+}
+
+/// Parts of a cart header that are verified.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CheckKind {
+    /// The logo shown (and checked) by the boot ROM.
+    Logo,
+    /// Checksum or complement over the header.
+    HeaderChecksum,
+    /// Checksum over the entire ROM.
+    GlobalChecksum,
+    /// Licensee code, with the new code being used when required.
+    Licensee,
+    /// ROM size matching the one given in the header.
+    RomSize,
+    /// RAM size being valid for the cart type.
+    RamSize,
+    /// Byte that has a fixed value on every cart.
+    FixedValue,
+}
+
+impl Display for CheckKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Logo => "Logo",
+            HeaderChecksum => "Header checksum",
+            GlobalChecksum => "Global checksum",
+            Licensee => "Licensee code",
+            RomSize => "ROM size",
+            RamSize => "RAM size",
+            FixedValue => "Fixed value",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// CRC16 as used in NDS cart headers.
+pub(crate) fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |mut crc, byte| {
+        crc ^= byte.u16();
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::cart_info::{CartInfo, CheckKind::*, GGC_LOGO};
+
+    /// A 32 KiB GG ROM with the title "TEST" and correct checksums.
+    fn ggc_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x104..0x134].copy_from_slice(&GGC_LOGO);
+        rom[0x134..0x138].copy_from_slice(b"TEST");
+        rom[0x14D] = 0xA7;
+        rom[0x14E] = 0x17;
+        rom[0x14F] = 0x2D;
+        rom
+    }
+
+    #[test]
+    fn ggc_valid() {
+        let info = CartInfo::ggc(&ggc_rom());
+        assert_eq!(info.title, "TEST");
+        assert!(info.passed(HeaderChecksum));
+        assert!(info.passed(GlobalChecksum));
+        assert!(info.valid());
+    }
+
+    #[test]
+    fn ggc_bad_header_checksum() {
+        let mut rom = ggc_rom();
+        rom[0x14D] = 0xA6;
+        rom[0x14F] = 0x2C;
+        let info = CartInfo::ggc(&rom);
+        assert!(!info.passed(HeaderChecksum));
+        assert!(info.passed(GlobalChecksum));
+    }
+
+    #[test]
+    fn ggc_bad_global_checksum() {
+        let mut rom = ggc_rom();
+        rom[0x4000] = 1;
+        let info = CartInfo::ggc(&rom);
+        assert!(info.passed(HeaderChecksum));
+        assert!(!info.passed(GlobalChecksum));
+        assert!(!info.valid());
+    }
+
+    #[test]
+    fn ggc_huc3_has_ram() {
+        let mut rom = ggc_rom();
+        rom[0x147] = 0xFE;
+        rom[0x149] = 0x03;
+        assert!(CartInfo::ggc(&rom).passed(RamSize));
+        rom[0x149] = 0x00;
+        assert!(!CartInfo::ggc(&rom).passed(RamSize));
+    }
+
+    /// A GGA header with the title "TEST", the fixed value and a correct
+    /// complement, but no logo.
+    fn gga_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x200];
+        rom[0xA0..0xA4].copy_from_slice(b"TEST");
+        rom[0xB2] = 0x96;
+        rom[0xBD] = 0x11;
+        rom
+    }
+
+    #[test]
+    fn gga_fixed_value_and_complement() {
+        let info = CartInfo::gga(&gga_rom());
+        assert_eq!(info.title, "TEST");
+        assert!(info.passed(FixedValue));
+        assert!(info.passed(HeaderChecksum));
+        assert!(!info.passed(Logo));
+    }
+
+    #[test]
+    fn gga_bad_complement() {
+        let mut rom = gga_rom();
+        rom[0xBC] = 1;
+        let info = CartInfo::gga(&rom);
+        assert!(info.passed(FixedValue));
+        assert!(!info.passed(HeaderChecksum));
+    }
+
+    #[test]
+    fn gga_bad_fixed_value() {
+        let mut rom = gga_rom();
+        rom[0xB2] = 0x00;
+        rom[0xBD] = 0xA7;
+        let info = CartInfo::gga(&rom);
+        assert!(!info.passed(FixedValue));
+        assert!(info.passed(HeaderChecksum));
+    }
+}
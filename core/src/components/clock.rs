@@ -0,0 +1,16 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current time in seconds since the UNIX epoch.
+/// This is the clock source used by all cartridge RTCs.
+pub fn since_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
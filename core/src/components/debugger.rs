@@ -4,6 +4,15 @@
 // If a copy of the MPL2 was not distributed with this file, you can
 // obtain one at https://mozilla.org/MPL/2.0/.
 
+use std::{
+    collections::VecDeque,
+    fmt::{Display, Formatter, UpperHex},
+    mem,
+};
+
+/// Maximum amount of messages kept in [Debugger::trace_log].
+const TRACE_LOG_LEN: usize = 500;
+
 /// Debugger info that is required to be known by the system.
 /// Is generic over GGC/GGA; generic type Ptr is pointer size
 /// on the current system (u16/u32)
@@ -14,61 +23,423 @@ pub struct Debugger<Ptr: PartialEq + Clone + Copy> {
     pub serial_output: String,
     /// A list of breakpoints the system should stop on.
     pub breakpoints: Vec<Breakpoint<Ptr>>,
-    /// If a breakpoint was hit.
-    pub is_breakpoint_hit: bool,
-    /// The hit breakpoint.
-    pub breakpoint_hit: Option<Breakpoint<Ptr>>,
+    /// The last breakpoint that was hit, if not yet handled.
+    pub breakpoint_hit: Option<BreakpointHit<Ptr>>,
+    /// Messages logged by tracepoints, oldest first.
+    pub trace_log: VecDeque<String>,
+    /// PC of the instruction the last execute breakpoint was hit at.
+    /// It is executed without checking breakpoints again when emulation
+    /// continues, so that it does not stop on the same breakpoint forever.
+    resume_pc: Option<Ptr>,
 }
 
 impl<Ptr: PartialEq + Clone + Copy> Debugger<Ptr> {
-    /// Called before a memory write is executed, which might trigger a BP.
-    /// Returns if emulation should continue.
-    pub fn write_occurred(&mut self, addr: Ptr) -> bool {
-        if self.breakpoints.is_empty() {
-            return true;
+    /// Add a message to the trace log, dropping the oldest one if full.
+    fn trace(&mut self, message: String) {
+        log::info!("{message}");
+        if self.trace_log.len() == TRACE_LOG_LEN {
+            self.trace_log.pop_front();
         }
+        self.trace_log.push_back(message);
+    }
+}
 
-        let bp = self
-            .breakpoints
-            .iter()
-            .find(|bp| bp.value == Some(addr) && bp.write);
-        if let Some(bp) = bp {
-            self.breakpoint_hit = Some(bp.clone());
-            self.is_breakpoint_hit = true;
-        }
+/// A system that can be debugged with breakpoints.
+/// The `check_*` functions are called by the system on accesses and return
+/// if emulation should continue.
+pub trait Debuggable {
+    /// Pointer size of the system.
+    type Ptr: PartialOrd + Copy + Default + UpperHex + TryFrom<u32>;
+    /// Names of the registers breakpoint conditions can use, in lowercase.
+    const REGISTERS: &'static [&'static str];
 
-        !self.is_breakpoint_hit
-    }
+    fn debugger(&self) -> &Debugger<Self::Ptr>;
+    fn debugger_mut(&mut self) -> &mut Debugger<Self::Ptr>;
+
+    /// Value of the register at the given index in [Self::REGISTERS].
+    fn register(&self, idx: usize) -> u32;
+    /// Read a byte of memory without any side effects.
+    fn peek(&self, addr: u32) -> u8;
 
     /// Called before an instruction is executed, which might trigger a BP.
     /// If it does, function returns false and inst should not be executed.
-    pub fn should_execute(&mut self, pc: Ptr) -> bool {
-        if self.breakpoints.is_empty() {
+    #[inline]
+    fn check_execute(&mut self, pc: Self::Ptr) -> bool {
+        if self.debugger().breakpoints.is_empty() {
+            return true;
+        }
+        if self.debugger_mut().resume_pc.take() == Some(pc) {
             return true;
         }
 
-        let bp = self
-            .breakpoints
-            .iter()
-            .find(|bp| bp.value == Some(pc) && bp.pc);
-        if let Some(bp) = bp {
-            self.breakpoint_hit = Some(bp.clone());
-            self.is_breakpoint_hit = true;
+        let execute = self.check_access(AccessKind::Execute, pc, 0);
+        if !execute {
+            self.debugger_mut().resume_pc = Some(pc);
         }
-        !self.is_breakpoint_hit
+        execute
+    }
+
+    /// Called after a memory read by the CPU, which might trigger a BP.
+    #[inline]
+    fn check_read(&mut self, addr: Self::Ptr, value: u32) -> bool {
+        self.debugger().breakpoints.is_empty() || self.check_access(AccessKind::Read, addr, value)
+    }
+
+    /// Called before a memory write by the CPU, which might trigger a BP.
+    #[inline]
+    fn check_write(&mut self, addr: Self::Ptr, value: u32) -> bool {
+        self.debugger().breakpoints.is_empty() || self.check_access(AccessKind::Write, addr, value)
+    }
+
+    /// Check all breakpoints against an access, counting hits and logging
+    /// tracepoints. `value` is the value read or written.
+    fn check_access(&mut self, kind: AccessKind, addr: Self::Ptr, value: u32) -> bool {
+        let mut cont = true;
+        for idx in 0..self.debugger().breakpoints.len() {
+            let bp = &self.debugger().breakpoints[idx];
+            if !bp.triggers_on(kind, addr) {
+                continue;
+            }
+            if let Some(condition) = &bp.condition {
+                if !condition.eval(self, value) {
+                    continue;
+                }
+            }
+
+            let dbg = self.debugger_mut();
+            let bp = &mut dbg.breakpoints[idx];
+            bp.hit_count += 1;
+            if bp.hit_count <= bp.ignore_count {
+                continue;
+            }
+
+            if bp.log_only {
+                let width = mem::size_of::<Self::Ptr>() * 2;
+                let message = match kind {
+                    AccessKind::Execute => format!("{kind} at {addr:0width$X}"),
+                    _ => format!("{kind} at {addr:0width$X}: {value:X}"),
+                };
+                dbg.trace(message);
+            } else {
+                dbg.breakpoint_hit = Some(BreakpointHit {
+                    breakpoint: bp.clone(),
+                    kind,
+                    addr,
+                });
+                cont = false;
+            }
+        }
+        cont
     }
 }
 
-/// A breakpoint.
+/// A breakpoint. Triggers on accesses of the enabled kinds to an address
+/// or range of addresses, if its condition holds.
 #[derive(Clone, Debug, Default)]
 pub struct Breakpoint<Ptr> {
-    /// Address/value that this breakpoint is at.
+    /// Address that this breakpoint is at, or the start of its range.
     pub value: Option<Ptr>,
-    /// String representation of the address/value; used by egui as a text
+    /// String representation of the address; used by egui as a text
     /// buffer. TODO: kinda unclean to have GUI state here...
     pub value_text: String,
+    /// Inclusive end of the range of addresses, if not a single address.
+    pub end: Option<Ptr>,
+    /// String representation of the end address.
+    pub end_text: String,
     /// If this breakpoint triggers on the PC.
     pub pc: bool,
+    /// If this breakpoint triggers on a read.
+    pub read: bool,
     /// If this breakpoint triggers on a write.
     pub write: bool,
+    /// Condition that needs to hold for this breakpoint to trigger.
+    pub condition: Option<Condition>,
+    /// String representation of the condition.
+    pub condition_text: String,
+    /// Amount of hits to ignore before this breakpoint triggers.
+    pub ignore_count: u32,
+    /// Amount of times this breakpoint was hit, including ignored hits.
+    pub hit_count: u32,
+    /// If this is a tracepoint, which only logs hits to
+    /// [Debugger::trace_log] instead of stopping.
+    pub log_only: bool,
+}
+
+impl<Ptr: PartialOrd + Copy> Breakpoint<Ptr> {
+    /// If this breakpoint triggers on the given access, not considering
+    /// its condition. Breakpoints with an invalid condition never trigger.
+    pub fn triggers_on(&self, kind: AccessKind, addr: Ptr) -> bool {
+        let enabled = match kind {
+            AccessKind::Execute => self.pc,
+            AccessKind::Read => self.read,
+            AccessKind::Write => self.write,
+        } && !self.condition_invalid();
+        match self.value {
+            Some(start) => enabled && start <= addr && addr <= self.end.unwrap_or(start),
+            None => false,
+        }
+    }
+
+    /// If a condition was entered, but could not be parsed.
+    pub fn condition_invalid(&self) -> bool {
+        self.condition.is_none() && !self.condition_text.trim().is_empty()
+    }
+}
+
+/// A breakpoint that was hit.
+#[derive(Clone, Debug)]
+pub struct BreakpointHit<Ptr> {
+    /// The breakpoint at the time it was hit.
+    pub breakpoint: Breakpoint<Ptr>,
+    /// Kind of access that triggered it.
+    pub kind: AccessKind,
+    /// Address that was accessed.
+    pub addr: Ptr,
+}
+
+/// Kinds of accesses a breakpoint can trigger on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Execute,
+    Read,
+    Write,
+}
+
+impl Display for AccessKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Execute => "Execute",
+            Self::Read => "Read",
+            Self::Write => "Write",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A condition of a breakpoint, comparing a register, memory or the
+/// accessed value to a number, like `r0 == 5` or `0x2000100 != 0`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub operand: Operand,
+    pub comparison: Comparison,
+    pub number: u32,
+}
+
+impl Condition {
+    /// Parse a condition in the form `<operand> <comparison> <number>`.
+    /// Operands are the register names given, `value` for the value read or
+    /// written, or a memory address with an optional width in bits, like
+    /// `0x2000100:16`. Numbers are decimal or hex prefixed with `0x`.
+    pub fn parse(text: &str, registers: &[&str]) -> Option<Self> {
+        let op_start = text.find(['=', '!', '<', '>'])?;
+        let (operand, rest) = text.split_at(op_start);
+        let (comparison, number) = match rest.get(..2) {
+            Some("==") => (Comparison::Eq, &rest[2..]),
+            Some("!=") => (Comparison::Ne, &rest[2..]),
+            Some("<=") => (Comparison::Le, &rest[2..]),
+            Some(">=") => (Comparison::Ge, &rest[2..]),
+            _ if rest.starts_with('<') => (Comparison::Lt, &rest[1..]),
+            _ if rest.starts_with('>') => (Comparison::Gt, &rest[1..]),
+            _ => return None,
+        };
+
+        let operand = operand.trim().to_lowercase();
+        let operand = if operand == "value" {
+            Operand::Value
+        } else if let Some(idx) = registers.iter().position(|r| *r == operand) {
+            Operand::Register(idx)
+        } else {
+            let (addr, width) = match operand.split_once(':') {
+                Some((addr, "8")) => (addr, 1),
+                Some((addr, "16")) => (addr, 2),
+                Some((addr, "32")) => (addr, 4),
+                Some(_) => return None,
+                None => (operand.as_str(), 1),
+            };
+            Operand::Memory {
+                addr: parse_number(addr)?,
+                width,
+            }
+        };
+
+        Some(Self {
+            operand,
+            comparison,
+            number: parse_number(number)?,
+        })
+    }
+
+    /// Evaluate the condition, given the value read or written.
+    fn eval<S: Debuggable + ?Sized>(&self, sys: &S, value: u32) -> bool {
+        let lhs = match self.operand {
+            Operand::Value => value,
+            Operand::Register(idx) => sys.register(idx),
+            Operand::Memory { addr, width } => (0..width).rev().fold(0, |acc, offs| {
+                (acc << 8) | sys.peek(addr.wrapping_add(offs)) as u32
+            }),
+        };
+        match self.comparison {
+            Comparison::Eq => lhs == self.number,
+            Comparison::Ne => lhs != self.number,
+            Comparison::Lt => lhs < self.number,
+            Comparison::Le => lhs <= self.number,
+            Comparison::Gt => lhs > self.number,
+            Comparison::Ge => lhs >= self.number,
+        }
+    }
+}
+
+/// Left side of a [Condition].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+    /// The value read or written; 0 for execute breakpoints.
+    Value,
+    /// Register, by index into [Debuggable::REGISTERS].
+    Register(usize),
+    /// Little-endian value in memory, with a width of 1, 2 or 4 bytes.
+    Memory { addr: u32, width: u32 },
+}
+
+/// Comparison done by a [Condition].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Parse a decimal number or a hex number prefixed with `0x`.
+fn parse_number(text: &str) -> Option<u32> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::debugger::{
+        AccessKind, Breakpoint, Comparison, Condition, Debuggable, Debugger, Operand,
+    };
+
+    const REGISTERS: &[&str] = &["r0", "sp"];
+
+    /// System with r0 = 5, sp = 0x100, and memory holding the low byte of
+    /// each address.
+    #[derive(Default)]
+    struct Mock(Debugger<u32>);
+
+    impl Debuggable for Mock {
+        type Ptr = u32;
+        const REGISTERS: &'static [&'static str] = REGISTERS;
+
+        fn debugger(&self) -> &Debugger<u32> {
+            &self.0
+        }
+
+        fn debugger_mut(&mut self) -> &mut Debugger<u32> {
+            &mut self.0
+        }
+
+        fn register(&self, idx: usize) -> u32 {
+            [5, 0x100][idx]
+        }
+
+        fn peek(&self, addr: u32) -> u8 {
+            addr as u8
+        }
+    }
+
+    fn parse(text: &str) -> Option<Condition> {
+        Condition::parse(text, REGISTERS)
+    }
+
+    fn breakpoint(start: u32, end: Option<u32>, condition: &str) -> Breakpoint<u32> {
+        Breakpoint {
+            value: Some(start),
+            end,
+            pc: true,
+            condition: parse(condition),
+            condition_text: condition.to_string(),
+            ..Breakpoint::default()
+        }
+    }
+
+    #[test]
+    fn parse_operands() {
+        let memory = |addr, width| Operand::Memory { addr, width };
+        assert_eq!(parse("R0 == 5").unwrap().operand, Operand::Register(0));
+        assert_eq!(parse("value>1").unwrap().operand, Operand::Value);
+        assert_eq!(parse("0x40 == 1").unwrap().operand, memory(0x40, 1));
+        assert_eq!(parse("0x40:8 == 1").unwrap().operand, memory(0x40, 1));
+        assert_eq!(parse("0x40:16 == 1").unwrap().operand, memory(0x40, 2));
+        assert_eq!(parse("64:32 == 1").unwrap().operand, memory(0x40, 4));
+
+        assert_eq!(parse("r9 == 1"), None);
+        assert_eq!(parse("0x40:12 == 1"), None);
+        assert_eq!(parse("0xZZ == 1"), None);
+    }
+
+    #[test]
+    fn parse_comparisons() {
+        let comparison = |text| parse(text).unwrap().comparison;
+        assert_eq!(comparison("r0 == 1"), Comparison::Eq);
+        assert_eq!(comparison("r0 != 1"), Comparison::Ne);
+        assert_eq!(comparison("r0 <= 1"), Comparison::Le);
+        assert_eq!(comparison("r0 < 1"), Comparison::Lt);
+        assert_eq!(comparison("r0 >= 1"), Comparison::Ge);
+        assert_eq!(comparison("r0 > 1"), Comparison::Gt);
+        assert_eq!(parse("r0 = 1"), None);
+        assert_eq!(parse("r0 1"), None);
+    }
+
+    #[test]
+    fn parse_numbers() {
+        assert_eq!(parse("r0 == 0x1F").unwrap().number, 0x1F);
+        assert_eq!(parse("r0 == 0X1f").unwrap().number, 0x1F);
+        assert_eq!(parse("r0 == 31").unwrap().number, 31);
+        assert_eq!(parse("r0 == 1F"), None);
+        assert_eq!(parse("r0 =="), None);
+    }
+
+    #[test]
+    fn triggers_on_range() {
+        let bp = breakpoint(0x10, Some(0x20), "");
+        assert!(!bp.triggers_on(AccessKind::Execute, 0xF));
+        assert!(bp.triggers_on(AccessKind::Execute, 0x10));
+        assert!(bp.triggers_on(AccessKind::Execute, 0x20));
+        assert!(!bp.triggers_on(AccessKind::Execute, 0x21));
+        assert!(!bp.triggers_on(AccessKind::Read, 0x10));
+
+        // An end before the start matches nothing
+        let bp = breakpoint(0x20, Some(0x10), "");
+        assert!((0..0x30).all(|addr| !bp.triggers_on(AccessKind::Execute, addr)));
+    }
+
+    #[test]
+    fn invalid_condition_disables() {
+        let bp = breakpoint(0x10, None, "r9 == 1");
+        assert!(bp.condition_invalid());
+        assert!(!bp.triggers_on(AccessKind::Execute, 0x10));
+    }
+
+    #[test]
+    fn conditions() {
+        let stops = |condition| {
+            let mut sys = Mock::default();
+            sys.0.breakpoints.push(breakpoint(0x10, None, condition));
+            !sys.check_access(AccessKind::Execute, 0x10, 0)
+        };
+        assert!(stops(""));
+        assert!(stops("r0 <= 5"));
+        assert!(!stops("r0 < 5"));
+        assert!(stops("sp == 0x100"));
+        assert!(stops("0x1234:16 == 0x3534"));
+        assert!(!stops("0x1234 == 0x3534"));
+        assert!(stops("value == 0"));
+    }
 }
@@ -0,0 +1,689 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Post-processing of frames produced by the systems: Upscalers made for
+//! pixel art, masks imitating the look of the original screens and blending
+//! of consecutive frames. All of it runs on the CPU.
+
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Colour;
+
+/// Scale used for masks when no upscaler is used, since they need
+/// multiple pixels per original pixel.
+const MASK_SCALE: usize = 3;
+/// Brightness of the gaps between pixels with [Mask::LcdGrid].
+const GRID_BRIGHTNESS: u32 = 180;
+/// Brightness of the gaps between lines with [Mask::Scanlines].
+const SCANLINE_BRIGHTNESS: u32 = 140;
+/// How much of the previous frame is still visible with
+/// [Blending::Ghosting], out of 256.
+const GHOSTING: u32 = 115;
+/// Maximum YUV distance of colours considered equal by xBR.
+const XBR_THRESHOLD: u32 = 155;
+/// Maximum differences in Y, U and V of colours considered equal by HQ2x.
+const HQ_THRESHOLD: [u32; 3] = [48, 7, 6];
+
+/// Upscalers for pixel art, which smooth edges while keeping the
+/// image sharp.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Upscaler {
+    None,
+    /// Scale2x, also known as EPX or AdvMAME2x.
+    Scale2x,
+    /// Scale3x, also known as AdvMAME3x.
+    Scale3x,
+    /// xBR at 2x, which detects edges of any angle and blends along them.
+    Xbr2x,
+    /// HQ2x, which blends pixels with differing neighbours.
+    Hq2x,
+}
+
+impl Upscaler {
+    pub const ALL: [Self; 5] = [
+        Self::None,
+        Self::Scale2x,
+        Self::Scale3x,
+        Self::Xbr2x,
+        Self::Hq2x,
+    ];
+
+    /// Factor the upscaler increases the frame size by.
+    pub fn scale(self) -> usize {
+        match self {
+            Self::None => 1,
+            Self::Scale2x | Self::Xbr2x | Self::Hq2x => 2,
+            Self::Scale3x => 3,
+        }
+    }
+}
+
+impl Display for Upscaler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::None => "None",
+            Self::Scale2x => "Scale2x",
+            Self::Scale3x => "Scale3x",
+            Self::Xbr2x => "xBR 2x",
+            Self::Hq2x => "HQ2x",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Masks imitating the structure of the original screens.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mask {
+    None,
+    /// Dark gaps between all pixels, like handheld LCDs.
+    LcdGrid,
+    /// Dark gaps between lines, like CRTs.
+    Scanlines,
+}
+
+impl Mask {
+    pub const ALL: [Self; 3] = [Self::None, Self::LcdGrid, Self::Scanlines];
+}
+
+impl Display for Mask {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::None => "None",
+            Self::LcdGrid => "LCD grid",
+            Self::Scanlines => "Scanlines",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Blending of consecutive frames.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Blending {
+    None,
+    /// Average every frame with the previous one. Makes effects that flicker
+    /// objects every frame to fake transparency look like intended.
+    Mix,
+    /// Let frames fade out slowly, like the slow response of the original
+    /// LCDs. Also smooths flickering, but leaves trails behind moving objects.
+    Ghosting,
+}
+
+impl Blending {
+    pub const ALL: [Self; 3] = [Self::None, Self::Mix, Self::Ghosting];
+}
+
+impl Display for Blending {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::None => "None",
+            Self::Mix => "Mix frames",
+            Self::Ghosting => "LCD ghosting",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Post-processing to apply to frames.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Filters {
+    pub upscaler: Upscaler,
+    pub mask: Mask,
+    pub blending: Blending,
+}
+
+impl Filters {
+    /// Factor the frame size is increased by.
+    pub fn scale(&self) -> usize {
+        match self.upscaler.scale() {
+            1 if self.mask != Mask::None => MASK_SCALE,
+            scale => scale,
+        }
+    }
+
+    /// Size of frames after post-processing, given the size the system
+    /// produces them at (see [crate::System::screen_size]).
+    pub fn output_size(&self, size: [usize; 2]) -> [usize; 2] {
+        size.map(|s| s * self.scale())
+    }
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        Self {
+            upscaler: Upscaler::None,
+            mask: Mask::None,
+            blending: Blending::None,
+        }
+    }
+}
+
+/// Post-processing stage, which keeps the previous frame for blending.
+#[derive(Default)]
+pub struct PostProcess {
+    /// Post-processing to apply.
+    pub filters: Filters,
+    /// Previous frame for blending, before upscaling.
+    previous: Vec<Colour>,
+}
+
+impl PostProcess {
+    /// Apply post-processing to a frame of the given size. The result is
+    /// of size [Filters::output_size].
+    pub fn apply(&mut self, frame: Vec<Colour>, size: [usize; 2]) -> Vec<Colour> {
+        let frame = self.blend(frame);
+        let mut frame = match self.filters.upscaler {
+            Upscaler::None if self.filters.mask != Mask::None => nearest(&frame, size, MASK_SCALE),
+            Upscaler::None => frame,
+            Upscaler::Scale2x => scale2x(&frame, size),
+            Upscaler::Scale3x => scale3x(&frame, size),
+            Upscaler::Xbr2x => xbr2x(&frame, size),
+            Upscaler::Hq2x => hq2x(&frame, size),
+        };
+        let scale = self.filters.scale();
+        match self.filters.mask {
+            Mask::None => (),
+            Mask::LcdGrid => apply_mask(&mut frame, size[0] * scale, GRID_BRIGHTNESS, |x, y| {
+                x % scale == scale - 1 || y % scale == scale - 1
+            }),
+            Mask::Scanlines => {
+                apply_mask(&mut frame, size[0] * scale, SCANLINE_BRIGHTNESS, |_, y| {
+                    y % scale == scale - 1
+                });
+            }
+        }
+        frame
+    }
+
+    /// Blend the frame with the previous one, and keep what is needed for
+    /// blending the next one.
+    fn blend(&mut self, frame: Vec<Colour>) -> Vec<Colour> {
+        if self.filters.blending == Blending::None || self.previous.len() != frame.len() {
+            self.previous.clone_from(&frame);
+            return frame;
+        }
+
+        let weight = match self.filters.blending {
+            Blending::Ghosting => GHOSTING,
+            _ => 128,
+        };
+        let blended = frame
+            .iter()
+            .zip(&self.previous)
+            .map(|(cur, prev)| mix(*cur, *prev, weight))
+            .collect::<Vec<_>>();
+        if self.filters.blending == Blending::Ghosting {
+            // The blended frame fades out over the next frames
+            self.previous.clone_from(&blended);
+        } else {
+            self.previous = frame;
+        }
+        blended
+    }
+
+    /// Forget the previous frame, for when it has nothing to do with the
+    /// next one, like after loading a ROM.
+    pub fn reset(&mut self) {
+        self.previous.clear();
+    }
+}
+
+/// Mix two colours, with the given amount of the second out of 256.
+fn mix(a: Colour, b: Colour, weight: u32) -> Colour {
+    let mut out = a;
+    for (out, (a, b)) in out.iter_mut().zip(a.iter().zip(b.iter())).take(3) {
+        *out = ((*a as u32 * (256 - weight) + *b as u32 * weight) >> 8) as u8;
+    }
+    out
+}
+
+/// Darken the pixels for which the given function returns true to the
+/// given brightness, out of 256.
+fn apply_mask(
+    frame: &mut [Colour],
+    width: usize,
+    brightness: u32,
+    gap: impl Fn(usize, usize) -> bool,
+) {
+    for (y, line) in frame.chunks_exact_mut(width).enumerate() {
+        for (x, pixel) in line.iter_mut().enumerate() {
+            if gap(x, y) {
+                *pixel = mix(*pixel, [0, 0, 0, 255], 256 - brightness);
+            }
+        }
+    }
+}
+
+/// Scale the frame up by the given factor, without smoothing.
+fn nearest(frame: &[Colour], [width, height]: [usize; 2], scale: usize) -> Vec<Colour> {
+    let mut out = Vec::with_capacity(frame.len() * scale * scale);
+    for y in 0..height * scale {
+        let line = &frame[(y / scale) * width..][..width];
+        for x in 0..width * scale {
+            out.push(line[x / scale]);
+        }
+    }
+    out
+}
+
+/// Accessor for pixels of a frame, with coordinates outside of it clamped
+/// to the edge.
+struct Frame<'a> {
+    pixels: &'a [Colour],
+    size: [usize; 2],
+}
+
+impl Frame<'_> {
+    fn get(&self, x: isize, y: isize) -> Colour {
+        let x = x.clamp(0, self.size[0] as isize - 1) as usize;
+        let y = y.clamp(0, self.size[1] as isize - 1) as usize;
+        self.pixels[y * self.size[0] + x]
+    }
+
+    /// Run the given function for every pixel, which writes a
+    /// `scale`x`scale` block of the output.
+    fn scale<const N: usize>(
+        &self,
+        scale: usize,
+        mut block: impl FnMut(isize, isize) -> [Colour; N],
+    ) -> Vec<Colour> {
+        let [width, height] = self.size;
+        let out_width = width * scale;
+        let mut out = vec![[0; 4]; self.pixels.len() * N];
+        for y in 0..height {
+            for x in 0..width {
+                let pixels = block(x as isize, y as isize);
+                for (idx, pixel) in pixels.into_iter().enumerate() {
+                    let (ox, oy) = (x * scale + idx % scale, y * scale + idx / scale);
+                    out[oy * out_width + ox] = pixel;
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Scale2x: Pixels next to a corner of two equal neighbours take their colour.
+fn scale2x(frame: &[Colour], size: [usize; 2]) -> Vec<Colour> {
+    let frame = Frame {
+        pixels: frame,
+        size,
+    };
+    frame.scale::<4>(2, |x, y| {
+        let e = frame.get(x, y);
+        let (b, d) = (frame.get(x, y - 1), frame.get(x - 1, y));
+        let (f, h) = (frame.get(x + 1, y), frame.get(x, y + 1));
+        if b == h || d == f {
+            return [e; 4];
+        }
+        [
+            if d == b { d } else { e },
+            if b == f { f } else { e },
+            if d == h { d } else { e },
+            if h == f { f } else { e },
+        ]
+    })
+}
+
+/// Scale3x: Like [scale2x], with edge pixels also checking the diagonals.
+fn scale3x(frame: &[Colour], size: [usize; 2]) -> Vec<Colour> {
+    let frame = Frame {
+        pixels: frame,
+        size,
+    };
+    frame.scale::<9>(3, |x, y| {
+        let [a, b, c] = [-1, 0, 1].map(|dx| frame.get(x + dx, y - 1));
+        let [d, e, f] = [-1, 0, 1].map(|dx| frame.get(x + dx, y));
+        let [g, h, i] = [-1, 0, 1].map(|dx| frame.get(x + dx, y + 1));
+        if b == h || d == f {
+            return [e; 9];
+        }
+        [
+            if d == b { d } else { e },
+            if (d == b && e != c) || (b == f && e != a) {
+                b
+            } else {
+                e
+            },
+            if b == f { f } else { e },
+            if (d == b && e != g) || (d == h && e != a) {
+                d
+            } else {
+                e
+            },
+            e,
+            if (b == f && e != i) || (h == f && e != c) {
+                f
+            } else {
+                e
+            },
+            if d == h { d } else { e },
+            if (d == h && e != i) || (h == f && e != g) {
+                h
+            } else {
+                e
+            },
+            if h == f { f } else { e },
+        ]
+    })
+}
+
+/// xBR at 2x: For every corner of a pixel, compare how strongly colours
+/// change across and along the diagonal to find edges, then blend the
+/// corner with the colour on the other side of the edge.
+/// The edge's angle decides how many sub-pixels are blended.
+fn xbr2x(frame: &[Colour], size: [usize; 2]) -> Vec<Colour> {
+    let frame = Frame {
+        pixels: frame,
+        size,
+    };
+    frame.scale::<4>(2, |x, y| {
+        let mut block = [frame.get(x, y); 4];
+        for rotation in 0..4 {
+            xbr_corner(&mut block, rotation, |dx, dy| {
+                let (dx, dy) = rotate(dx, dy, rotation);
+                frame.get(x + dx, y + dy)
+            });
+        }
+        block
+    })
+}
+
+/// Filter the bottom right corner of a pixel's 2x2 block, with the pixel's
+/// surroundings and the block rotated by the given amount of quarter turns.
+/// Surrounding pixels are named like this, with E being the current one:
+/// ```text
+///    A  B  C
+///    D  E  F  F4
+///    G  H  I  I4
+///       H5 I5
+/// ```
+fn xbr_corner(block: &mut [Colour; 4], rotation: u8, px: impl Fn(isize, isize) -> Colour) {
+    let (e, f, h, i) = (px(0, 0), px(1, 0), px(0, 1), px(1, 1));
+    if e == h || e == f {
+        return;
+    }
+    let (b, c, d, g) = (px(0, -1), px(1, -1), px(-1, 0), px(-1, 1));
+    let (f4, i4, h5, i5) = (px(2, 0), px(2, 1), px(0, 2), px(1, 2));
+
+    // Weighted amount of change in the direction of the diagonals H-F and
+    // E-I. If colours change less along H-F, an edge cuts off the corner
+    let along_hf =
+        yuv_diff(e, c) + yuv_diff(e, g) + yuv_diff(i, h5) + yuv_diff(i, f4) + 4 * yuv_diff(h, f);
+    let along_ei =
+        yuv_diff(h, d) + yuv_diff(h, i5) + yuv_diff(f, i4) + yuv_diff(f, b) + 4 * yuv_diff(e, i);
+    let colour = if yuv_diff(e, f) <= yuv_diff(e, h) {
+        f
+    } else {
+        h
+    };
+    let sub = |x, y| {
+        let (x, y) = rotate(x, y, rotation);
+        ((y + 1) / 2 * 2 + (x + 1) / 2) as usize
+    };
+    let (corner, left, up) = (sub(1, 1), sub(-1, 1), sub(1, -1));
+
+    let edge = (!similar(f, b) && !similar(h, d))
+        || (similar(e, i) && !similar(f, i4) && !similar(h, i5))
+        || similar(e, g)
+        || similar(e, c);
+    if along_hf < along_ei && edge {
+        // Shallow edges also reach into the neighbouring sub-pixels
+        let (ke, ki) = (yuv_diff(f, g), yuv_diff(h, c));
+        let shallow_left = 2 * ke <= ki && e != g && d != g;
+        let shallow_up = ke >= 2 * ki && e != c && b != c;
+        if shallow_left && shallow_up {
+            block[corner] = mix(block[corner], colour, 224);
+            block[left] = mix(block[left], colour, 64);
+            block[up] = mix(block[up], colour, 64);
+        } else if shallow_left {
+            block[corner] = mix(block[corner], colour, 192);
+            block[left] = mix(block[left], colour, 64);
+        } else if shallow_up {
+            block[corner] = mix(block[corner], colour, 192);
+            block[up] = mix(block[up], colour, 64);
+        } else {
+            block[corner] = mix(block[corner], colour, 128);
+        }
+    } else if along_hf <= along_ei {
+        block[corner] = mix(block[corner], colour, 128);
+    }
+}
+
+/// HQ2x: Every corner of a pixel is blended with the neighbours next to it
+/// that differ from the pixel, strongly if they form a diagonal edge.
+/// Instead of the original's table of interpolations for all 256 patterns
+/// of differing neighbours, its main cases are covered by rules:
+/// Diagonal edges across the corner, other pixels that differ on both sides
+/// of the corner (like single pixels) and a differing diagonal neighbour.
+fn hq2x(frame: &[Colour], size: [usize; 2]) -> Vec<Colour> {
+    let frame = Frame {
+        pixels: frame,
+        size,
+    };
+    frame.scale::<4>(2, |x, y| {
+        let e = frame.get(x, y);
+        [(-1, -1), (1, -1), (-1, 1), (1, 1)].map(|(dx, dy)| {
+            let (vertical, horizontal) = (frame.get(x, y + dy), frame.get(x + dx, y));
+            if hq_differ(e, vertical)
+                && hq_differ(e, horizontal)
+                && !hq_differ(vertical, horizontal)
+            {
+                // Neighbours that continue a diagonal edge past the corner
+                let along = [frame.get(x - dx, y + dy), frame.get(x + dx, y - dy)];
+                let weight = if along.iter().any(|p| !hq_differ(e, *p)) {
+                    4
+                } else {
+                    1
+                };
+                mix3(e, vertical, horizontal, weight)
+            } else {
+                let diagonal = frame.get(x + dx, y + dy);
+                if hq_differ(e, diagonal) {
+                    mix(e, diagonal, 64)
+                } else {
+                    e
+                }
+            }
+        })
+    })
+}
+
+/// Mix a colour with two others, with the given amount of each of them
+/// out of 16.
+fn mix3(a: Colour, b: Colour, c: Colour, weight: u32) -> Colour {
+    let mut out = a;
+    for i in 0..3 {
+        let sum = a[i] as u32 * (16 - 2 * weight) + (b[i] as u32 + c[i] as u32) * weight;
+        out[i] = (sum >> 4) as u8;
+    }
+    out
+}
+
+/// If two colours are different enough for HQ2x to blend them.
+fn hq_differ(a: Colour, b: Colour) -> bool {
+    yuv_delta(a, b)
+        .iter()
+        .zip(HQ_THRESHOLD)
+        .any(|(delta, threshold)| *delta > threshold)
+}
+
+/// Rotate a position by the given amount of quarter turns.
+fn rotate(mut x: isize, mut y: isize, rotation: u8) -> (isize, isize) {
+    for _ in 0..rotation {
+        (x, y) = (-y, x);
+    }
+    (x, y)
+}
+
+/// Difference between two colours, weighted towards brightness like
+/// human vision is.
+fn yuv_diff(a: Colour, b: Colour) -> u32 {
+    let [y, u, v] = yuv_delta(a, b);
+    (48 * y) + (7 * u) + (6 * v)
+}
+
+/// Absolute differences of two colours in Y, U and V.
+fn yuv_delta(a: Colour, b: Colour) -> [u32; 3] {
+    let [r, g, b] = [0, 1, 2].map(|i| a[i] as i32 - b[i] as i32);
+    let y = (299 * r + 587 * g + 114 * b) / 1000;
+    let u = (-169 * r - 331 * g + 500 * b) / 1000;
+    let v = (500 * r - 419 * g - 81 * b) / 1000;
+    [y, u, v].map(i32::unsigned_abs)
+}
+
+/// If two colours are close enough to be considered the same by xBR.
+fn similar(a: Colour, b: Colour) -> bool {
+    yuv_diff(a, b) < XBR_THRESHOLD
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        components::filter::{
+            hq2x, nearest, scale2x, scale3x, xbr2x, Blending, Filters, Mask, PostProcess, Upscaler,
+        },
+        Colour,
+    };
+
+    const B: Colour = [0, 0, 0, 255];
+    const W: Colour = [255, 255, 255, 255];
+
+    /// A white pixel in the middle of a black 3x3 frame.
+    fn single_pixel() -> Vec<Colour> {
+        let mut frame = vec![B; 9];
+        frame[4] = W;
+        frame
+    }
+
+    fn grey(value: u8) -> Colour {
+        [value, value, value, 255]
+    }
+
+    fn process(filters: Filters, frames: &[Vec<Colour>], size: [usize; 2]) -> Vec<Colour> {
+        let mut post = PostProcess {
+            filters,
+            ..PostProcess::default()
+        };
+        frames
+            .iter()
+            .map(|frame| post.apply(frame.clone(), size))
+            .last()
+            .unwrap()
+    }
+
+    #[test]
+    fn uniform_frame() {
+        for upscaler in Upscaler::ALL {
+            let filters = Filters {
+                upscaler,
+                ..Filters::default()
+            };
+            let out = process(filters, &[vec![W; 12]], [4, 3]);
+            assert_eq!(out.len(), 12 * upscaler.scale() * upscaler.scale());
+            assert!(out.iter().all(|p| *p == W), "{upscaler}");
+        }
+    }
+
+    #[test]
+    fn scale2x_single_pixel() {
+        assert_eq!(
+            scale2x(&single_pixel(), [3, 3]),
+            nearest(&single_pixel(), [3, 3], 2)
+        );
+    }
+
+    #[test]
+    fn scale2x_checkerboard() {
+        #[rustfmt::skip]
+        let expected = [
+            B, B, W, W,
+            B, W, B, W,
+            W, B, W, B,
+            W, W, B, B,
+        ];
+        assert_eq!(scale2x(&[B, W, W, B], [2, 2]), expected);
+    }
+
+    #[test]
+    fn scale3x_single_pixel() {
+        assert_eq!(
+            scale3x(&single_pixel(), [3, 3]),
+            nearest(&single_pixel(), [3, 3], 3)
+        );
+    }
+
+    #[test]
+    fn xbr2x_single_pixel() {
+        // The pixel gets round corners, its neighbours stay unchanged
+        let mut expected = vec![B; 36];
+        for idx in [14, 15, 20, 21] {
+            expected[idx] = grey(127);
+        }
+        assert_eq!(xbr2x(&single_pixel(), [3, 3]), expected);
+    }
+
+    #[test]
+    fn hq2x_single_pixel() {
+        // The pixel is slightly blended with its neighbours, which are
+        // blended with it at their corner facing it
+        let mut expected = vec![B; 36];
+        for idx in [14, 15, 20, 21] {
+            expected[idx] = grey(223);
+        }
+        for idx in [7, 10, 25, 28] {
+            expected[idx] = grey(63);
+        }
+        assert_eq!(hq2x(&single_pixel(), [3, 3]), expected);
+    }
+
+    #[test]
+    fn masks() {
+        let filters = Filters {
+            mask: Mask::LcdGrid,
+            ..Filters::default()
+        };
+        let g = grey(179);
+        assert_eq!(
+            process(filters, &[vec![W]], [1, 1]),
+            [W, W, g, W, W, g, g, g, g]
+        );
+
+        let filters = Filters {
+            mask: Mask::Scanlines,
+            ..Filters::default()
+        };
+        let g = grey(139);
+        assert_eq!(
+            process(filters, &[vec![W]], [1, 1]),
+            [W, W, W, W, W, W, g, g, g]
+        );
+    }
+
+    #[test]
+    fn blending() {
+        let frames = [vec![W], vec![B], vec![B]];
+        let filters = |blending| Filters {
+            blending,
+            ..Filters::default()
+        };
+        assert_eq!(process(filters(Blending::None), &frames[..2], [1, 1]), [B]);
+        assert_eq!(
+            process(filters(Blending::Mix), &frames[..2], [1, 1]),
+            [grey(127)]
+        );
+        assert_eq!(process(filters(Blending::Mix), &frames, [1, 1]), [B]);
+        assert_eq!(
+            process(filters(Blending::Ghosting), &frames[..2], [1, 1]),
+            [grey(114)]
+        );
+        assert_eq!(
+            process(filters(Blending::Ghosting), &frames, [1, 1]),
+            [grey(51)]
+        );
+    }
+}
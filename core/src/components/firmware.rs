@@ -0,0 +1,170 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    fmt::{Display, Formatter},
+    fs, io,
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+use Firmware::*;
+
+use crate::{common::SystemConfig, ggc::model::GgcModel};
+
+const KB: usize = 1024;
+
+/// Firmware images needed by some systems. These cannot be shipped with
+/// the emulator, so the user supplies them; their paths are set in
+/// [SystemConfig] and they are loaded when a cart is loaded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Firmware {
+    /// Boot ROM of the given GG(C) model. Optional; if not set, the boot
+    /// ROM is skipped, or Bootix is used on DMG models if enabled.
+    GgcBoot(GgcModel),
+    GgaBios,
+    NdsBios7,
+    NdsBios9,
+    PsxBios,
+}
+
+impl Firmware {
+    pub const ALL: [Firmware; 12] = [
+        GgcBoot(GgcModel::Dmg0),
+        GgcBoot(GgcModel::Dmg),
+        GgcBoot(GgcModel::Mgb),
+        GgcBoot(GgcModel::Sgb),
+        GgcBoot(GgcModel::Sgb2),
+        GgcBoot(GgcModel::Cgb0),
+        GgcBoot(GgcModel::Cgb),
+        GgcBoot(GgcModel::Agb),
+        GgaBios,
+        NdsBios7,
+        NdsBios9,
+        PsxBios,
+    ];
+
+    /// Size of the image in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            // Includes the unmapped area at 0x100-0x1FF
+            GgcBoot(model) if model.is_cgb() => 0x900,
+            GgcBoot(_) => 0x100,
+            GgaBios | NdsBios7 => 16 * KB,
+            NdsBios9 => 4 * KB,
+            PsxBios => 512 * KB,
+        }
+    }
+
+    /// CRC32 and name of known good dumps.
+    fn known_dumps(self) -> &'static [(u32, &'static str)] {
+        match self {
+            GgcBoot(GgcModel::Dmg0) => &[(0xC2F5_CC97, "DMG0")],
+            GgcBoot(GgcModel::Dmg) => &[(0x59C8_598E, "DMG")],
+            GgcBoot(GgcModel::Mgb) => &[(0xE692_0754, "MGB")],
+            GgcBoot(GgcModel::Sgb) => &[(0xEC8A_83B9, "SGB")],
+            GgcBoot(GgcModel::Sgb2) => &[(0x53D0_DD63, "SGB2")],
+            GgcBoot(GgcModel::Cgb0) => &[(0x5703_37EA, "CGB0")],
+            GgcBoot(GgcModel::Cgb) => &[(0x4188_4E46, "CGB")],
+            GgcBoot(GgcModel::Agb) => &[(0xFFD6_B0F1, "AGB")],
+            GgaBios => &[(0x8197_7335, "GBA")],
+            NdsBios7 => &[(0x1280_F0D5, "NDS")],
+            NdsBios9 => &[(0x2AB2_3573, "NDS")],
+            PsxBios => &[
+                (0x3B60_1FC8, "SCPH-1000"),
+                (0x3715_7331, "SCPH-1001"),
+                (0xFF3E_EB8C, "SCPH-5500"),
+                (0x8D8C_B7E4, "SCPH-5501"),
+                (0xD786_F0B9, "SCPH-5502"),
+                (0x5022_24B6, "SCPH-7001"),
+                (0x3181_78BF, "SCPH-7502"),
+                (0x171B_DCEC, "SCPH-101"),
+            ],
+        }
+    }
+
+    /// Identify an image by its hash. Returns the name of the dump,
+    /// or `None` if it is not a known good dump.
+    pub fn identify(self, image: &[u8]) -> Option<&'static str> {
+        let crc = crc32fast::hash(image);
+        self.known_dumps()
+            .iter()
+            .find(|(hash, _)| *hash == crc)
+            .map(|(_, name)| *name)
+    }
+
+    /// Load the image from the path set in the config.
+    /// Images with an unknown hash are still loaded, since they might
+    /// be a custom replacement, but a warning is logged.
+    pub fn load(self, config: &SystemConfig) -> Result<Box<[u8]>, FirmwareError> {
+        let path = config
+            .firmware
+            .get(&self)
+            .ok_or(FirmwareError::NotSet(self))?;
+        let image = fs::read(path).map_err(|err| FirmwareError::Io(self, path.clone(), err))?;
+        if image.len() != self.size() {
+            return Err(FirmwareError::WrongSize(self, image.len()));
+        }
+
+        if let Some(name) = self.identify(&image) {
+            log::info!("Loaded {self} ({name})");
+        } else {
+            log::warn!("{self} at '{}' is not a known dump!", path.display());
+        }
+        Ok(image.into_boxed_slice())
+    }
+
+    /// Load the image if a path is set for it in the config.
+    pub fn load_if_set(self, config: &SystemConfig) -> Result<Option<Box<[u8]>>, FirmwareError> {
+        if config.firmware.contains_key(&self) {
+            self.load(config).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Display for Firmware {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            GgcBoot(model) => return write!(f, "GGC {model} boot ROM"),
+            GgaBios => "GGA BIOS",
+            NdsBios7 => "NDS ARM7 BIOS",
+            NdsBios9 => "NDS ARM9 BIOS",
+            PsxBios => "PSX BIOS",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Error when a firmware image could not be loaded.
+#[derive(Debug)]
+pub enum FirmwareError {
+    /// No path was set for the firmware.
+    NotSet(Firmware),
+    /// The file could not be read.
+    Io(Firmware, PathBuf, io::Error),
+    /// The file has the wrong size, which is given.
+    WrongSize(Firmware, usize),
+}
+
+impl Display for FirmwareError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FirmwareError::NotSet(fw) => write!(f, "No {fw} set. Set one in the options."),
+            FirmwareError::Io(fw, path, err) => {
+                write!(f, "Failed to read {fw} from '{}': {err}", path.display())
+            }
+            FirmwareError::WrongSize(fw, size) => write!(
+                f,
+                "{fw} has the wrong size: expected {} bytes, got {size}",
+                fw.size()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FirmwareError {}
@@ -5,7 +5,12 @@
 // obtain one at https://mozilla.org/MPL/2.0/.
 
 pub mod arm;
+pub mod cart_info;
+pub mod clock;
 pub mod debugger;
+pub mod filter;
+pub mod firmware;
 pub mod memory;
+pub mod patch;
 pub mod scheduler;
 pub mod storage;
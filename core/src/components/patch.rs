@@ -0,0 +1,428 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Soft-patching of ROMs with IPS, UPS and BPS patches, as commonly used
+//! for translations and ROM hacks.
+
+use std::fmt::{Display, Formatter};
+
+/// Length of the checksums at the end of UPS and BPS patches.
+const FOOTER_LEN: usize = 12;
+/// Offset marking the end of an IPS patch, "EOF".
+const IPS_EOF: usize = 0x45_4F46;
+/// Maximum size of a patched ROM, far above any real one. Protects against
+/// malformed patches claiming huge outputs.
+const MAX_OUTPUT_LEN: usize = 64 << 20;
+
+/// Formats of patches that can be applied.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Ups,
+    Bps,
+}
+
+impl PatchFormat {
+    /// Detect the format of the given file, if it is a patch.
+    pub fn detect(file: &[u8]) -> Option<Self> {
+        match file.get(..5)? {
+            b"PATCH" => Some(Self::Ips),
+            [b'U', b'P', b'S', b'1', _] => Some(Self::Ups),
+            [b'B', b'P', b'S', b'1', _] => Some(Self::Bps),
+            _ => None,
+        }
+    }
+}
+
+impl Display for PatchFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Ips => "IPS",
+            Self::Ups => "UPS",
+            Self::Bps => "BPS",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Error when a patch could not be applied.
+#[derive(Debug)]
+pub enum PatchError {
+    /// The file is not a patch in a known format.
+    UnknownFormat,
+    /// The patch ended early or contains invalid data.
+    Malformed(PatchFormat),
+    /// The patch was made for a different ROM.
+    WrongRom(PatchFormat),
+    /// The patch or its output does not match its checksum.
+    Checksum(PatchFormat),
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "Not a patch in a known format."),
+            PatchError::Malformed(fmt) => write!(f, "The {fmt} patch is malformed."),
+            PatchError::WrongRom(fmt) => {
+                write!(f, "The {fmt} patch was made for a different ROM.")
+            }
+            PatchError::Checksum(fmt) => {
+                write!(f, "The {fmt} patch is corrupted: checksum mismatch.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Apply a patch to a ROM, returning the patched ROM.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let format = PatchFormat::detect(patch).ok_or(PatchError::UnknownFormat)?;
+    if format != PatchFormat::Ips && patch.len() < 4 + FOOTER_LEN {
+        return Err(PatchError::Malformed(format));
+    }
+    let mut reader = Reader {
+        data: patch,
+        pos: 4,
+    };
+    let result = match format {
+        PatchFormat::Ips => {
+            reader.pos = 5;
+            apply_ips(rom, &mut reader)
+        }
+        PatchFormat::Ups => apply_ups(rom, &mut reader),
+        PatchFormat::Bps => apply_bps(rom, &mut reader),
+    };
+    result.ok_or(PatchError::Malformed(format))?
+}
+
+/// IPS: Records of data to write at an offset, optionally run-length encoded.
+fn apply_ips(rom: &[u8], patch: &mut Reader) -> Option<Result<Vec<u8>, PatchError>> {
+    let mut out = rom.to_vec();
+    loop {
+        let offset = patch.be(3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+        let (len, byte) = match patch.be(2)? {
+            0 => (patch.be(2)?, Some(patch.byte()?)),
+            len => (len, None),
+        };
+        let end = offset.checked_add(len)?;
+        if out.len() < end {
+            out.resize(end, 0);
+        }
+        match byte {
+            Some(byte) => out[offset..end].fill(byte),
+            None => out[offset..end].copy_from_slice(patch.bytes(len)?),
+        }
+    }
+    // Optional extension: Size to truncate the output to
+    if let Some(len) = patch.be(3) {
+        out.truncate(len);
+    }
+    Some(Ok(out))
+}
+
+/// UPS: Runs of bytes to XOR the ROM with, at relative offsets.
+fn apply_ups(rom: &[u8], patch: &mut Reader) -> Option<Result<Vec<u8>, PatchError>> {
+    let format = PatchFormat::Ups;
+    let (source_len, target_len) = (patch.vli()?, patch.vli()?);
+    if target_len > MAX_OUTPUT_LEN {
+        return None;
+    }
+    if let Err(err) = check_footer(rom, patch.data, source_len, format) {
+        return Some(Err(err));
+    }
+
+    let mut out = rom.to_vec();
+    out.resize(target_len, 0);
+    let mut pos = 0usize;
+    while patch.pos < patch.data.len() - FOOTER_LEN {
+        pos = pos.checked_add(patch.vli()?)?;
+        loop {
+            let byte = patch.byte()?;
+            if let Some(out) = out.get_mut(pos) {
+                *out ^= byte;
+            }
+            pos = pos.checked_add(1)?;
+            if byte == 0 {
+                break;
+            }
+        }
+    }
+    Some(check_output(&out, patch.data, format))
+}
+
+/// BPS: Commands building the output from copies of the ROM, the patch
+/// and the output itself.
+fn apply_bps(rom: &[u8], patch: &mut Reader) -> Option<Result<Vec<u8>, PatchError>> {
+    let format = PatchFormat::Bps;
+    let (source_len, target_len) = (patch.vli()?, patch.vli()?);
+    let metadata_len = patch.vli()?;
+    patch.bytes(metadata_len)?;
+    if target_len > MAX_OUTPUT_LEN {
+        return None;
+    }
+    if let Err(err) = check_footer(rom, patch.data, source_len, format) {
+        return Some(Err(err));
+    }
+
+    let mut out = Vec::with_capacity(target_len);
+    let (mut source_offset, mut target_offset) = (0, 0);
+    while patch.pos < patch.data.len() - FOOTER_LEN {
+        let command = patch.vli()?;
+        let len = (command >> 2) + 1;
+        if len > target_len - out.len() {
+            return None;
+        }
+        match command & 3 {
+            // Source read: Copy from the ROM at the same offset
+            0 => {
+                let start = out.len();
+                out.extend_from_slice(rom.get(start..start.checked_add(len)?)?);
+            }
+            // Target read: Copy from the patch
+            1 => out.extend_from_slice(patch.bytes(len)?),
+            // Source copy: Copy from the ROM at a relative offset
+            2 => {
+                source_offset = relative(source_offset, patch.vli()?)?;
+                let end = source_offset.checked_add(len)?;
+                out.extend_from_slice(rom.get(source_offset..end)?);
+                source_offset += len;
+            }
+            // Target copy: Copy from the output at a relative offset,
+            // byte by byte since the ranges may overlap
+            _ => {
+                target_offset = relative(target_offset, patch.vli()?)?;
+                for _ in 0..len {
+                    out.push(*out.get(target_offset)?);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+    if out.len() != target_len {
+        return None;
+    }
+    Some(check_output(&out, patch.data, format))
+}
+
+/// Apply a BPS relative offset, with the sign in the lowest bit.
+fn relative(offset: usize, data: usize) -> Option<usize> {
+    if data & 1 != 0 {
+        offset.checked_sub(data >> 1)
+    } else {
+        offset.checked_add(data >> 1)
+    }
+}
+
+/// Check that a UPS/BPS patch is intact and meant for the given ROM.
+fn check_footer(
+    rom: &[u8],
+    patch: &[u8],
+    source_len: usize,
+    format: PatchFormat,
+) -> Result<(), PatchError> {
+    let crc_at = |offs: usize| u32::from_le_bytes(patch[offs..offs + 4].try_into().unwrap());
+    let footer = patch.len() - FOOTER_LEN;
+    if crc32fast::hash(&patch[..patch.len() - 4]) != crc_at(footer + 8) {
+        Err(PatchError::Checksum(format))
+    } else if rom.len() != source_len || crc32fast::hash(rom) != crc_at(footer) {
+        Err(PatchError::WrongRom(format))
+    } else {
+        Ok(())
+    }
+}
+
+/// Check the output of a UPS/BPS patch against its checksum.
+fn check_output(out: &[u8], patch: &[u8], format: PatchFormat) -> Result<Vec<u8>, PatchError> {
+    let offs = patch.len() - FOOTER_LEN + 4;
+    let crc = u32::from_le_bytes(patch[offs..offs + 4].try_into().unwrap());
+    if crc32fast::hash(out) == crc {
+        Ok(out.to_vec())
+    } else {
+        Err(PatchError::Checksum(format))
+    }
+}
+
+/// Reader over a patch, returning `None` when reading past its end.
+struct Reader<'p> {
+    data: &'p [u8],
+    pos: usize,
+}
+
+impl<'p> Reader<'p> {
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'p [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    /// Big-endian number of the given length in bytes.
+    fn be(&mut self, len: usize) -> Option<usize> {
+        let bytes = self.bytes(len)?;
+        Some(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize))
+    }
+
+    /// Variable-length number used by UPS and BPS.
+    fn vli(&mut self) -> Option<usize> {
+        let (mut data, mut shift) = (0usize, 1usize);
+        loop {
+            let byte = self.byte()?;
+            data = data.checked_add(((byte & 0x7F) as usize).checked_mul(shift)?)?;
+            if byte & 0x80 != 0 {
+                return Some(data);
+            }
+            shift = shift.checked_mul(0x80)?;
+            data = data.checked_add(shift)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::patch::{apply, PatchError, PatchFormat, MAX_OUTPUT_LEN};
+
+    const ROM: [u8; 4] = [1, 2, 3, 4];
+
+    fn vli(mut data: usize, out: &mut Vec<u8>) {
+        loop {
+            let byte = (data & 0x7F) as u8;
+            data >>= 7;
+            if data == 0 {
+                out.push(0x80 | byte);
+                return;
+            }
+            out.push(byte);
+            data -= 1;
+        }
+    }
+
+    /// Finish a UPS/BPS patch with the checksums of source, target and
+    /// the patch itself.
+    fn footer(mut patch: Vec<u8>, target: &[u8]) -> Vec<u8> {
+        patch.extend(crc32fast::hash(&ROM).to_le_bytes());
+        patch.extend(crc32fast::hash(target).to_le_bytes());
+        patch.extend(crc32fast::hash(&patch).to_le_bytes());
+        patch
+    }
+
+    /// A UPS patch changing ROM to [1, 5, 3, 4, 7].
+    fn ups_patch(target_len: usize) -> Vec<u8> {
+        let mut patch = b"UPS1".to_vec();
+        vli(ROM.len(), &mut patch);
+        vli(target_len, &mut patch);
+        // Skip 1 byte, XOR 2 with 7, then skip to the appended byte
+        for (skip, xor) in [(1, 7), (1, 7)] {
+            vli(skip, &mut patch);
+            patch.extend([xor, 0]);
+        }
+        footer(patch, &[1, 5, 3, 4, 7])
+    }
+
+    /// A BPS patch changing ROM to [1, 2, 9, 9, 9, 4].
+    fn bps_patch(target: &[u8]) -> Vec<u8> {
+        let mut patch = b"BPS1".to_vec();
+        for data in [ROM.len(), 6, 0] {
+            vli(data, &mut patch);
+        }
+        // Source read of 2, target read of [9]
+        vli(1 << 2, &mut patch);
+        vli(1, &mut patch);
+        patch.push(9);
+        // Target copy of 2 from offset 2, overlapping the output
+        vli((1 << 2) | 3, &mut patch);
+        vli(2 << 1, &mut patch);
+        // Source copy of 1 from offset 3
+        vli(2, &mut patch);
+        vli(3 << 1, &mut patch);
+        footer(patch, target)
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(PatchFormat::detect(b"PATCHEOF"), Some(PatchFormat::Ips));
+        assert_eq!(PatchFormat::detect(&ups_patch(5)), Some(PatchFormat::Ups));
+        assert_eq!(PatchFormat::detect(&bps_patch(&[])), Some(PatchFormat::Bps));
+        assert_eq!(PatchFormat::detect(&ROM), None);
+        assert!(matches!(apply(&ROM, &ROM), Err(PatchError::UnknownFormat)));
+    }
+
+    #[test]
+    fn ips() {
+        // A record at 1 and a run of 4 bytes at 3, extending the ROM
+        let mut patch = b"PATCH".to_vec();
+        patch.extend([0, 0, 1, 0, 2, 8, 8]);
+        patch.extend([0, 0, 3, 0, 0, 0, 4, 6]);
+        patch.extend(b"EOF");
+        assert_eq!(apply(&ROM, &patch).unwrap(), [1, 8, 8, 6, 6, 6, 6]);
+
+        // Truncation extension
+        patch.extend([0, 0, 2]);
+        assert_eq!(apply(&ROM, &patch).unwrap(), [1, 8]);
+
+        // Missing end marker
+        patch.truncate(patch.len() - 6);
+        assert!(matches!(
+            apply(&ROM, &patch),
+            Err(PatchError::Malformed(PatchFormat::Ips))
+        ));
+    }
+
+    #[test]
+    fn ups() {
+        assert_eq!(apply(&ROM, &ups_patch(5)).unwrap(), [1, 5, 3, 4, 7]);
+
+        // Output not matching its checksum
+        let err = apply(&ROM, &ups_patch(4));
+        assert!(matches!(err, Err(PatchError::Checksum(PatchFormat::Ups))));
+    }
+
+    #[test]
+    fn bps() {
+        let target = [1, 2, 9, 9, 9, 4];
+        assert_eq!(apply(&ROM, &bps_patch(&target)).unwrap(), target);
+
+        // Output not matching its checksum
+        let err = apply(&ROM, &bps_patch(&[0]));
+        assert!(matches!(err, Err(PatchError::Checksum(PatchFormat::Bps))));
+    }
+
+    #[test]
+    fn checksums() {
+        // Patch corrupted
+        let mut patch = bps_patch(&[1, 2, 9, 9, 9, 4]);
+        patch[8] ^= 1;
+        let err = apply(&ROM, &patch);
+        assert!(matches!(err, Err(PatchError::Checksum(PatchFormat::Bps))));
+
+        // Patch for another ROM
+        let err = apply(&[1, 2, 3, 5], &ups_patch(5));
+        assert!(matches!(err, Err(PatchError::WrongRom(PatchFormat::Ups))));
+        let err = apply(&[1, 2, 3], &ups_patch(5));
+        assert!(matches!(err, Err(PatchError::WrongRom(PatchFormat::Ups))));
+    }
+
+    #[test]
+    fn huge_output() {
+        let err = apply(&ROM, &ups_patch(MAX_OUTPUT_LEN + 1));
+        assert!(matches!(err, Err(PatchError::Malformed(PatchFormat::Ups))));
+
+        let mut patch = b"BPS1".to_vec();
+        for data in [ROM.len(), MAX_OUTPUT_LEN + 1, 0] {
+            vli(data, &mut patch);
+        }
+        let err = apply(&ROM, &footer(patch, &[]));
+        assert!(matches!(err, Err(PatchError::Malformed(PatchFormat::Bps))));
+    }
+}
@@ -0,0 +1,689 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+//! High-level emulation of the GGA BIOS, used when no BIOS dump is
+//! available. Software interrupts are implemented directly; the BIOS
+//! region only contains a minimal IRQ handler.
+
+use std::f64::consts::TAU;
+
+use crate::{
+    components::arm::{interface::SysWrapper, registers::Mode},
+    gga::{
+        addr::{DISPCNT, IE, IF, IME, SOUNDBIAS},
+        memory::KB,
+        GameGirlAdv,
+    },
+    numutil::{hword, NumExt},
+};
+
+/// Address of the interrupt flags the IRQ handler sets for IntrWait.
+const BIOS_IF: u32 = 0x0300_7FF8;
+/// Address of the return flag checked by SoftReset.
+const RESET_FLAG: u32 = 0x0300_7FFA;
+/// Checksum of the official BIOS, returned by GetBiosChecksum.
+const CHECKSUM: u32 = 0xBAAE_187F;
+
+/// Code of the HLE BIOS. Only the SWI and IRQ vectors are used.
+const CODE: [u32; 12] = [
+    0xEAFF_FFFE, // 00: b 00 (Reset, unused)
+    0xEAFF_FFFE, // 04: b 04 (Undefined, unused)
+    0xE1B0_F00E, // 08: movs pc, lr (SWI, only unknown ones end up here)
+    0xEAFF_FFFE, // 0C: b 0C (Prefetch abort, unused)
+    0xEAFF_FFFE, // 10: b 10 (Data abort, unused)
+    0xEAFF_FFFE, // 14: b 14 (Reserved, unused)
+    // IRQ: Call the handler at 0x03FFFFFC, same as the official BIOS
+    0xE92D_500F, // 18: stmfd sp!, {r0-r3, r12, lr}
+    0xE3A0_0301, // 1C: mov r0, #0x04000000
+    0xE28F_E000, // 20: add lr, pc, #0
+    0xE510_F004, // 24: ldr pc, [r0, #-4]
+    0xE8BD_500F, // 28: ldmfd sp!, {r0-r3, r12, lr}
+    0xE25E_F004, // 2C: subs pc, lr, #4
+];
+
+/// The HLE BIOS image.
+pub static HLE_BIOS: [u8; 16 * KB] = make_image();
+
+const fn make_image() -> [u8; 16 * KB] {
+    let mut image = [0; 16 * KB];
+    let mut idx = 0;
+    while idx < CODE.len() {
+        let bytes = CODE[idx].to_le_bytes();
+        let mut byte = 0;
+        while byte < 4 {
+            image[idx * 4 + byte] = bytes[byte];
+            byte += 1;
+        }
+        idx += 1;
+    }
+    image
+}
+
+/// Handle a software interrupt. Returns false if it is not implemented,
+/// in which case it is ignored by the BIOS stub.
+pub fn handle_swi(gg: &mut SysWrapper<GameGirlAdv>, comment: u8) -> bool {
+    let [r0, r1, r2, r3] = [0, 1, 2, 3].map(|r| gg.cpu.registers[r]);
+    match comment {
+        0x00 => soft_reset(gg),
+        0x01 => register_ram_reset(gg, r0),
+        0x02 | 0x03 => gg.halt(),
+        0x04 => intr_wait(gg, r0 != 0, r1.u16()),
+        0x05 => intr_wait(gg, true, 1),
+        0x06 => div(gg, r0 as i32, r1 as i32),
+        0x07 => div(gg, r1 as i32, r0 as i32),
+        0x08 => gg.cpu.registers[0] = sqrt(r0),
+        0x09 => {
+            let (result, a, b) = arctan(r0 as i32);
+            gg.cpu.registers[0] = result as u32;
+            gg.cpu.registers[1] = a as u32;
+            gg.cpu.registers[3] = b as u32;
+        }
+        0x0A => {
+            let (result, a) = arctan2(r0 as i32, r1 as i32);
+            gg.cpu.registers[0] = result.u32();
+            gg.cpu.registers[1] = a as u32;
+            gg.cpu.registers[3] = 0x170;
+        }
+        0x0B => cpu_set(gg, r0, r1, r2),
+        0x0C => cpu_fast_set(gg, r0, r1, r2),
+        0x0D => gg.cpu.registers[0] = CHECKSUM,
+        0x0E => bg_affine_set(gg, r0, r1, r2),
+        0x0F => obj_affine_set(gg, r0, r1, r2, r3),
+        0x10 => bit_unpack(gg, r0, r1, r2),
+        0x11 | 0x12 if readable(r0) => {
+            let data = lz77_uncomp(gg, r0);
+            write_out(gg, r1, &data, comment == 0x12);
+        }
+        0x13 if readable(r0) => {
+            let data = huff_uncomp(gg, r0);
+            write_out(gg, r1, &data, false);
+        }
+        0x14 | 0x15 if readable(r0) => {
+            let data = rl_uncomp(gg, r0);
+            write_out(gg, r1, &data, comment == 0x15);
+        }
+        0x16 | 0x17 if readable(r0) => {
+            let data = diff_unfilter::<1>(gg, r0);
+            write_out(gg, r1, &data, comment == 0x17);
+        }
+        0x18 if readable(r0) => {
+            let data = diff_unfilter::<2>(gg, r0);
+            write_out(gg, r1, &data, true);
+        }
+        0x19 => {
+            let level = if r0 == 0 { 0 } else { 0x200 };
+            let bias = gg[SOUNDBIAS];
+            gg.set_hword(0x0400_0000 + SOUNDBIAS, (bias & !0x3FF) | level);
+        }
+        // Source was in the BIOS region, nothing happens
+        0x11..=0x18 => (),
+
+        _ => {
+            log::warn!("Unimplemented HLE BIOS call 0x{comment:02X}");
+            return false;
+        }
+    }
+    // Value left on the bus when the official BIOS returns from a SWI
+    gg.memory.bios_value = 0xE3A0_2004;
+    true
+}
+
+/// If the BIOS allows reading from the given address. It refuses to
+/// copy from its own region.
+fn readable(addr: u32) -> bool {
+    addr & 0x0E00_0000 != 0
+}
+
+fn soft_reset(gg: &mut SysWrapper<GameGirlAdv>) {
+    let to_ram = gg.get_byte(RESET_FLAG) != 0;
+    for addr in (0x0300_7E00..0x0300_8000).step_by(4) {
+        gg.set_word(addr, 0);
+    }
+
+    gg.cpu.set_cpsr(Mode::System.to_u32());
+    gg.cpu.sp[Mode::Supervisor as usize] = 0x0300_7FE0;
+    gg.cpu.sp[Mode::Irq as usize] = 0x0300_7FA0;
+    gg.cpu.lr = [0; 6];
+    gg.cpu.spsr = [0; 6];
+    gg.cpu.registers = [0; 16];
+    gg.cpu.registers[13] = 0x0300_7F00;
+    gg.set_pc(if to_ram { 0x0200_0000 } else { 0x0800_0000 });
+}
+
+fn register_ram_reset(gg: &mut SysWrapper<GameGirlAdv>, flags: u32) {
+    gg.set_hword(0x0400_0000 + DISPCNT, 0x80);
+
+    let memory = [
+        (0x0200_0000, 0x0204_0000),
+        // The top of IWRAM contains the stacks and IRQ handler
+        (0x0300_0000, 0x0300_7E00),
+        (0x0500_0000, 0x0500_0400),
+        (0x0600_0000, 0x0601_8000),
+        (0x0700_0000, 0x0700_0400),
+    ];
+    for (bit, (start, end)) in memory.into_iter().enumerate() {
+        if flags.is_bit(bit as u16) {
+            for addr in (start..end).step_by(4) {
+                gg.set_word(addr, 0);
+            }
+        }
+    }
+
+    let registers: [&[(u32, u32)]; 3] = [
+        // Serial
+        &[(0x120, 0x130), (0x140, 0x142), (0x150, 0x15C)],
+        // Sound
+        &[(0x060, 0x086), (0x090, 0x0A0)],
+        // Others
+        &[
+            (0x004, 0x006),
+            (0x008, 0x058),
+            (0x0B0, 0x0E0),
+            (0x100, 0x110),
+            (IE, IE + 2),
+            (0x204, 0x206),
+            (IME, IME + 2),
+        ],
+    ];
+    for (bit, ranges) in registers.into_iter().enumerate() {
+        if flags.is_bit(bit as u16 + 5) {
+            for (start, end) in ranges {
+                for addr in (*start..*end).step_by(2) {
+                    gg.set_hword(0x0400_0000 + addr, 0);
+                }
+            }
+        }
+    }
+    if flags.is_bit(5) {
+        // RCNT, general purpose mode
+        gg.set_hword(0x0400_0134, 0x8000);
+    }
+}
+
+/// Wait until one of the given interrupts was handled.
+/// Instead of looping inside the BIOS, this rewinds the PC to the SWI
+/// instruction after halting, so it runs again after the IRQ handler
+/// returned.
+fn intr_wait(gg: &mut SysWrapper<GameGirlAdv>, discard: bool, flags: u16) {
+    let handled = gg.get_hword(BIOS_IF);
+    if discard && !gg.memory.intr_waiting {
+        gg.set_hword(BIOS_IF, handled & !flags);
+    } else if handled & flags != 0 {
+        gg.set_hword(BIOS_IF, handled & !flags);
+        gg.memory.intr_waiting = false;
+        return;
+    }
+
+    gg.memory.intr_waiting = true;
+    let swi = gg.cpu.pc() - gg.cpu.inst_size() * 2;
+    gg.set_pc(swi);
+    gg.set_hword(0x0400_0000 + IME, 1);
+    if gg[IE] & gg[IF] == 0 {
+        gg.halt();
+    }
+}
+
+fn div(gg: &mut SysWrapper<GameGirlAdv>, num: i32, denom: i32) {
+    let (quot, rem) = if denom == 0 {
+        // The official BIOS hangs, this is what it returns
+        // once it gives up
+        (if num < 0 { -1 } else { 1 }, num)
+    } else {
+        (num.wrapping_div(denom), num.wrapping_rem(denom))
+    };
+    gg.cpu.registers[0] = quot as u32;
+    gg.cpu.registers[1] = rem as u32;
+    gg.cpu.registers[3] = quot.unsigned_abs();
+}
+
+fn sqrt(value: u32) -> u32 {
+    // Integer square root by bit-by-bit calculation
+    let mut rem = value;
+    let mut root = 0;
+    let mut bit = 1 << 30;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rem >= root + bit {
+            rem -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+/// Arctangent of a 1.1.14 value, using the same polynomial
+/// approximation as the official BIOS. Also returns the intermediate
+/// values left in R1 and R3.
+fn arctan(value: i32) -> (i32, i32, i32) {
+    let a = -(value.wrapping_mul(value) >> 14);
+    let mut b = (0xA9i32.wrapping_mul(a) >> 14) + 0x390;
+    for add in [0x91C, 0xFB6, 0x16AA, 0x2081, 0x3651, 0xA2F9] {
+        b = (b.wrapping_mul(a) >> 14) + add;
+    }
+    (value.wrapping_mul(b) >> 16, a, b)
+}
+
+/// Arctangent of y/x, covering the full circle (0x0000-0xFFFF).
+/// Also returns the value left in R1, which stays y if either is 0.
+fn arctan2(x: i32, y: i32) -> (u16, i32) {
+    let atan = |value: i32| {
+        let (result, a, _) = arctan(value);
+        (result, a)
+    };
+    let (result, a) = if y == 0 {
+        (if x >= 0 { 0 } else { 0x8000 }, y)
+    } else if x == 0 {
+        (if y >= 0 { 0x4000 } else { 0xC000 }, y)
+    } else if y >= 0 && x >= 0 && x >= y {
+        atan((y << 14) / x)
+    } else if y >= 0 && x < 0 && -x >= y {
+        let (result, a) = atan((y << 14) / x);
+        (result + 0x8000, a)
+    } else if y >= 0 {
+        let (result, a) = atan((x << 14) / y);
+        (0x4000 - result, a)
+    } else if x <= 0 && -x > -y {
+        let (result, a) = atan((y << 14) / x);
+        (result + 0x8000, a)
+    } else if x > 0 && x >= -y {
+        let (result, a) = atan((y << 14) / x);
+        (result + 0x10000, a)
+    } else {
+        let (result, a) = atan((x << 14) / y);
+        (0xC000 - result, a)
+    };
+    (result as u16, a)
+}
+
+fn cpu_set(gg: &mut SysWrapper<GameGirlAdv>, src: u32, dst: u32, control: u32) {
+    if !readable(src) {
+        return;
+    }
+    let count = control & 0x1F_FFFF;
+    let fill = control.is_bit(24);
+    if control.is_bit(26) {
+        let (src, dst) = (src & !3, dst & !3);
+        for idx in 0..count {
+            let value = gg.get_word(if fill { src } else { src + idx * 4 });
+            gg.set_word(dst + idx * 4, value);
+        }
+    } else {
+        let (src, dst) = (src & !1, dst & !1);
+        for idx in 0..count {
+            let value = gg.get_hword(if fill { src } else { src + idx * 2 });
+            gg.set_hword(dst + idx * 2, value);
+        }
+    }
+}
+
+fn cpu_fast_set(gg: &mut SysWrapper<GameGirlAdv>, src: u32, dst: u32, control: u32) {
+    // Always copies blocks of 8 words
+    let count = ((control & 0x1F_FFFF) + 7) & !7;
+    cpu_set(gg, src, dst, (control & (1 << 24)) | (1 << 26) | count);
+}
+
+/// Sine of the given angle (0-255 is a full circle) in 1.1.14 format.
+fn sine(angle: u32) -> i32 {
+    let angle = (angle & 0xFF) as f64 * TAU / 256.0;
+    (angle.sin() * 16384.0).round() as i32
+}
+
+/// Calculate the affine parameters (PA, PB, PC, PD) for the given
+/// 8.8 scale and angle.
+fn affine_params(sx: i32, sy: i32, theta: u16) -> [i32; 4] {
+    let angle = (theta >> 8).u32();
+    let (sin, cos) = (sine(angle), sine(angle + 0x40));
+    [
+        (sx * cos) >> 14,
+        (-sx * sin) >> 14,
+        (sy * sin) >> 14,
+        (sy * cos) >> 14,
+    ]
+}
+
+fn bg_affine_set(gg: &mut SysWrapper<GameGirlAdv>, src: u32, dst: u32, count: u32) {
+    for idx in 0..count {
+        let src = src + idx * 20;
+        let dst = dst + idx * 16;
+        let ox = gg.get_word(src) as i32;
+        let oy = gg.get_word(src + 4) as i32;
+        let cx = gg.get_hword(src + 8) as i16 as i32;
+        let cy = gg.get_hword(src + 10) as i16 as i32;
+        let sx = gg.get_hword(src + 12) as i16 as i32;
+        let sy = gg.get_hword(src + 14) as i16 as i32;
+        let theta = gg.get_hword(src + 16);
+
+        let params = affine_params(sx, sy, theta);
+        for (offs, param) in params.iter().enumerate() {
+            gg.set_hword(dst + offs.u32() * 2, *param as u16);
+        }
+        let [pa, pb, pc, pd] = params;
+        gg.set_word(dst + 8, (ox - (pa * cx + pb * cy)) as u32);
+        gg.set_word(dst + 12, (oy - (pc * cx + pd * cy)) as u32);
+    }
+}
+
+fn obj_affine_set(gg: &mut SysWrapper<GameGirlAdv>, src: u32, dst: u32, count: u32, stride: u32) {
+    for idx in 0..count {
+        let src = src + idx * 8;
+        let dst = dst + idx * stride * 4;
+        let sx = gg.get_hword(src) as i16 as i32;
+        let sy = gg.get_hword(src + 2) as i16 as i32;
+        let theta = gg.get_hword(src + 4);
+
+        let params = affine_params(sx, sy, theta);
+        for (offs, param) in params.iter().enumerate() {
+            gg.set_hword(dst + offs.u32() * stride, *param as u16);
+        }
+    }
+}
+
+fn bit_unpack(gg: &mut SysWrapper<GameGirlAdv>, src: u32, mut dst: u32, info: u32) {
+    if !readable(src) {
+        return;
+    }
+    let len = gg.get_hword(info).u32();
+    let src_width = gg.get_byte(info + 2).u32();
+    let dst_width = gg.get_byte(info + 3).u32();
+    let offset = gg.get_word(info + 4);
+    let (add, zero_too) = (offset & 0x7FFF_FFFF, offset.is_bit(31));
+    if !matches!(src_width, 1 | 2 | 4 | 8) || !matches!(dst_width, 1 | 2 | 4 | 8 | 16 | 32) {
+        return;
+    }
+
+    let mut buffer = 0u64;
+    let mut buffered = 0;
+    for idx in 0..len {
+        let byte = gg.get_byte(src + idx).u32();
+        for shift in (0..8).step_by(src_width.us()) {
+            let mut unit = (byte >> shift) & ((1 << src_width) - 1);
+            if unit != 0 || zero_too {
+                unit = unit.wrapping_add(add);
+            }
+            buffer |= ((unit as u64) & ((1 << dst_width) - 1)) << buffered;
+            buffered += dst_width;
+            if buffered >= 32 {
+                gg.set_word(dst, buffer as u32);
+                dst += 4;
+                buffer >>= 32;
+                buffered -= 32;
+            }
+        }
+    }
+}
+
+/// Read the decompressed size from a compression header.
+fn uncomp_size(gg: &SysWrapper<GameGirlAdv>, src: u32) -> usize {
+    (gg.get_word(src) >> 8).us()
+}
+
+fn lz77_uncomp(gg: &SysWrapper<GameGirlAdv>, mut src: u32) -> Vec<u8> {
+    let size = uncomp_size(gg, src);
+    let mut out = Vec::with_capacity(size);
+    src += 4;
+
+    while out.len() < size {
+        let flags = gg.get_byte(src);
+        src += 1;
+        for bit in (0..8).rev() {
+            if out.len() >= size {
+                break;
+            }
+            if flags.is_bit(bit) {
+                let info = hword(gg.get_byte(src + 1), gg.get_byte(src));
+                src += 2;
+                let disp = (info & 0xFFF).us() + 1;
+                let len = (info >> 12).us() + 3;
+                for _ in 0..len {
+                    // Invalid displacements read garbage on hardware
+                    let byte = out.len().checked_sub(disp).map_or(0, |idx| out[idx]);
+                    out.push(byte);
+                }
+            } else {
+                out.push(gg.get_byte(src));
+                src += 1;
+            }
+        }
+    }
+    out.truncate(size);
+    out
+}
+
+fn huff_uncomp(gg: &SysWrapper<GameGirlAdv>, src: u32) -> Vec<u8> {
+    let size = uncomp_size(gg, src);
+    let bits = gg.get_word(src) & 0xF;
+    if bits != 4 && bits != 8 {
+        // Other widths would never fill the output buffer; hardware only
+        // uses 4 and 8
+        log::warn!("HuffUnComp with unsupported data size of {bits} bits");
+        return Vec::new();
+    }
+    let root = src + 5;
+    let mut stream = src + 4 + (gg.get_byte(src + 4).u32() + 1) * 2;
+    let mut out = Vec::with_capacity(size + 4);
+
+    let mut node_addr = root;
+    let mut node = gg.get_byte(root);
+    let mut buffer = 0u32;
+    let mut buffered = 0;
+    while out.len() < size {
+        let word = gg.get_word(stream);
+        stream += 4;
+        for bit in (0..32).rev() {
+            let right = word.is_bit(bit);
+            let child = (node_addr & !1) + (node & 0x3F).u32() * 2 + 2 + right as u32;
+            if node.is_bit(7 - right as u16) {
+                // Child is data
+                buffer |= (gg.get_byte(child).u32() & ((1 << bits) - 1)) << buffered;
+                buffered += bits;
+                if buffered == 32 {
+                    out.extend_from_slice(&buffer.to_le_bytes());
+                    buffer = 0;
+                    buffered = 0;
+                    if out.len() >= size {
+                        break;
+                    }
+                }
+                node_addr = root;
+                node = gg.get_byte(root);
+            } else {
+                node_addr = child;
+                node = gg.get_byte(child);
+            }
+        }
+    }
+    out.truncate(size);
+    out
+}
+
+fn rl_uncomp(gg: &SysWrapper<GameGirlAdv>, mut src: u32) -> Vec<u8> {
+    let size = uncomp_size(gg, src);
+    let mut out = Vec::with_capacity(size);
+    src += 4;
+
+    while out.len() < size {
+        let flag = gg.get_byte(src);
+        src += 1;
+        if flag.is_bit(7) {
+            let byte = gg.get_byte(src);
+            src += 1;
+            out.extend((0..(flag & 0x7F) + 3).map(|_| byte));
+        } else {
+            for _ in 0..=(flag & 0x7F) {
+                out.push(gg.get_byte(src));
+                src += 1;
+            }
+        }
+    }
+    out.truncate(size);
+    out
+}
+
+/// Undo a difference filter on units of the given size in bytes.
+fn diff_unfilter<const SIZE: u32>(gg: &SysWrapper<GameGirlAdv>, src: u32) -> Vec<u8> {
+    let size = uncomp_size(gg, src);
+    let mut out = Vec::with_capacity(size);
+    let mut value = 0u16;
+    for idx in (0..size.u32()).step_by(SIZE.us()) {
+        if SIZE == 1 {
+            value = value.wrapping_add(gg.get_byte(src + 4 + idx).u16()) & 0xFF;
+            out.push(value.u8());
+        } else {
+            value = value.wrapping_add(gg.get_hword(src + 4 + idx));
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    out
+}
+
+/// Write decompressed data. VRAM does not support byte writes, so
+/// halfwords are written there.
+fn write_out(gg: &mut SysWrapper<GameGirlAdv>, dst: u32, data: &[u8], vram: bool) {
+    if vram {
+        for (idx, bytes) in data.chunks(2).enumerate() {
+            let value = hword(bytes[0], bytes.get(1).copied().unwrap_or(0));
+            gg.set_hword(dst + idx.u32() * 2, value);
+        }
+    } else {
+        for (idx, byte) in data.iter().enumerate() {
+            gg.set_byte(dst + idx.u32(), *byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f64::consts::PI, thread};
+
+    use crate::{
+        components::arm::interface::SysWrapper,
+        gga::{bios::handle_swi, GameGirlAdv},
+    };
+
+    /// Call a SWI with the given arguments in R0-R3, returning R0, R1 and R3
+    /// afterwards.
+    fn call(gg: &mut GameGirlAdv, comment: u8, args: [u32; 4]) -> [u32; 3] {
+        gg.cpu.registers[..4].copy_from_slice(&args);
+        let mut wrapper = SysWrapper { inner: gg };
+        assert!(handle_swi(&mut wrapper, comment));
+        [0, 1, 3].map(|r| gg.cpu.registers[r])
+    }
+
+    /// Run the given test with a system, on a thread with enough stack
+    /// space for it.
+    fn with_system(inner: fn(&mut GameGirlAdv)) {
+        thread::Builder::new()
+            .stack_size(32 << 20)
+            .spawn(move || inner(&mut GameGirlAdv::default()))
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn div() {
+        with_system(|gg| {
+            let div = |gg: &mut GameGirlAdv, num: i32, denom: i32| {
+                let [quot, rem, abs] = call(gg, 0x06, [num as u32, denom as u32, 0, 0]);
+                (quot as i32, rem as i32, abs)
+            };
+            assert_eq!(div(gg, 7, 2), (3, 1, 3));
+            assert_eq!(div(gg, -7, 2), (-3, -1, 3));
+            assert_eq!(div(gg, 7, -2), (-3, 1, 3));
+            assert_eq!(div(gg, -7, -2), (3, -1, 3));
+            assert_eq!(div(gg, i32::MIN, -1), (i32::MIN, 0, 0x8000_0000));
+            assert_eq!(div(gg, 5, 0), (1, 5, 1));
+            assert_eq!(div(gg, -5, 0), (-1, -5, 1));
+
+            // DivArm takes its arguments the other way around
+            assert_eq!(
+                call(gg, 0x07, [2, -7i32 as u32, 0, 0]),
+                [-3i32 as u32, -1i32 as u32, 3]
+            );
+        });
+    }
+
+    #[test]
+    fn sqrt() {
+        with_system(|gg| {
+            for (value, root) in [
+                (0, 0),
+                (1, 1),
+                (2, 1),
+                (15, 3),
+                (16, 4),
+                (1_000_000, 1000),
+                (0x4000_0000, 0x8000),
+                (u32::MAX, 0xFFFF),
+            ] {
+                assert_eq!(call(gg, 0x08, [value, 0, 0, 0])[0], root, "{value}");
+            }
+        });
+    }
+
+    #[test]
+    fn arctan() {
+        with_system(|gg| {
+            // Input, then R0, R1 and R3 afterwards
+            for (value, expected) in [
+                (0, [0, 0, 0xA2F9]),
+                (0x1000, [0x9FB, -0x400, 0x9FB3]),
+                (0x2000, [0x12E4, -0x1000, 0x9720]),
+                (0x4000, [0x2000, -0x4000, 0x8000]),
+                (-0x4000, [-0x2000, -0x4000, 0x8000]),
+                // Outside of -1 to 1, the approximation diverges
+                (0x7FFF, [0x16D8, -0xFFFC, 0x22DB6]),
+            ] {
+                let expected = expected.map(|r: i32| r as u32);
+                assert_eq!(
+                    call(gg, 0x09, [value as u32, 0, 0, 0]),
+                    expected,
+                    "{value:X}"
+                );
+
+                if (-0x4000..=0x4000).contains(&value) {
+                    let ideal = (value as f64 / 16384.0).atan() / (PI / 2.0) * 16384.0;
+                    assert!((expected[0] as i32 as f64 - ideal).abs() < 1.0);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn arctan2() {
+        with_system(|gg| {
+            // X and Y, then R0 and R1 afterwards
+            for (x, y, expected) in [
+                (1, 0, [0, 0]),
+                (-1, 0, [0x8000, 0]),
+                (0, 1, [0x4000, 1]),
+                (0, -1, [0xC000, -1]),
+                (0x100, 0x100, [0x2000, -0x4000]),
+                (-0x100, 0x100, [0x6000, -0x4000]),
+                (-0x100, -0x100, [0xA000, -0x4000]),
+                (0x100, -0x100, [0xE000, -0x4000]),
+                (0x4000, 0x1000, [0x9FB, -0x400]),
+                (-0x1000, 0x3000, [0x4D1C, -0x71C]),
+                (0x1234, -0x4321, [0xCACA, -0x4B4]),
+            ] {
+                let regs = call(gg, 0x0A, [x as u32, y as u32, 0, 0]);
+                assert_eq!(
+                    regs,
+                    [expected[0] as u32, expected[1] as u32, 0x170],
+                    "{x:X} {y:X}"
+                );
+
+                let ideal = (y as f64).atan2(x as f64).rem_euclid(2.0 * PI) / PI * 32768.0;
+                assert!((expected[0] as f64 - ideal).abs() < 1.0);
+            }
+        });
+    }
+}
@@ -0,0 +1,297 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::{Display, Formatter};
+
+use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
+use SaveKind::*;
+
+use super::{
+    flash::{FlashChip, FlashState},
+    EepromSize, SaveType,
+};
+
+bitflags! {
+    /// Peripherals a cart can have.
+    #[derive(Deserialize, Serialize)]
+    pub struct Peripherals: u8 {
+        const RTC    = 1 << 0;
+        const SOLAR  = 1 << 1;
+        const GYRO   = 1 << 2;
+        const RUMBLE = 1 << 3;
+        const TILT   = 1 << 4;
+    }
+}
+
+impl Default for Peripherals {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// Type and size of a cart's save, without any state.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SaveKind {
+    #[default]
+    Nothing,
+    Sram,
+    Eeprom(EepromSize),
+    Flash64,
+    Flash128,
+}
+
+impl SaveKind {
+    pub const ALL: [SaveKind; 7] = [
+        Nothing,
+        Sram,
+        Eeprom(EepromSize::Unknown),
+        Eeprom(EepromSize::E512),
+        Eeprom(EepromSize::E8k),
+        Flash64,
+        Flash128,
+    ];
+
+    /// Save kind of the given flash chip.
+    pub fn flash(chip: FlashChip) -> Self {
+        if chip.is_128k() {
+            Flash128
+        } else {
+            Flash64
+        }
+    }
+
+    /// If this is a flash save, if it is 128KB.
+    pub fn flash_size(self) -> Option<bool> {
+        match self {
+            Flash64 => Some(false),
+            Flash128 => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Default flash chip for this save kind.
+    pub fn default_flash_chip(self) -> FlashChip {
+        match self {
+            Flash128 => FlashChip::Macronix128,
+            _ => FlashChip::Macronix64,
+        }
+    }
+
+    /// Create the save type, with the given chip if it is a flash save.
+    pub(super) fn make(self, flash_chip: FlashChip) -> SaveType {
+        match self {
+            Nothing => SaveType::Nothing,
+            Sram => SaveType::Sram,
+            Eeprom(size) => SaveType::Eeprom(super::Eeprom::new(size)),
+            Flash64 => SaveType::Flash64(FlashState::new(flash_chip)),
+            Flash128 => SaveType::Flash128 {
+                state: FlashState::new(flash_chip),
+                bank: 0,
+            },
+        }
+    }
+}
+
+/// Cart properties that cannot be reliably detected from the ROM.
+/// Properties that are `None` are detected instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CartOverride {
+    /// Save type and size.
+    pub save: Option<SaveKind>,
+    /// Flash chip model. Only used with flash saves; also determines
+    /// their size. Stored as the chip's ID, under its old name.
+    #[serde(rename = "flash_id", default, with = "chip_id")]
+    pub flash_chip: Option<FlashChip>,
+    /// Peripherals on the cart.
+    pub peripherals: Option<Peripherals>,
+    /// If the ROM is mirrored across the entire ROM area.
+    pub mirror: Option<bool>,
+}
+
+/// (De)serialize a flash chip as its ID, which is how user overrides stored
+/// it before the chips were told apart. Unknown IDs are detected instead.
+mod chip_id {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::gga::cartridge::flash::FlashChip;
+
+    // The signature is given by serde
+    #[allow(clippy::ref_option, clippy::trivially_copy_pass_by_ref)]
+    pub fn serialize<S: Serializer>(chip: &Option<FlashChip>, ser: S) -> Result<S::Ok, S::Error> {
+        chip.map(FlashChip::id).serialize(ser)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<Option<FlashChip>, D::Error> {
+        let id = Option::<[u8; 2]>::deserialize(de)?;
+        let chip = id.and_then(FlashChip::from_id);
+        if chip.is_none() && id.is_some() {
+            log::warn!("Unknown flash chip ID {id:02X?} in cart override, ignoring");
+        }
+        Ok(chip)
+    }
+}
+
+/// Look up a game in the database, by its game code.
+pub fn lookup(game_code: &str) -> Option<&'static CartOverride> {
+    DATABASE
+        .iter()
+        .find(|(code, _)| game_code.starts_with(code))
+        .map(|(_, entry)| entry)
+}
+
+/// Where a detected property came from.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Nothing was found, the default is used.
+    #[default]
+    Default,
+    /// A save library string was found in the ROM.
+    RomString(&'static str),
+    /// The game is in the built-in database.
+    Database,
+    /// The user set an override for this game.
+    UserOverride,
+    /// Forced by a system option.
+    SystemConfig,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::RomString(str) => write!(f, "found '{str}' in ROM"),
+            Source::Database => write!(f, "game database"),
+            Source::UserOverride => write!(f, "user override"),
+            Source::SystemConfig => write!(f, "system option"),
+        }
+    }
+}
+
+/// A detected cart property, along with where it came from.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Detected<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Detected<T> {
+    /// Replace the value if `value` is `Some`.
+    pub(super) fn or(&mut self, value: Option<T>, source: Source) {
+        if let Some(value) = value {
+            *self = Detected { value, source };
+        }
+    }
+}
+
+/// Cart properties that were detected when loading the ROM.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Detection {
+    pub save: Detected<SaveKind>,
+    pub flash_chip: Detected<FlashChip>,
+    pub peripherals: Detected<Peripherals>,
+    pub mirror: Detected<bool>,
+}
+
+const fn entry(save: SaveKind, peripherals: Peripherals) -> CartOverride {
+    CartOverride {
+        save: Some(save),
+        flash_chip: None,
+        peripherals: Some(peripherals),
+        mirror: None,
+    }
+}
+
+/// Classic NES Series carts, which mirror their ROM and check for it.
+const fn nes_classic() -> CartOverride {
+    CartOverride {
+        save: Some(Eeprom(EepromSize::Unknown)),
+        flash_chip: None,
+        peripherals: None,
+        mirror: Some(true),
+    }
+}
+
+const NONE: Peripherals = Peripherals::empty();
+const RTC: Peripherals = Peripherals::RTC;
+const SOLAR: Peripherals = Peripherals::RTC.union(Peripherals::SOLAR);
+const TWISTED: Peripherals = Peripherals::GYRO.union(Peripherals::RUMBLE);
+
+/// Known games, by game code prefix (without region where possible).
+const DATABASE: &[(&str, CartOverride)] = &[
+    // Pokemon
+    ("AXV", entry(Flash128, RTC)),  // Ruby
+    ("AXP", entry(Flash128, RTC)),  // Sapphire
+    ("BPE", entry(Flash128, RTC)),  // Emerald
+    ("BPR", entry(Flash128, NONE)), // FireRed
+    ("BPG", entry(Flash128, NONE)), // LeafGreen
+    // Boktai
+    ("U3I", entry(Eeprom(EepromSize::E8k), SOLAR)), // Boktai
+    ("U32", entry(Eeprom(EepromSize::E8k), SOLAR)), // Boktai 2
+    ("U33", entry(Eeprom(EepromSize::E8k), SOLAR)), // Shin Bokura no Taiyou
+    // Other RTC carts
+    ("BR4", entry(Flash128, RTC)), // Rockman EXE 4.5
+    ("BKA", entry(Flash128, RTC)), // Sennen Kazoku
+    // Sensors and rumble
+    ("RZW", entry(Sram, TWISTED)),             // WarioWare: Twisted
+    ("V49", entry(Sram, Peripherals::RUMBLE)), // Drill Dozer
+    ("KYG", entry(Eeprom(EepromSize::Unknown), Peripherals::TILT)), // Yoshi's Topsy-Turvy
+    ("KHP", entry(Eeprom(EepromSize::Unknown), Peripherals::TILT)), // Koro Koro Puzzle
+    // Classic NES Series
+    ("FAD", nes_classic()), // Castlevania
+    ("FBM", nes_classic()), // Bomberman
+    ("FDK", nes_classic()), // Donkey Kong
+    ("FDM", nes_classic()), // Dr. Mario
+    ("FEB", nes_classic()), // Excitebike
+    ("FIC", nes_classic()), // Ice Climber
+    ("FLB", nes_classic()), // Zelda II
+    ("FMR", nes_classic()), // Metroid
+    ("FP7", nes_classic()), // Pac-Man
+    ("FSM", nes_classic()), // Super Mario Bros.
+    ("FXV", nes_classic()), // Xevious
+    ("FZL", nes_classic()), // The Legend of Zelda
+];
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::gga::cartridge::{
+        database::{CartOverride, Peripherals, SaveKind},
+        flash::FlashChip,
+    };
+
+    /// Overrides as they were stored before flash chips were told apart.
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct OldOverride {
+        save: Option<SaveKind>,
+        flash_id: Option<[u8; 2]>,
+        peripherals: Option<Peripherals>,
+        mirror: Option<bool>,
+    }
+
+    #[test]
+    fn flash_chip_stored_as_id() {
+        let old = OldOverride {
+            save: Some(SaveKind::Flash64),
+            flash_id: Some([0xBF, 0xD4]),
+            peripherals: None,
+            mirror: None,
+        };
+        let bytes = bincode::serialize(&old).unwrap();
+        let over: CartOverride = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(over.flash_chip, Some(FlashChip::Sst64));
+        assert_eq!(bincode::serialize(&over).unwrap(), bytes);
+
+        let unknown = OldOverride {
+            flash_id: Some([0, 0]),
+            ..old
+        };
+        let over: CartOverride =
+            bincode::deserialize(&bincode::serialize(&unknown).unwrap()).unwrap();
+        assert_eq!(over.flash_chip, None);
+    }
+}
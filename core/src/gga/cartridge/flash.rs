@@ -0,0 +1,392 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+use FlashChip::*;
+use FlashCmdStage::*;
+
+/// Size of an Atmel page, which is written in one operation.
+const ATMEL_PAGE: usize = 128;
+/// Time in cycles an Atmel chip is busy for after writing a page.
+const ATMEL_PAGE_WRITE: u32 = us(5000);
+
+/// Convert microseconds to GGA cycles.
+/// Done in 64 bits, since the multiplication overflows 32 bits.
+const fn us(us: u32) -> u32 {
+    (us as u64 * 16_780_000 / 1_000_000) as u32
+}
+
+/// Flash chips found on GGA carts.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FlashChip {
+    #[default]
+    Macronix64,
+    Panasonic64,
+    Sst64,
+    Atmel64,
+    Macronix128,
+    Sanyo128,
+}
+
+impl FlashChip {
+    pub const ALL: [FlashChip; 6] = [
+        Macronix64,
+        Panasonic64,
+        Sst64,
+        Atmel64,
+        Macronix128,
+        Sanyo128,
+    ];
+
+    /// Chip ID (manufacturer, device), returned in ID mode.
+    pub fn id(self) -> [u8; 2] {
+        match self {
+            Macronix64 => [0xC2, 0x1C],
+            Panasonic64 => [0x32, 0x1B],
+            Sst64 => [0xBF, 0xD4],
+            Atmel64 => [0x1F, 0x3D],
+            Macronix128 => [0xC2, 0x09],
+            Sanyo128 => [0x62, 0x13],
+        }
+    }
+
+    /// The chip with the given ID, if it is a known one.
+    pub fn from_id(id: [u8; 2]) -> Option<Self> {
+        Self::ALL.into_iter().find(|chip| chip.id() == id)
+    }
+
+    /// If this chip is 128KB, split into 2 banks.
+    pub fn is_128k(self) -> bool {
+        matches!(self, Macronix128 | Sanyo128)
+    }
+
+    /// Time in cycles the chip is busy for after an erase.
+    /// Typical times from the datasheets, not the maximum.
+    fn timing(self, op: FlashOp) -> u32 {
+        match (self, op) {
+            // Atmel chips have no erase commands
+            (Atmel64, _) => 0,
+            (Sst64, FlashOp::EraseSector) => us(18_000),
+            (Sst64, FlashOp::EraseChip) => us(70_000),
+            (Panasonic64, FlashOp::EraseSector) => us(10_000),
+            (Panasonic64, FlashOp::EraseChip) | (Sanyo128, FlashOp::EraseSector) => us(20_000),
+            (Macronix64 | Macronix128, FlashOp::EraseSector) => us(25_000),
+            (Macronix64 | Macronix128 | Sanyo128, FlashOp::EraseChip) => us(40_000),
+        }
+    }
+}
+
+impl Display for FlashChip {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Macronix64 => "Macronix 64K",
+            Panasonic64 => "Panasonic 64K",
+            Sst64 => "SST 64K",
+            Atmel64 => "Atmel 64K",
+            Macronix128 => "Macronix 128K",
+            Sanyo128 => "Sanyo 128K",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Erase operations, which keep the chip busy for a while.
+/// Byte writes finish before software can observe them, and page writes
+/// only exist on Atmel chips; see [ATMEL_PAGE_WRITE].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum FlashOp {
+    EraseSector,
+    EraseChip,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+pub struct FlashState {
+    command_stage: Option<FlashCmdStage>,
+    pub(super) mode: FlashMode,
+    pub(super) chip: FlashChip,
+    /// Value the chip will read as once the current operation finishes,
+    /// `None` if not busy.
+    pub(super) busy: Option<u8>,
+}
+
+impl FlashState {
+    /// Read a byte while the chip is busy, or in ID mode.
+    /// Returns `None` if the byte should be read from the save instead.
+    pub(super) fn read(self, addr: usize) -> Option<u8> {
+        match (self.busy, self.mode) {
+            // Data polling: bit 7 is inverted until done
+            (Some(value), _) => Some(!value & 0x80),
+            (None, FlashMode::Id) => Some(self.chip.id()[addr & 1]),
+            _ => None,
+        }
+    }
+
+    /// Write a byte. Returns the time in cycles the chip is now busy for,
+    /// if it started an operation.
+    pub(super) fn write(
+        &mut self,
+        addr: usize,
+        value: u8,
+        ram: &mut [u8],
+        bank: Option<&mut u8>,
+    ) -> Option<u32> {
+        if self.busy.is_some() {
+            // Writes are ignored until the operation is finished
+            return None;
+        }
+        if let FlashMode::PageWrite(written) = self.mode {
+            return self.page_write(addr, value, ram, written);
+        }
+        let bank_offs = if bank.as_deref() == Some(&1) {
+            0x10000
+        } else {
+            0
+        };
+
+        match (addr, value, self.command_stage) {
+            (0x0, _, _) if self.mode == FlashMode::BankSelect => {
+                self.mode = FlashMode::Regular;
+                *bank.unwrap() = value & 1;
+            }
+
+            (_, _, _) if self.mode == FlashMode::Write => {
+                self.mode = FlashMode::Regular;
+                ram[addr | bank_offs] = value;
+            }
+
+            (0x5555, 0xAA, None) => self.command_stage = Some(FirstWritten),
+            (0x2AAA, 0x55, Some(FirstWritten)) => self.command_stage = Some(SecondWritten),
+
+            // Erase 4K sector
+            (_, 0x30, Some(SecondWritten)) => {
+                let erase = self.mode == FlashMode::Erase;
+                self.mode = FlashMode::Regular;
+                self.command_stage = None;
+                if erase {
+                    let addr = (addr & 0xF000) | bank_offs;
+                    ram[addr..(addr + 0x1000)].fill(0xFF);
+                    return self.start(self.chip.timing(FlashOp::EraseSector), 0xFF);
+                }
+            }
+
+            (0x5555, _, Some(SecondWritten)) => {
+                self.command_stage = None;
+                match value {
+                    // Enter Erase mode, Atmel chips have no erase commands
+                    0x80 if self.chip != Atmel64 => self.mode = FlashMode::Erase,
+                    // Erase entire chip
+                    0x10 => {
+                        let erase = self.mode == FlashMode::Erase;
+                        self.mode = FlashMode::Regular;
+                        if erase {
+                            ram.fill(0xFF);
+                            return self.start(self.chip.timing(FlashOp::EraseChip), 0xFF);
+                        }
+                    }
+
+                    // Enter write mode, Atmel chips write entire pages
+                    0xA0 if self.chip == Atmel64 => self.mode = FlashMode::PageWrite(0),
+                    0xA0 => self.mode = FlashMode::Write,
+                    // Enter bank select, if banked chip
+                    0xB0 if bank.is_some() => self.mode = FlashMode::BankSelect,
+
+                    // Enter ID mode
+                    0x90 => self.mode = FlashMode::Id,
+                    // Exit ID mode
+                    0xF0 => self.mode = FlashMode::Regular,
+
+                    _ => (),
+                }
+            }
+
+            // Reset without a command sequence, used by Sanyo chips
+            // to terminate operations
+            (_, 0xF0, None) if self.chip == Sanyo128 => self.mode = FlashMode::Regular,
+
+            _ => (),
+        }
+        None
+    }
+
+    /// Write a byte of an Atmel page.
+    fn page_write(&mut self, addr: usize, value: u8, ram: &mut [u8], written: u8) -> Option<u32> {
+        let page = addr & !(ATMEL_PAGE - 1);
+        if written == 0 {
+            // Bytes that are not loaded end up erased
+            ram[page..(page + ATMEL_PAGE)].fill(0xFF);
+        }
+        ram[addr] = value;
+
+        if usize::from(written) + 1 == ATMEL_PAGE {
+            self.mode = FlashMode::Regular;
+            self.start(ATMEL_PAGE_WRITE, value)
+        } else {
+            self.mode = FlashMode::PageWrite(written + 1);
+            None
+        }
+    }
+
+    /// The current operation finished.
+    pub(super) fn done(&mut self) {
+        self.busy = None;
+    }
+
+    /// Start an operation that keeps the chip busy for the given time.
+    fn start(&mut self, time: u32, result: u8) -> Option<u32> {
+        if time == 0 {
+            None
+        } else {
+            self.busy = Some(result);
+            Some(time)
+        }
+    }
+
+    pub(super) const fn new(chip: FlashChip) -> Self {
+        // Why is Default not const...
+        Self {
+            command_stage: None,
+            mode: FlashMode::Regular,
+            chip,
+            busy: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+pub enum FlashCmdStage {
+    FirstWritten,
+    SecondWritten,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub enum FlashMode {
+    Regular,
+    Write,
+    /// Atmel page write, with the amount of bytes written so far.
+    PageWrite(u8),
+    Id,
+    Erase,
+    BankSelect,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gga::cartridge::flash::{
+        us, FlashChip, FlashChip::*, FlashMode, FlashState, ATMEL_PAGE, ATMEL_PAGE_WRITE,
+    };
+
+    /// Send a command, with the unlock sequence before it.
+    fn command(flash: &mut FlashState, ram: &mut [u8], cmd: u8) -> Option<u32> {
+        flash.write(0x5555, 0xAA, ram, None);
+        flash.write(0x2AAA, 0x55, ram, None);
+        flash.write(0x5555, cmd, ram, None)
+    }
+
+    #[test]
+    fn ids() {
+        for chip in FlashChip::ALL {
+            let mut ram = vec![0; 0x10000];
+            let mut flash = FlashState::new(chip);
+            assert_eq!(flash.read(0), None);
+            command(&mut flash, &mut ram, 0x90);
+            assert_eq!([flash.read(0), flash.read(1)], chip.id().map(Some));
+            command(&mut flash, &mut ram, 0xF0);
+            assert_eq!(flash.read(0), None);
+
+            assert_eq!(FlashChip::from_id(chip.id()), Some(chip));
+        }
+        assert_eq!(FlashChip::from_id([0, 0]), None);
+    }
+
+    #[test]
+    fn byte_write() {
+        let mut ram = vec![0xFF; 0x10000];
+        let mut flash = FlashState::new(Macronix64);
+        command(&mut flash, &mut ram, 0xA0);
+        assert_eq!(flash.write(0x1234, 0x42, &mut ram, None), None);
+        assert_eq!(ram[0x1234], 0x42);
+        // Only one byte is written per command
+        flash.write(0x1235, 0x42, &mut ram, None);
+        assert_eq!(ram[0x1235], 0xFF);
+    }
+
+    #[test]
+    fn erase_sector_busy() {
+        let mut ram = vec![0; 0x10000];
+        let mut flash = FlashState::new(Macronix64);
+        command(&mut flash, &mut ram, 0x80);
+        flash.write(0x5555, 0xAA, &mut ram, None);
+        flash.write(0x2AAA, 0x55, &mut ram, None);
+        assert_eq!(flash.write(0x3000, 0x30, &mut ram, None), Some(us(25_000)));
+        assert!(ram[0x3000..0x4000].iter().all(|b| *b == 0xFF));
+        assert_eq!(ram[0x2FFF], 0);
+        assert_eq!(ram[0x4000], 0);
+
+        // Data polling reads bit 7 inverted, writes are ignored
+        assert_eq!(flash.read(0x3000), Some(0x00));
+        assert_eq!(command(&mut flash, &mut ram, 0x90), None);
+        assert_eq!(flash.mode, FlashMode::Regular);
+        flash.done();
+        assert_eq!(flash.read(0x3000), None);
+    }
+
+    #[test]
+    fn erase_chip() {
+        let mut ram = vec![0; 0x10000];
+        let mut flash = FlashState::new(Sst64);
+        command(&mut flash, &mut ram, 0x80);
+        assert_eq!(command(&mut flash, &mut ram, 0x10), Some(us(70_000)));
+        assert!(ram.iter().all(|b| *b == 0xFF));
+
+        // Erasing needs the erase mode command first
+        let mut flash = FlashState::new(Sst64);
+        ram.fill(0);
+        assert_eq!(command(&mut flash, &mut ram, 0x10), None);
+        assert!(ram.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn atmel_page_write() {
+        let mut ram = vec![0; 0x10000];
+        let mut flash = FlashState::new(Atmel64);
+        // No erase mode
+        command(&mut flash, &mut ram, 0x80);
+        assert_eq!(flash.mode, FlashMode::Regular);
+
+        command(&mut flash, &mut ram, 0xA0);
+        for i in 0..ATMEL_PAGE - 1 {
+            assert_eq!(flash.write(0x200 + i, i as u8, &mut ram, None), None);
+        }
+        assert_eq!(
+            flash.write(0x27F, 0x7F, &mut ram, None),
+            Some(ATMEL_PAGE_WRITE)
+        );
+        assert!((0..ATMEL_PAGE).all(|i| ram[0x200 + i] == i as u8));
+        // Bit 7 of the last byte written, inverted
+        assert_eq!(flash.read(0x200), Some(0x80));
+    }
+
+    #[test]
+    fn bank_select() {
+        let mut ram = vec![0xFF; 0x20000];
+        let mut flash = FlashState::new(Macronix128);
+        let mut bank = 0;
+        flash.write(0x5555, 0xAA, &mut ram, Some(&mut bank));
+        flash.write(0x2AAA, 0x55, &mut ram, Some(&mut bank));
+        flash.write(0x5555, 0xB0, &mut ram, Some(&mut bank));
+        flash.write(0x0, 1, &mut ram, Some(&mut bank));
+        assert_eq!(bank, 1);
+
+        flash.write(0x5555, 0xAA, &mut ram, Some(&mut bank));
+        flash.write(0x2AAA, 0x55, &mut ram, Some(&mut bank));
+        flash.write(0x5555, 0xA0, &mut ram, Some(&mut bank));
+        flash.write(0x10, 0x42, &mut ram, Some(&mut bank));
+        assert_eq!(ram[0x10010], 0x42);
+        assert_eq!(ram[0x10], 0xFF);
+    }
+}
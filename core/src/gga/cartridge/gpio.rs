@@ -0,0 +1,96 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::{Deserialize, Serialize};
+
+use super::{gyro::Gyro, rtc::Rtc, solar::SolarSensor};
+use crate::numutil::NumExt;
+
+/// Address of the GPIO data register.
+pub const GPIO_DATA: u32 = 0x0800_00C4;
+/// Address of the GPIO direction register.
+pub const GPIO_DIRECTION: u32 = 0x0800_00C6;
+/// Address of the GPIO control register.
+pub const GPIO_CONTROL: u32 = 0x0800_00C8;
+
+/// The 4-bit general purpose I/O port found on some cartridges,
+/// mapped into ROM at 0x080000C4-0x080000C9.
+/// Devices are connected to its pins.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Gpio {
+    /// Pin values last written by the GGA.
+    data: u8,
+    /// Pin directions, 1 means output (GGA to device).
+    direction: u8,
+    /// If the registers can be read; otherwise, reads return ROM.
+    pub(crate) readable: bool,
+
+    /// The RTC, if this cart has one.
+    pub rtc: Option<Rtc>,
+    /// The solar sensor, if this cart has one.
+    pub solar: Option<SolarSensor>,
+    /// The gyro sensor, if this cart has one.
+    pub gyro: Option<Gyro>,
+    /// If this cart has a rumble motor, driven by pin 3.
+    pub rumble: bool,
+}
+
+impl Gpio {
+    /// If any devices are connected to the port.
+    pub fn has_devices(&self) -> bool {
+        self.rtc.is_some() || self.solar.is_some() || self.gyro.is_some() || self.rumble
+    }
+
+    /// If the rumble motor is currently active.
+    pub fn rumble_active(&self) -> bool {
+        self.rumble && (self.data & self.direction).is_bit(3)
+    }
+
+    /// Read a GPIO register. Address must be one of the 3 registers.
+    pub fn read(&self, addr: u32) -> u16 {
+        match addr & !1 {
+            GPIO_DATA => {
+                let device = self.rtc.as_ref().map_or(0, Rtc::pins)
+                    | self.solar.as_ref().map_or(0, SolarSensor::pins)
+                    | self.gyro.as_ref().map_or(0, Gyro::pins);
+                ((self.data & self.direction) | (device & !self.direction)).u16()
+            }
+            GPIO_DIRECTION => self.direction.u16(),
+            _ => self.readable as u16,
+        }
+    }
+
+    /// Write a GPIO register. Address must be one of the 3 registers.
+    /// Returns true if a device requested an interrupt.
+    pub fn write(&mut self, addr: u32, value: u16) -> bool {
+        match addr & !1 {
+            GPIO_DATA => {
+                self.data = value.u8() & 0xF;
+                let pins = self.data & self.direction;
+                if let Some(solar) = &mut self.solar {
+                    solar.write(pins);
+                }
+                if let Some(gyro) = &mut self.gyro {
+                    gyro.write(pins);
+                }
+                self.rtc.as_mut().map_or(false, |rtc| rtc.write(pins))
+            }
+            GPIO_DIRECTION => {
+                self.direction = value.u8() & 0xF;
+                false
+            }
+            _ => {
+                self.readable = value.is_bit(0);
+                false
+            }
+        }
+    }
+
+    /// If the given address is a GPIO register.
+    pub fn is_register(addr: u32) -> bool {
+        (GPIO_DATA..=(GPIO_CONTROL + 1)).contains(&addr)
+    }
+}
@@ -0,0 +1,53 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::{Deserialize, Serialize};
+
+use crate::numutil::NumExt;
+
+/// Sample value when not rotating.
+const CENTER: f32 = 0x6C0 as f32;
+/// Sample difference from the center at full rotation speed.
+const RANGE: f32 = 0x400 as f32;
+
+/// Gyro sensor of WarioWare: Twisted, connected to the cartridge GPIO port.
+/// Pins are START (0), SCK (1) and SIO (2, output). A rising START
+/// samples the sensor, the sample is then shifted out MSB first on falling
+/// SCK edges.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Gyro {
+    /// Current rotation speed on the Z axis, from -1.0 to 1.0.
+    /// Positive is clockwise.
+    pub(crate) rotation: f32,
+    /// Sample being shifted out.
+    sample: u16,
+    /// Previous state of the SCK pin, to detect falling edges.
+    sck: bool,
+    /// Value of the SIO pin.
+    sio: bool,
+}
+
+impl Gyro {
+    /// The pins driven by the sensor.
+    pub(super) fn pins(&self) -> u8 {
+        (self.sio as u8) << 2
+    }
+
+    /// Update the pins driven by the GGA.
+    pub(super) fn write(&mut self, pins: u8) {
+        if pins.is_bit(0) {
+            let rotation = self.rotation.clamp(-1.0, 1.0);
+            self.sample = (CENTER + rotation * RANGE) as u16;
+        }
+
+        let sck = pins.is_bit(1);
+        if self.sck && !sck {
+            self.sio = self.sample.is_bit(15);
+            self.sample <<= 1;
+        }
+        self.sck = sck;
+    }
+}
@@ -0,0 +1,539 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+pub mod database;
+pub mod flash;
+pub mod gpio;
+pub mod gyro;
+pub mod rtc;
+pub mod solar;
+pub mod tilt;
+
+use std::{cell::RefCell, iter};
+
+use serde::{Deserialize, Serialize};
+use SaveType::*;
+
+use crate::{
+    common::SystemConfig,
+    components::storage::GameSave,
+    gga::{
+        cartridge::{
+            database::{Detected, Detection, Peripherals, SaveKind, Source},
+            flash::FlashState,
+            gpio::Gpio,
+            gyro::Gyro,
+            rtc::Rtc,
+            solar::SolarSensor,
+            tilt::TiltSensor,
+        },
+        memory::KB,
+    },
+    numutil::NumExt,
+};
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct Cartridge {
+    #[serde(skip)]
+    #[serde(default)]
+    pub rom: Vec<u8>,
+    pub ram: Vec<u8>,
+    pub save_type: SaveType,
+    /// GPIO port and connected devices, if the cart has any.
+    pub gpio: Option<Gpio>,
+    /// Tilt sensor, if the cart has one.
+    pub tilt: Option<TiltSensor>,
+    /// Cart properties detected when loading the ROM.
+    #[serde(skip)]
+    #[serde(default)]
+    pub detection: Detection,
+}
+
+impl Cartridge {
+    pub fn read_ram_byte(&self, addr: usize) -> u8 {
+        if let Some(tilt) = &self.tilt {
+            if TiltSensor::is_mapped(addr) {
+                return tilt.read(addr);
+            }
+        }
+
+        match &self.save_type {
+            Flash64(state) => state.read(addr).unwrap_or(self.ram[addr]),
+            Flash128 { state, bank } => state
+                .read(addr)
+                .unwrap_or(self.ram[addr | (bank.us() << 16)]),
+            Sram => self.ram[addr & 0x7FFF],
+
+            _ => 0xFF,
+        }
+    }
+
+    pub fn read_ram_hword(&self) -> u16 {
+        match &self.save_type {
+            Eeprom(eeprom) => eeprom.read(),
+            _ => 0,
+        }
+    }
+
+    /// Write a byte to the save area. Returns the time in cycles until
+    /// the flash chip finishes the operation it started, if any.
+    pub fn write_ram_byte(&mut self, addr: usize, value: u8) -> Option<u32> {
+        if let Some(tilt) = &mut self.tilt {
+            if TiltSensor::is_mapped(addr) {
+                tilt.write(addr, value);
+                return None;
+            }
+        }
+
+        match &mut self.save_type {
+            Flash64(state) => state.write(addr, value, &mut self.ram, None),
+            Flash128 { state, bank } => state.write(addr, value, &mut self.ram, Some(bank)),
+            Sram => {
+                self.ram[addr & 0x7FFF] = value;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// The flash chip finished its current operation.
+    pub fn flash_done(&mut self) {
+        if let Flash64(state) | Flash128 { state, .. } = &mut self.save_type {
+            state.done();
+        }
+    }
+
+    pub fn write_ram_hword(&mut self, value: u16) {
+        if let Eeprom(eeprom) = &mut self.save_type {
+            eeprom.write(value, &mut self.ram);
+        }
+    }
+
+    pub fn is_eeprom_at(&self, addr: u32) -> bool {
+        matches!(self.save_type, SaveType::Eeprom(_))
+            && (self.rom.len() <= 16 * (KB * KB) || addr >= 0x0DFF_FF00)
+    }
+
+    /// If the GPIO registers are currently mapped over ROM.
+    pub fn gpio_readable(&self) -> bool {
+        self.gpio.as_ref().is_some_and(|gpio| gpio.readable)
+    }
+
+    /// If the cart's rumble motor is currently active.
+    pub fn rumble(&self) -> bool {
+        self.gpio.as_ref().is_some_and(Gpio::rumble_active)
+    }
+
+    /// Set the tilt of the cart's tilt sensor, if it has one.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        if let Some(tilt) = &mut self.tilt {
+            tilt.tilt = [x, y];
+        }
+    }
+
+    /// Set the light level seen by the cart's solar sensor, if it has one.
+    pub fn set_light(&mut self, level: u8) {
+        if let Some(solar) = self.gpio.as_mut().and_then(|g| g.solar.as_mut()) {
+            solar.light = level;
+        }
+    }
+
+    /// Set the rotation speed seen by the cart's gyro sensor, if it has one.
+    pub fn set_rotation(&mut self, rotation: f32) {
+        if let Some(gyro) = self.gpio.as_mut().and_then(|g| g.gyro.as_mut()) {
+            gyro.rotation = rotation;
+        }
+    }
+
+    /// If the ROM is mirrored across the entire ROM area.
+    pub fn is_mirrored(&self) -> bool {
+        self.detection.mirror.value
+    }
+
+    /// Mask to apply to ROM addresses when the ROM is mirrored.
+    pub fn mirror_mask(&self) -> usize {
+        // Remove the padding added when loading
+        (self.rom.len() & !0x7FFF).next_power_of_two() - 1
+    }
+
+    pub fn load_rom(&mut self, rom: Vec<u8>, config: &SystemConfig) {
+        self.rom = rom;
+        self.detection = self.detect(config);
+        let Detection {
+            save,
+            flash_chip,
+            peripherals,
+            ..
+        } = self.detection;
+        self.save_type = save.value.make(flash_chip.value);
+
+        let peripherals = peripherals.value;
+        let mut gpio = Gpio::default();
+        if peripherals.contains(Peripherals::RTC) {
+            gpio.rtc = Some(Rtc::default());
+        }
+        if peripherals.contains(Peripherals::SOLAR) {
+            gpio.solar = Some(SolarSensor::default());
+        }
+        if peripherals.contains(Peripherals::GYRO) {
+            gpio.gyro = Some(Gyro::default());
+        }
+        gpio.rumble = peripherals.contains(Peripherals::RUMBLE);
+        self.gpio = if gpio.has_devices() { Some(gpio) } else { None };
+        self.tilt = peripherals
+            .contains(Peripherals::TILT)
+            .then(TiltSensor::default);
+
+        let ff_iter = iter::repeat(0xFF);
+        let len = self.ram.len();
+        match self.save_type {
+            Nothing => {}
+            Eeprom(_) => self.ram.extend(ff_iter.take((8 * KB) - len)),
+            Sram => self.ram.extend(ff_iter.take((32 * KB) - len)),
+            Flash64(_) => self.ram.extend(ff_iter.take((64 * KB) - len)),
+            Flash128 { .. } => self.ram.extend(ff_iter.take((128 * KB) - len)),
+        }
+    }
+
+    pub fn make_save(&self) -> Option<GameSave> {
+        let rtc = self.gpio.as_ref().and_then(|g| g.rtc.as_ref());
+        match self.save_type {
+            Nothing if rtc.is_none() => None,
+            _ => Some(GameSave {
+                ram: self.ram.clone(),
+                rtc: rtc.map(|rtc| rtc.start),
+                title: self.title(),
+            }),
+        }
+    }
+
+    pub fn load_save(&mut self, mut save: GameSave) {
+        save.ram.resize(self.ram.len(), 0xFF);
+        self.ram = save.ram;
+        let rtc = self.gpio.as_mut().and_then(|g| g.rtc.as_mut());
+        if let (Some(rtc), Some(start)) = (rtc, save.rtc) {
+            rtc.start = start;
+        }
+    }
+
+    pub fn title(&self) -> String {
+        self.read_string(0x0A0, 12)
+    }
+
+    pub fn game_code(&self) -> String {
+        self.read_string(0x0AC, 4)
+    }
+
+    /// Detect cart properties. User overrides take priority over the
+    /// database, which takes priority over detection from the ROM.
+    fn detect(&self, config: &SystemConfig) -> Detection {
+        let code = self.game_code();
+        let mut detection = Detection {
+            save: self.detect_save(),
+            ..Detection::default()
+        };
+
+        for (entry, source) in [
+            (database::lookup(&code), Source::Database),
+            (config.gga_overrides.get(&code), Source::UserOverride),
+        ] {
+            if let Some(entry) = entry {
+                detection.save.or(entry.save, source);
+                detection.flash_chip.or(entry.flash_chip, source);
+                detection.peripherals.or(entry.peripherals, source);
+                detection.mirror.or(entry.mirror, source);
+            }
+        }
+
+        let flash_chip = detection.flash_chip;
+        if flash_chip.source == Source::Default {
+            detection.flash_chip.value = detection.save.value.default_flash_chip();
+        } else if let Some(size) = detection.save.value.flash_size() {
+            // The chip determines the size of the save
+            if size != flash_chip.value.is_128k() {
+                detection.save = Detected {
+                    value: SaveKind::flash(flash_chip.value),
+                    source: flash_chip.source,
+                };
+            }
+        }
+        if config.force_gga_rtc && !detection.peripherals.value.contains(Peripherals::RTC) {
+            detection.peripherals.value |= Peripherals::RTC;
+            detection.peripherals.source = Source::SystemConfig;
+        }
+        detection
+    }
+
+    fn detect_save(&self) -> Detected<SaveKind> {
+        // This is not efficient
+        let save_types: [(SaveKind, &str); 5] = [
+            (SaveKind::Flash128, "FLASH1M_V"),
+            (SaveKind::Flash64, "FLASH_V"),
+            (SaveKind::Flash64, "FLASH512_V"),
+            (SaveKind::Sram, "SRAM_V"),
+            (SaveKind::Eeprom(EepromSize::Unknown), "EEPROM_V"),
+        ];
+        let self_str = String::from_utf8_lossy(&self.rom);
+        for (value, str) in save_types {
+            if self_str.contains(str) {
+                return Detected {
+                    value,
+                    source: Source::RomString(str),
+                };
+            }
+        }
+        Detected::default()
+    }
+
+    fn read_string(&self, base: usize, max: usize) -> String {
+        let mut buf = String::new();
+        for idx in 0..max {
+            let ch = self.rom[base + idx] as char;
+            if ch == '\0' {
+                break;
+            }
+            buf.push(ch);
+        }
+        buf
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub enum SaveType {
+    #[default]
+    Nothing,
+    Eeprom(Eeprom),
+    Sram,
+    Flash64(FlashState),
+    Flash128 {
+        state: FlashState,
+        bank: u8,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Eeprom {
+    size: EepromSize,
+    command: EepromCmd,
+
+    recv_buffer: u128,
+    recv_count: u32,
+
+    send_buffer: u128,
+    send_count: RefCell<u32>,
+}
+
+impl Eeprom {
+    pub fn read(&self) -> u16 {
+        let mut count = self.send_count.borrow_mut();
+        if *count == 0 {
+            1
+        } else {
+            *count -= 1;
+            (self.send_buffer >> *count) as u16 & 1
+        }
+    }
+
+    pub fn write(&mut self, value: u16, ram: &mut [u8]) {
+        let bit = value & 1;
+        self.recv_buffer = (self.recv_buffer << 1) | bit as u128;
+        self.recv_count += 1;
+
+        if self.recv_count == 2 {
+            self.command = match self.recv_buffer & 3 {
+                0b11 => EepromCmd::Read,
+                0b10 => EepromCmd::Write,
+                _ => {
+                    self.reset_rx();
+                    EepromCmd::Nothing
+                }
+            };
+        } else if self.recv_count == self.cmd_size(self.command) {
+            self.recv_buffer >>= 1; // Shift out the 0 bit
+            match self.command {
+                EepromCmd::Nothing => panic!("invalid command!"),
+
+                EepromCmd::Read => {
+                    let recv_buffer = self.recv_buffer as u32;
+                    let addr = recv_buffer.bits(0, self.size as u32) & 0x3FF;
+                    let idx = addr.us() << 3; // Addressing is in 64bits, not 8bits
+                    for byte in ram.iter_mut().skip(idx).take(8) {
+                        self.send_buffer <<= 8;
+                        self.send_buffer |= *byte as u128;
+                    }
+                    *self.send_count.borrow_mut() = 68; // 4 dummy bits
+                }
+
+                EepromCmd::Write => {
+                    let mut data = self.recv_buffer as u64;
+                    let addr = ((self.recv_buffer >> 64) as u32).bits(0, self.size as u32) & 0x3FF;
+                    let idx = addr.us() << 3; // Addressing is in 64bits, not 8bits
+                    for byte in ram.iter_mut().skip(idx).take(8).rev() {
+                        *byte = data as u8;
+                        data >>= 8;
+                    }
+                    // We want to send 1's, which indicate the operation is done.
+                    self.send_buffer = u128::MAX;
+                    *self.send_count.borrow_mut() = 128;
+                }
+            }
+            self.reset_rx();
+        }
+    }
+
+    pub fn dma3_started(&mut self, dst: u32, cnt: u32) {
+        // Try to detect EEPROM size
+        if self.size == EepromSize::Unknown && (0xD00_0000..=0xDFF_FFFF).contains(&dst) {
+            self.size = match cnt {
+                9 | 73 => EepromSize::E512,
+                17 | 81 => EepromSize::E8k,
+                _ => EepromSize::Unknown,
+            }
+        }
+        // Reset state, just in case
+        self.reset_rx();
+    }
+
+    fn reset_rx(&mut self) {
+        self.recv_count = 0;
+        self.recv_buffer = 0;
+    }
+
+    fn cmd_size(&self, cmd: EepromCmd) -> u32 {
+        2 + self.size as u32 + cmd as u32
+    }
+
+    const fn new(size: EepromSize) -> Self {
+        Self {
+            size,
+            command: EepromCmd::Nothing,
+            recv_buffer: 0,
+            recv_count: 0,
+            send_buffer: 0,
+            send_count: RefCell::new(0),
+        }
+    }
+}
+
+/// Eeprom size. Integer equals address size.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub enum EepromSize {
+    Unknown = 99,
+    E512 = 6,
+    E8k = 14,
+}
+
+/// Eeprom commands. Size equals length of the command, minus
+/// the 2 bits of the command itself and the address.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub enum EepromCmd {
+    Nothing = 0,
+    Read = 1,
+    Write = 65,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::SystemConfig,
+        gga::cartridge::{
+            database::{CartOverride, Detected, Peripherals, SaveKind, Source},
+            flash::FlashChip,
+            Cartridge,
+        },
+    };
+
+    /// A ROM with the given game code, using the SRAM library.
+    fn rom(code: &str) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0xAC..0xB0].copy_from_slice(code.as_bytes());
+        rom[0x1000..0x1006].copy_from_slice(b"SRAM_V");
+        rom
+    }
+
+    fn detect(code: &str, config: &SystemConfig) -> Cartridge {
+        let mut cart = Cartridge::default();
+        cart.load_rom(rom(code), config);
+        cart
+    }
+
+    #[test]
+    fn detect_from_rom() {
+        let cart = detect("ZZZE", &SystemConfig::default());
+        assert_eq!(
+            cart.detection.save,
+            Detected {
+                value: SaveKind::Sram,
+                source: Source::RomString("SRAM_V"),
+            }
+        );
+        assert_eq!(cart.detection.peripherals.source, Source::Default);
+    }
+
+    #[test]
+    fn database_wins() {
+        // Pokemon Ruby
+        let cart = detect("AXVE", &SystemConfig::default());
+        let detection = cart.detection;
+        assert_eq!(
+            detection.save,
+            Detected {
+                value: SaveKind::Flash128,
+                source: Source::Database,
+            }
+        );
+        assert_eq!(
+            detection.peripherals,
+            Detected {
+                value: Peripherals::RTC,
+                source: Source::Database,
+            }
+        );
+        assert_eq!(detection.flash_chip.value, FlashChip::Macronix128);
+        assert!(cart.gpio.is_some_and(|gpio| gpio.rtc.is_some()));
+    }
+
+    #[test]
+    fn user_override_wins() {
+        let mut config = SystemConfig::default();
+        config.gga_overrides.insert(
+            "AXVE".to_string(),
+            CartOverride {
+                flash_chip: Some(FlashChip::Sanyo128),
+                peripherals: Some(Peripherals::empty()),
+                ..CartOverride::default()
+            },
+        );
+        let detection = detect("AXVE", &config).detection;
+        // Not overridden, still from the database
+        assert_eq!(detection.save.source, Source::Database);
+        assert_eq!(
+            detection.flash_chip,
+            Detected {
+                value: FlashChip::Sanyo128,
+                source: Source::UserOverride,
+            }
+        );
+        assert_eq!(
+            detection.peripherals,
+            Detected {
+                value: Peripherals::empty(),
+                source: Source::UserOverride,
+            }
+        );
+
+        // A 64K chip also changes the save size
+        config.gga_overrides.get_mut("AXVE").unwrap().flash_chip = Some(FlashChip::Sst64);
+        let detection = detect("AXVE", &config).detection;
+        assert_eq!(
+            detection.save,
+            Detected {
+                value: SaveKind::Flash64,
+                source: Source::UserOverride,
+            }
+        );
+    }
+}
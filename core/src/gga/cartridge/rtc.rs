@@ -0,0 +1,364 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{components::clock::since_unix, numutil::NumExt};
+
+/// UNIX time of 2000-01-01 00:00:00, which is the RTC's epoch.
+const EPOCH_2000: u64 = 946_684_800;
+const SECS_PER_DAY: u64 = 86400;
+/// Days in the 100 years the RTC can count before its year wraps to 00.
+const DAYS_PER_CENTURY: u64 = 36525;
+
+// Status register bits
+const STATUS_24H: u16 = 6;
+const STATUS_WRITABLE: u8 = 0b0110_1010;
+
+/// Seiko S-3511 real-time clock, connected to the cartridge GPIO port.
+/// Pins are SCK (0), SIO (1) and CS (2).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rtc {
+    /// UNIX time at which the clock would have read 2000-01-01 00:00:00.
+    /// Uses wrapping arithmetic, since games can set the clock to any time.
+    pub(crate) start: u64,
+    status: u8,
+
+    /// Previous state of the SCK pin, to detect rising edges.
+    sck: bool,
+    /// Value of the SIO pin, when driven by the RTC.
+    sio: bool,
+    /// Bits received/sent of the current byte.
+    bit: u8,
+    state: RtcState,
+}
+
+impl Rtc {
+    /// The pins driven by the RTC.
+    pub(super) fn pins(&self) -> u8 {
+        (self.sio as u8) << 1
+    }
+
+    /// Update the pins driven by the GGA.
+    /// Returns true if the RTC requested an interrupt.
+    pub(super) fn write(&mut self, pins: u8) -> bool {
+        let sck = pins.is_bit(0);
+        let sio = pins.is_bit(1);
+        let cs = pins.is_bit(2);
+
+        let mut irq = false;
+        if !cs {
+            // Transfer ended or not started yet
+            self.state = RtcState::Command(0);
+            self.bit = 0;
+            self.sio = true;
+        } else if !self.sck && sck {
+            irq = self.clock(sio);
+        }
+        self.sck = sck;
+        irq
+    }
+
+    /// Rising clock edge, transfer a bit in/out (LSB first).
+    fn clock(&mut self, sio: bool) -> bool {
+        let bit = self.bit;
+        self.bit = (self.bit + 1) & 7;
+
+        match &mut self.state {
+            RtcState::Command(cmd) => {
+                *cmd |= (sio as u8) << bit;
+                if bit == 7 {
+                    // Commands are sent MSB first, unlike everything else
+                    let command = cmd.reverse_bits();
+                    return self.command(command);
+                }
+            }
+
+            RtcState::Read { data, idx } => {
+                self.sio = data[*idx].is_bit(bit.u16());
+                if bit == 7 {
+                    *idx = (*idx + 1).min(data.len() - 1);
+                }
+            }
+
+            RtcState::Write {
+                cmd,
+                data,
+                idx,
+                len,
+            } => {
+                data[*idx] |= (sio as u8) << bit;
+                if bit == 7 {
+                    *idx += 1;
+                    if *idx == *len {
+                        let (cmd, data) = (*cmd, *data);
+                        self.state = RtcState::Done;
+                        self.execute_write(cmd, data);
+                    }
+                }
+            }
+
+            RtcState::Done => (),
+        }
+        false
+    }
+
+    /// Execute a command byte, which has the format `0110CCCR`.
+    /// Returns true if it requested an interrupt.
+    fn command(&mut self, command: u8) -> bool {
+        if command & 0xF0 != 0x60 {
+            log::debug!("Invalid RTC command 0x{command:02X}");
+            self.state = RtcState::Done;
+            return false;
+        }
+
+        let cmd = (command >> 1) & 7;
+        let len = match cmd {
+            1 => 1,
+            2 => 7,
+            3 => 3,
+            _ => 0,
+        };
+        self.state = match cmd {
+            // Reset
+            0 => {
+                self.status = 0;
+                RtcState::Done
+            }
+            // Force an interrupt
+            6 => {
+                self.state = RtcState::Done;
+                return true;
+            }
+
+            _ if len == 0 => RtcState::Done,
+            _ if command.is_bit(0) => {
+                let mut data = [0; 7];
+                match cmd {
+                    1 => data[0] = self.status,
+                    2 => data = self.date_time(),
+                    _ => data[..3].copy_from_slice(&self.date_time()[4..]),
+                }
+                RtcState::Read { data, idx: 0 }
+            }
+            _ => RtcState::Write {
+                cmd,
+                data: [0; 7],
+                idx: 0,
+                len,
+            },
+        };
+        false
+    }
+
+    fn execute_write(&mut self, cmd: u8, data: [u8; 7]) {
+        match cmd {
+            1 => self.status = data[0] & STATUS_WRITABLE,
+            2 => self.set_date_time(data),
+            _ => {
+                // Time only, keep the current date
+                let mut date_time = self.date_time();
+                date_time[4..].copy_from_slice(&data[..3]);
+                self.set_date_time(date_time);
+            }
+        }
+    }
+
+    /// Current date and time in the RTC's format:
+    /// year, month, day, weekday, hour, minute, second; all in BCD.
+    fn date_time(&self) -> [u8; 7] {
+        // Starts before 1970, from setting a date after 2054, wrap around.
+        // A start after the current time, from the host clock being set
+        // back, is treated as 2000-01-01 00:00:00.
+        let secs = since_unix().wrapping_sub(self.start);
+        let secs = if (secs as i64) < 0 { 0 } else { secs };
+        let days = secs / SECS_PER_DAY;
+        let time = secs % SECS_PER_DAY;
+        let (year, month, day) = civil_from_days(days % DAYS_PER_CENTURY);
+        // 2000-01-01 was a saturday
+        let weekday = (days + 6) % 7;
+
+        let hour = time / 3600;
+        let pm = (hour >= 12) as u8;
+        let hour = if self.status.is_bit(STATUS_24H) {
+            hour
+        } else {
+            hour % 12
+        };
+
+        [
+            bcd(year),
+            bcd(month),
+            bcd(day),
+            bcd(weekday),
+            bcd(hour) | (pm << 7),
+            bcd((time / 60) % 60),
+            bcd(time % 60),
+        ]
+    }
+
+    fn set_date_time(&mut self, data: [u8; 7]) {
+        let year = from_bcd(data[0]);
+        let month = from_bcd(data[1] & 0x1F).clamp(1, 12);
+        let day = from_bcd(data[2] & 0x3F).clamp(1, 31);
+        let mut hour = from_bcd(data[4] & 0x3F);
+        if !self.status.is_bit(STATUS_24H) && data[4].is_bit(7) {
+            hour += 12;
+        }
+        let minute = from_bcd(data[5] & 0x7F);
+        let second = from_bcd(data[6] & 0x7F);
+
+        let secs =
+            days_from_civil(year, month, day) * SECS_PER_DAY + hour * 3600 + minute * 60 + second;
+        self.start = since_unix().wrapping_sub(secs);
+    }
+}
+
+impl Default for Rtc {
+    fn default() -> Self {
+        Self {
+            // Start out at the current time
+            start: EPOCH_2000,
+            status: 1 << STATUS_24H,
+            sck: false,
+            sio: true,
+            bit: 0,
+            state: RtcState::Command(0),
+        }
+    }
+}
+
+/// State of the RTC's serial transfer.
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+enum RtcState {
+    /// Receiving a command byte.
+    Command(u8),
+    /// Sending data to the GGA.
+    Read { data: [u8; 7], idx: usize },
+    /// Receiving `len` bytes of data for the given command.
+    Write {
+        cmd: u8,
+        data: [u8; 7],
+        idx: usize,
+        len: usize,
+    },
+    /// Transfer finished, waiting for CS to go low.
+    Done,
+}
+
+fn bcd(value: u64) -> u8 {
+    (((value / 10) << 4) | (value % 10)) as u8
+}
+
+fn from_bcd(value: u8) -> u64 {
+    ((value >> 4) * 10 + (value & 0xF)) as u64
+}
+
+/// Convert days since 2000-01-01 to (year since 2000, month, day).
+/// Only valid until 2100, which is fine since the RTC's year only has 2
+/// digits; callers wrap the days to [DAYS_PER_CENTURY].
+fn civil_from_days(mut days: u64) -> (u64, u64, u64) {
+    let mut year = 0u64;
+    loop {
+        let year_len = if year.is_multiple_of(4) { 366 } else { 365 };
+        if days < year_len {
+            break;
+        }
+        days -= year_len;
+        year += 1;
+    }
+
+    let mut month = 1;
+    while days >= month_len(year, month) {
+        days -= month_len(year, month);
+        month += 1;
+    }
+    (year, month, days + 1)
+}
+
+/// Convert (year since 2000, month, day) to days since 2000-01-01.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let years: u64 = (0..year)
+        .map(|y| if y.is_multiple_of(4) { 366 } else { 365 })
+        .sum();
+    let months: u64 = (1..month).map(|m| month_len(year, m)).sum();
+    years + months + day - 1
+}
+
+fn month_len(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year.is_multiple_of(4) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        components::clock::since_unix,
+        gga::cartridge::rtc::{
+            civil_from_days, days_from_civil, Rtc, DAYS_PER_CENTURY, SECS_PER_DAY,
+        },
+    };
+
+    #[test]
+    fn civil_dates() {
+        for (days, date) in [
+            (0, (0, 1, 1)),
+            (30, (0, 1, 31)),
+            (59, (0, 2, 29)),
+            (60, (0, 3, 1)),
+            (365, (0, 12, 31)),
+            (366, (1, 1, 1)),
+            (424, (1, 2, 28)),
+            (425, (1, 3, 1)),
+            (8825, (24, 2, 29)),
+            (DAYS_PER_CENTURY - 1, (99, 12, 31)),
+        ] {
+            assert_eq!(civil_from_days(days), date, "{days}");
+            assert_eq!(days_from_civil(date.0, date.1, date.2), days, "{date:?}");
+        }
+    }
+
+    #[test]
+    fn set_and_read() {
+        let mut rtc = Rtc::default();
+        // 2024-02-29, a thursday, 13:45:00 in BCD; the PM flag is set in
+        // 24-hour mode too
+        rtc.set_date_time([0x24, 0x02, 0x29, 0, 0x13, 0x45, 0]);
+        assert_eq!(rtc.date_time()[..6], [0x24, 0x02, 0x29, 0x04, 0x93, 0x45]);
+
+        // 12-hour mode, with the PM flag
+        rtc.status = 0;
+        assert_eq!(rtc.date_time()[4], 0x81);
+
+        // After 2054, the start is before 1970
+        rtc.status = Rtc::default().status;
+        rtc.set_date_time([0x99, 0x12, 0x31, 0, 0x23, 0, 0]);
+        assert_eq!(rtc.date_time()[..5], [0x99, 0x12, 0x31, 0x04, 0xA3]);
+    }
+
+    #[test]
+    fn start_in_the_future() {
+        // Restored from a save with the host clock set back
+        let rtc = Rtc {
+            start: since_unix() + SECS_PER_DAY,
+            ..Rtc::default()
+        };
+        assert_eq!(rtc.date_time(), [0, 1, 1, 6, 0, 0, 0]);
+    }
+
+    #[test]
+    fn century_wraps() {
+        let rtc = Rtc {
+            start: since_unix().wrapping_sub((DAYS_PER_CENTURY + 1) * SECS_PER_DAY),
+            ..Rtc::default()
+        };
+        assert_eq!(rtc.date_time()[..3], [0, 1, 2]);
+    }
+}
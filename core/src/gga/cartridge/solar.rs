@@ -0,0 +1,50 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::{Deserialize, Serialize};
+
+use crate::numutil::NumExt;
+
+/// Solar sensor of the Boktai carts, connected to the cartridge GPIO port.
+/// Pins are CLK (0), RST (1), CS (2, low active) and FLAG (3, output).
+/// The game counts clock pulses until FLAG goes high; the brighter the
+/// light, the fewer pulses it takes.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SolarSensor {
+    /// Current light level, 0 is complete darkness.
+    pub(crate) light: u8,
+    /// Pulses counted since the last reset.
+    counter: u16,
+    /// Pulse count at which FLAG goes high, sampled on reset.
+    sample: u8,
+    /// Previous state of the CLK pin, to detect rising edges.
+    clk: bool,
+}
+
+impl SolarSensor {
+    /// The pins driven by the sensor.
+    pub(super) fn pins(&self) -> u8 {
+        ((self.counter >= self.sample.u16()) as u8) << 3
+    }
+
+    /// Update the pins driven by the GGA.
+    pub(super) fn write(&mut self, pins: u8) {
+        if pins.is_bit(2) {
+            // Not selected
+            return;
+        }
+
+        if pins.is_bit(1) {
+            self.counter = 0;
+            self.sample = 0xFF - self.light;
+        }
+        let clk = pins.is_bit(0);
+        if clk && !self.clk {
+            self.counter += 1;
+        }
+        self.clk = clk;
+    }
+}
@@ -0,0 +1,59 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::{Deserialize, Serialize};
+
+use crate::numutil::U16Ext;
+
+/// Sample value when level.
+const CENTER: f32 = 0x3A0 as f32;
+/// Sample difference from the center when fully tilted.
+const RANGE: f32 = 0x200 as f32;
+
+/// ADXL202 accelerometer of Yoshi's Topsy-Turvy and Koro Koro Puzzle.
+/// Mapped into the save area at 0x0E008000-0x0E0085FF; these carts use
+/// EEPROM for saving, so it does not conflict.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct TiltSensor {
+    /// Current tilt, both axes from -1.0 to 1.0.
+    pub(crate) tilt: [f32; 2],
+    /// Latched X/Y samples.
+    latched: [u16; 2],
+    /// If the first latch command (0x55) was written.
+    latch_prepared: bool,
+}
+
+impl TiltSensor {
+    /// If the given save area address belongs to the sensor.
+    pub fn is_mapped(addr: usize) -> bool {
+        (0x8000..=0x85FF).contains(&addr)
+    }
+
+    pub(super) fn read(&self, addr: usize) -> u8 {
+        let [x, y] = self.latched;
+        match addr & 0xFF00 {
+            0x8200 => x.low(),
+            // Bit 7 indicates that the sample is ready
+            0x8300 => (x.high() & 0xF) | 0x80,
+            0x8400 => y.low(),
+            0x8500 => y.high() & 0xF,
+            _ => 0,
+        }
+    }
+
+    pub(super) fn write(&mut self, addr: usize, value: u8) {
+        match (addr & 0xFF00, value) {
+            (0x8000, 0x55) => self.latch_prepared = true,
+            (0x8100, 0xAA) if self.latch_prepared => {
+                self.latch_prepared = false;
+                for (latched, tilt) in self.latched.iter_mut().zip(self.tilt) {
+                    *latched = (CENTER + tilt.clamp(-1.0, 1.0) * RANGE) as u16;
+                }
+            }
+            _ => (),
+        }
+    }
+}
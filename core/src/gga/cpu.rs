@@ -5,11 +5,14 @@
 // obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::{
-    components::arm::{
-        interface::{ArmSystem, RwType},
-        Access, Cpu, Exception,
+    components::{
+        arm::{
+            interface::{ArmSystem, RwType, SysWrapper},
+            Access, Cpu, Exception,
+        },
+        debugger::{AccessKind, Debuggable, Debugger},
     },
-    gga::{addr, addr::WAITCNT, GameGirlAdv},
+    gga::{addr, addr::WAITCNT, bios, GameGirlAdv},
     numutil::NumExt,
 };
 
@@ -60,6 +63,10 @@ impl ArmSystem for GameGirlAdv {
         self.memory.prefetch_len = 0;
     }
 
+    fn handle_swi(gg: &mut SysWrapper<Self>, comment: u8) -> bool {
+        gg.config.gga_hle_bios && bios::handle_swi(gg, comment)
+    }
+
     fn get<T: RwType>(&mut self, addr: u32) -> T {
         match T::WIDTH {
             1 => T::from_u8(self.get_byte(addr)),
@@ -81,8 +88,25 @@ impl ArmSystem for GameGirlAdv {
     }
 
     fn check_debugger(&mut self) -> bool {
-        self.options.running &= self.debugger.should_execute(self.cpu.pc());
-        self.options.running
+        let pc = self.cpu.pc();
+        let execute = self.check_execute(pc);
+        if !execute && self.options.running {
+            self.options.running = false;
+            self.options.notify(format!("Breakpoint hit at {pc:08X}"));
+        }
+        execute
+    }
+
+    fn check_watchpoint(&mut self, kind: AccessKind, addr: u32, value: u32) {
+        let cont = match kind {
+            AccessKind::Write => self.check_write(addr, value),
+            _ => self.check_read(addr, value),
+        };
+        if !cont && self.options.running {
+            self.options.running = false;
+            self.options
+                .notify(format!("{kind} breakpoint hit at {addr:08X}"));
+        }
     }
 
     fn can_cache_at(pc: u32) -> bool {
@@ -91,3 +115,33 @@ impl ArmSystem for GameGirlAdv {
             || (0x800_0000..=0xDFF_FFFF).contains(&pc)
     }
 }
+
+impl Debuggable for GameGirlAdv {
+    type Ptr = u32;
+    const REGISTERS: &'static [&'static str] = &[
+        "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "r13",
+        "r14", "r15", "sp", "lr", "pc", "cpsr",
+    ];
+
+    fn debugger(&self) -> &Debugger<u32> {
+        &self.debugger
+    }
+
+    fn debugger_mut(&mut self) -> &mut Debugger<u32> {
+        &mut self.debugger
+    }
+
+    fn register(&self, idx: usize) -> u32 {
+        match idx {
+            0..=12 => self.cpu.reg(idx.u32()),
+            13 | 16 => self.cpu.sp(),
+            14 | 17 => self.cpu.lr(),
+            15 | 18 => self.cpu.pc(),
+            _ => self.cpu.cpsr,
+        }
+    }
+
+    fn peek(&self, addr: u32) -> u8 {
+        self.get_byte(addr)
+    }
+}
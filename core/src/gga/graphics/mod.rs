@@ -13,14 +13,16 @@
 
 mod bitmap;
 mod objects;
-mod palette;
+pub mod palette;
 mod render;
 pub mod threading;
 mod tile;
 
+use std::sync::Arc;
+
 use serde::{Deserialize, Serialize};
 
-use self::threading::PpuType;
+use self::{palette::ColourProfile, threading::PpuType};
 use super::memory::KB;
 use crate::{
     common::BorrowedSystem,
@@ -93,6 +95,11 @@ pub struct Ppu {
     bg_x: [i32; 2],
     bg_y: [i32; 2],
 
+    /// Colours shown for each RGB555 colour, see [ColourProfile].
+    #[serde(skip)]
+    #[serde(default = "serde_colour_table")]
+    pub(crate) colours: Arc<[Colour]>,
+
     #[cfg_attr(not(feature = "threaded-ppu"), serde(default))]
     #[cfg_attr(not(feature = "threaded-ppu"), serde(skip))]
     #[cfg(not(feature = "threaded-ppu"))]
@@ -269,23 +276,22 @@ impl Ppu {
 
         if blend_mode == 0 {
             // Fast path: No blending
-            Self::adjust_pixel(&mut backdrop);
-
             #[cfg(not(feature = "threaded-ppu"))]
             let ppu = &mut gg.ppu;
             #[cfg(feature = "threaded-ppu")]
             let ppu = &mut *gg.ppu;
+            palette::output_colour(&ppu.colours, &mut backdrop);
 
             'pixels: for (x, pixel) in ppu.pixels[start..].iter_mut().take(240).enumerate() {
                 for prio in 0..4 {
                     if ppu.obj_layers[prio][x][3] != EMPTY_A {
                         *pixel = ppu.obj_layers[prio][x];
-                        Self::adjust_pixel(pixel);
+                        palette::output_colour(&ppu.colours, pixel);
                         continue 'pixels;
                     }
                     if ppu.bg_layers[prio][x][3] != EMPTY_A {
                         *pixel = ppu.bg_layers[prio][x];
-                        Self::adjust_pixel(pixel);
+                        palette::output_colour(&ppu.colours, pixel);
                         continue 'pixels;
                     }
                 }
@@ -385,8 +391,12 @@ impl Ppu {
                 );
             }
 
-            for pixel in gg.ppu.pixels[start..].iter_mut().take(240) {
-                Self::adjust_pixel(pixel);
+            #[cfg(not(feature = "threaded-ppu"))]
+            let ppu = &mut gg.ppu;
+            #[cfg(feature = "threaded-ppu")]
+            let ppu = &mut *gg.ppu;
+            for pixel in ppu.pixels[start..].iter_mut().take(240) {
+                palette::output_colour(&ppu.colours, pixel);
             }
         }
 
@@ -473,13 +483,6 @@ impl Ppu {
 
         ppu.pixels.to_vec()
     }
-
-    #[inline]
-    fn adjust_pixel(pixel: &mut Colour) {
-        for col in pixel.iter_mut().take(3) {
-            *col = (*col << 3) | (*col >> 2);
-        }
-    }
 }
 
 impl Default for Ppu {
@@ -498,6 +501,7 @@ impl Default for Ppu {
             obj_layers: serde_layer_arr(),
             win_masks: serde_mask_arr(),
             win_blend: serde_mask2_arr(),
+            colours: serde_colour_table(),
 
             #[cfg(not(feature = "threaded-ppu"))]
             last_frame: None,
@@ -508,6 +512,13 @@ impl Default for Ppu {
 fn serde_colour_arr() -> [Colour; 240 * 160] {
     [[0, 0, 0, 255]; 240 * 160]
 }
+fn serde_colour_table() -> Arc<[Colour]> {
+    thread_local! {
+        // Built once, since a PPU is created on every state load
+        static TABLE: Arc<[Colour]> = ColourProfile::None.lookup_table();
+    }
+    TABLE.with(Arc::clone)
+}
 fn serde_layer_arr() -> [Layer; 4] {
     [[EMPTY; 240]; 4]
 }
@@ -4,12 +4,118 @@
 // If a copy of the MPL2 was not distributed with this file, you can
 // obtain one at https://mozilla.org/MPL/2.0/.
 
+use std::{
+    fmt::{Display, Formatter},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
 use crate::{
     gga::graphics::Ppu,
     numutil::{hword, NumExt},
     Colour,
 };
 
+/// Gamma the output is encoded with, the one of common monitors.
+const OUTPUT_GAMMA: f64 = 2.2;
+
+/// Colour profiles imitating the screens of different GGA models, which
+/// all show colours a lot less saturated than a modern monitor would.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColourProfile {
+    /// Raw colours, expanded linearly.
+    None,
+    /// Original model, with a dark reflective screen.
+    Agb001,
+    /// First SP model, with the same screen lit from the front.
+    Ags001,
+    /// Later SP model, with a backlit screen.
+    Ags101,
+}
+
+impl ColourProfile {
+    pub const ALL: [Self; 4] = [Self::None, Self::Agb001, Self::Ags001, Self::Ags101];
+
+    /// Gamma of the screen, a factor for the brightness of the output, and
+    /// how much each input channel contributes to each output channel.
+    /// AGB-001 is from Near's article on colour emulation,
+    /// <https://near.sh/articles/video/color-emulation>.
+    /// The SP models are from Pokefan531's `gba-color` and `sp101-color`
+    /// shaders, <https://github.com/libretro/slang-shaders/tree/master/handheld/shaders/color>,
+    /// which apply their luminance before encoding the output.
+    fn parameters(self) -> (f64, f64, [[f64; 3]; 3]) {
+        let luminance = |lum: f64| lum.powf(1.0 / OUTPUT_GAMMA);
+        match self {
+            Self::None => unreachable!(),
+            Self::Agb001 => (
+                4.0,
+                255.0 / 280.0,
+                [[255.0, 50.0, 0.0], [10.0, 230.0, 30.0], [50.0, 10.0, 220.0]]
+                    .map(|row| row.map(|w| w / 255.0)),
+            ),
+            Self::Ags001 => (
+                2.2,
+                luminance(0.93),
+                [
+                    [0.80, 0.275, -0.075],
+                    [0.135, 0.64, 0.225],
+                    [0.195, 0.155, 0.65],
+                ],
+            ),
+            Self::Ags101 => (
+                2.2,
+                luminance(0.94),
+                [[0.96, 0.02, 0.02], [0.11, 0.89, 0.0], [-0.01, 0.01, 1.0]],
+            ),
+        }
+    }
+
+    /// Build the table mapping every RGB555 colour to the colour it is
+    /// shown as with this profile.
+    pub fn lookup_table(self) -> Arc<[Colour]> {
+        let table = (0..0x8000u16).map(|raw| {
+            let rgb = [raw.bits(0, 5), raw.bits(5, 5), raw.bits(10, 5)];
+            if self == Self::None {
+                let [r, g, b] = rgb.map(|c| ((c << 3) | (c >> 2)).u8());
+                return [r, g, b, 255];
+            }
+
+            let (lcd_gamma, brightness, matrix) = self.parameters();
+            let linear = rgb.map(|c| (c as f64 / 31.0).powf(lcd_gamma));
+            let [r, g, b] = matrix.map(|row| {
+                let mixed = row.iter().zip(linear).map(|(w, c)| w * c).sum::<f64>();
+                let out = mixed.clamp(0.0, 1.0).powf(1.0 / OUTPUT_GAMMA) * brightness * 255.0;
+                out.round().clamp(0.0, 255.0) as u8
+            });
+            [r, g, b, 255]
+        });
+        table.collect()
+    }
+}
+
+/// Convert a finished pixel from the RGB555 the PPU works with to the
+/// colour shown, using a colour profile's lookup table. Alpha is kept.
+/// Channels brighter than the maximum after blending are saturated.
+#[inline]
+pub(super) fn output_colour(colours: &[Colour], pixel: &mut Colour) {
+    let [r, g, b, _] = pixel.map(|c| c.min(31).u16());
+    let raw = r | (g << 5) | (b << 10);
+    pixel[..3].copy_from_slice(&colours[raw.us()][..3]);
+}
+
+impl Display for ColourProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::None => "None",
+            Self::Agb001 => "GBA (AGB-001)",
+            Self::Ags001 => "GBA SP (AGS-001)",
+            Self::Ags101 => "GBA SP (AGS-101)",
+        };
+        write!(f, "{name}")
+    }
+}
+
 impl Ppu {
     pub fn hword_to_colour_vram(&self, addr: usize) -> Colour {
         let lo = self.vram[addr];
@@ -24,6 +130,11 @@ impl Ppu {
         Self::hword_to_colour(hword(lo, hi))
     }
 
+    /// Set the colour profile applied to the output.
+    pub fn set_colour_profile(&mut self, profile: ColourProfile) {
+        self.colours = profile.lookup_table();
+    }
+
     fn hword_to_colour(hword: u16) -> Colour {
         let r = hword.bits(0, 5).u8();
         let g = hword.bits(5, 5).u8();
@@ -31,3 +142,31 @@ impl Ppu {
         [r, g, b, 255]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::gga::graphics::palette::ColourProfile;
+
+    #[test]
+    fn black_and_white() {
+        let white = |profile: ColourProfile| profile.lookup_table()[0x7FFF];
+        for profile in ColourProfile::ALL {
+            assert_eq!(profile.lookup_table()[0], [0, 0, 0, 255], "{profile}");
+        }
+        assert_eq!(white(ColourProfile::None), [255; 4]);
+        assert_eq!(white(ColourProfile::Agb001), [232, 232, 232, 255]);
+        assert_eq!(white(ColourProfile::Ags001), [247, 247, 247, 255]);
+        assert_eq!(white(ColourProfile::Ags101), [248, 248, 248, 255]);
+    }
+
+    #[test]
+    fn primaries() {
+        // Pure red bleeds into the other channels on the GBA screen
+        let red = |profile: ColourProfile| profile.lookup_table()[0x1F];
+        assert_eq!(red(ColourProfile::None), [255, 0, 0, 255]);
+        let [r, g, b, _] = red(ColourProfile::Agb001);
+        assert!(r > g && g > 0 && b > 0);
+        let [r, g, b, _] = red(ColourProfile::Ags101);
+        assert!(r > g && g > 0 && b == 0);
+    }
+}
@@ -21,12 +21,14 @@ use crate::{
         },
         memory::{MemoryMappedSystem, MemoryMapper},
     },
-    gga::{addr::*, dma::Dmas, timer::Timers, GameGirlAdv},
+    gga::{
+        addr::*, bios::HLE_BIOS, cartridge::gpio::Gpio, dma::Dmas, scheduling::AdvEvent,
+        timer::Timers, GameGirlAdv,
+    },
     numutil::{hword, word, NumExt, U16Ext, U32Ext},
 };
 
 pub const KB: usize = 1024;
-pub const BIOS: &[u8] = include_bytes!("bios.bin");
 
 /// Memory struct containing the GGA's memory regions along with page tables
 /// and other auxiliary cached information relating to memory.
@@ -38,11 +40,17 @@ pub struct Memory {
     pub iwram: [u8; 32 * KB],
     #[serde(with = "serde_arrays")]
     pub mmio: [u16; KB / 2],
+    /// The BIOS image. Either a dump loaded at runtime or the HLE BIOS.
+    #[serde(skip)]
+    pub(crate) bios: Box<[u8]>,
 
     /// Value to return when trying to read BIOS outside of it
     pub(crate) bios_value: u32,
     /// Length of the prefetch buffer at the current PC.
     pub(crate) prefetch_len: u16,
+    /// If an HLE BIOS IntrWait call is waiting for an interrupt.
+    #[serde(default)]
+    pub(crate) intr_waiting: bool,
 
     mapper: MemoryMapper<8192>,
     wait_word: [u16; 32],
@@ -55,7 +63,7 @@ impl GameGirlAdv {
     #[inline]
     pub(super) fn get_byte(&self, addr: u32) -> u8 {
         MemoryMapper::get(self, addr, !0, |this, addr| match addr {
-            0x0000_0000..=0x0000_3FFF if this.cpu.pc() < 0x0100_0000 => Self::bios_read(addr),
+            0x0000_0000..=0x0000_3FFF if this.cpu.pc() < 0x0100_0000 => this.bios_read(addr),
             0x0000_0000..=0x0000_3FFF => this.memory.bios_value.u8(),
 
             0x0400_0000..=0x04FF_FFFF if addr.is_bit(0) => this.get_mmio(addr).high(),
@@ -67,6 +75,19 @@ impl GameGirlAdv {
                 this.cart.rom[addr.us() - 0x800_0000]
             }
 
+            // GPIO, first ROM page is unmapped while it is readable
+            _ if Gpio::is_register(addr) && this.cart.gpio_readable() => {
+                let value = this.cart.gpio.as_ref().unwrap().read(addr);
+                if addr.is_bit(0) {
+                    value.high()
+                } else {
+                    value.low()
+                }
+            }
+            0x0800_0000..=0x0800_7FFF if this.cart.rom.len() > (addr.us() - 0x800_0000) => {
+                this.cart.rom[addr.us() - 0x800_0000]
+            }
+
             _ => this.invalid_read::<false>(addr).u8(),
         })
     }
@@ -76,7 +97,7 @@ impl GameGirlAdv {
     #[inline]
     pub(super) fn get_hword(&self, addr: u32) -> u16 {
         MemoryMapper::get(self, addr, !1, |this, addr| match addr {
-            0x0000_0000..=0x0000_3FFF if this.cpu.pc() < 0x0100_0000 => Self::bios_read(addr),
+            0x0000_0000..=0x0000_3FFF if this.cpu.pc() < 0x0100_0000 => this.bios_read(addr),
             0x0000_0000..=0x0000_3FFF => this.memory.bios_value.u16(),
 
             0x0400_0000..=0x04FF_FFFF => this.get_mmio(addr),
@@ -86,6 +107,12 @@ impl GameGirlAdv {
             // If not, account for unmapped last page due to EEPROM
             0x0DFF_8000..=0x0DFF_FFFF => hword(this.get_byte(addr), this.get_byte(addr + 1)),
 
+            // GPIO, first ROM page is unmapped while it is readable
+            _ if Gpio::is_register(addr) && this.cart.gpio_readable() => {
+                this.cart.gpio.as_ref().unwrap().read(addr)
+            }
+            0x0800_0000..=0x0800_7FFF => hword(this.get_byte(addr), this.get_byte(addr + 1)),
+
             // Other saves
             0x0E00_0000..=0x0FFF_FFFF => {
                 // Reading halfwords causes the byte to be repeated
@@ -102,15 +129,17 @@ impl GameGirlAdv {
     #[inline]
     pub fn get_word(&self, addr: u32) -> u32 {
         MemoryMapper::get(self, addr, !3, |this, addr| match addr {
-            0x0000_0000..=0x0000_3FFF if this.cpu.pc() < 0x0100_0000 => Self::bios_read(addr),
+            0x0000_0000..=0x0000_3FFF if this.cpu.pc() < 0x0100_0000 => this.bios_read(addr),
             0x0000_0000..=0x0000_3FFF => this.memory.bios_value,
 
             0x0400_0000..=0x04FF_FFFF => {
                 word(this.get_mmio(addr), this.get_mmio(addr.wrapping_add(2)))
             }
 
-            // Account for unmapped last page due to EEPROM
-            0x0DFF_8000..=0x0DFF_FFFF => word(this.get_hword(addr), this.get_hword(addr + 2)),
+            // Account for unmapped last page due to EEPROM and GPIO
+            0x0DFF_8000..=0x0DFF_FFFF | 0x0800_0000..=0x0800_7FFF => {
+                word(this.get_hword(addr), this.get_hword(addr + 2))
+            }
 
             // Other saves
             0x0E00_0000..=0x0FFF_FFFF => {
@@ -229,13 +258,7 @@ impl GameGirlAdv {
             0x0400_00A4..=0x0400_00A7 => self.apu.push_sample::<1>(value),
 
             // HALTCNT
-            0x0400_0301 => {
-                // We're halted, emulate peripherals until an interrupt is pending
-                while (self[IE] & self[IF]) == 0 {
-                    let evt = self.scheduler.pop();
-                    evt.kind.dispatch(self, evt.late_by);
-                }
-            }
+            0x0400_0301 => self.halt(),
 
             // Old sound
             0x0400_0060..=0x0400_0080 | 0x0400_0084 | 0x0400_0090..=0x0400_009F => {
@@ -253,7 +276,10 @@ impl GameGirlAdv {
             0x0400_0000..=0x0400_0301 => self.set_hword(addr, self.get_hword(addr).set_low(value)),
 
             // Cart save
-            0x0E00_0000..=0x0FFF_FFFF => self.cart.write_ram_byte(addr.us() & 0xFFFF, value),
+            0x0E00_0000..=0x0FFF_FFFF => self.write_save(addr, value),
+
+            // GPIO, upper bytes are unused
+            _ if Gpio::is_register(addr) && !addr.is_bit(0) => self.write_gpio(addr, value.u16()),
 
             // VRAM weirdness
             0x0500_0000..=0x0600_FFFF => self.set_hword(addr & !1, hword(value, value)),
@@ -288,9 +314,11 @@ impl GameGirlAdv {
                 } else {
                     value.low()
                 };
-                this.cart.write_ram_byte(addr_unaligned.us() & 0xFFFF, byte);
+                this.write_save(addr_unaligned, byte);
             }
 
+            _ if Gpio::is_register(addr) => this.write_gpio(addr, value),
+
             _ => (),
         });
         self.cpu.cache.write(addr);
@@ -311,8 +339,14 @@ impl GameGirlAdv {
                 // Writing words causes a byte from it to be written
                 let byte_shift = (addr_unaligned & 3) * 8;
                 let byte = (value >> byte_shift) & 0xFF;
-                this.cart
-                    .write_ram_byte(addr_unaligned.us() & 0xFFFF, byte.u8());
+                this.write_save(addr_unaligned, byte.u8());
+            }
+
+            _ if Gpio::is_register(addr) => {
+                this.write_gpio(addr, value.low());
+                if Gpio::is_register(addr + 2) {
+                    this.write_gpio(addr + 2, value.high());
+                }
             }
 
             _ => (),
@@ -320,6 +354,39 @@ impl GameGirlAdv {
         self.cpu.cache.write(addr);
     }
 
+    /// Halt the CPU, emulating peripherals until an interrupt is pending.
+    pub(super) fn halt(&mut self) {
+        while (self[IE] & self[IF]) == 0 {
+            let evt = self.scheduler.pop();
+            evt.kind.dispatch(self, evt.late_by);
+        }
+    }
+
+    fn write_save(&mut self, addr: u32, value: u8) {
+        if let Some(busy) = self.cart.write_ram_byte(addr.us() & 0xFFFF, value) {
+            self.scheduler.schedule(AdvEvent::FlashDone, busy as i32);
+        }
+    }
+
+    fn write_gpio(&mut self, addr: u32, value: u16) {
+        let was_readable = self.cart.gpio_readable();
+        let was_rumbling = self.cart.rumble();
+        let irq = match self.cart.gpio.as_mut() {
+            Some(gpio) => gpio.write(addr, value),
+            None => return,
+        };
+        if irq {
+            Cpu::request_interrupt(self, Interrupt::GamePak);
+        }
+        if was_rumbling != self.cart.rumble() {
+            (self.options.rumble)(!was_rumbling);
+        }
+        if was_readable != self.cart.gpio_readable() {
+            // First ROM page needs to be remapped
+            MemoryMapper::init_pages(self);
+        }
+    }
+
     fn set_mmio(&mut self, addr: u32, value: u16) {
         let a = addr & 0x3FF;
         match a {
@@ -413,9 +480,9 @@ impl GameGirlAdv {
         }
     }
 
-    fn bios_read<T>(addr: u32) -> T {
+    fn bios_read<T>(&self, addr: u32) -> T {
         unsafe {
-            let ptr = BIOS.as_ptr().add(addr.us() & 0x3FFF);
+            let ptr = self.memory.bios.as_ptr().add(addr.us() & 0x3FFF);
             ptr.cast::<T>().read()
         }
     }
@@ -497,8 +564,10 @@ impl Default for Memory {
             ewram: [0; 256 * KB],
             iwram: [0; 32 * KB],
             mmio: [0; KB / 2],
+            bios: Box::new(HLE_BIOS),
             bios_value: 0xE129_F000,
             prefetch_len: 0,
+            intr_waiting: false,
             mapper: MemoryMapper::default(),
             wait_word: [0; 32],
             wait_other: [0; 32],
@@ -569,6 +638,12 @@ impl MemoryMappedSystem<8192> for GameGirlAdv {
             0x0500_0000..=0x05FF_FFFF => offs(&self.ppu_nomut().palette, a - 0x500_0000),
             0x0600_0000..=0x0601_7FFF => offs(&self.ppu_nomut().vram, a - 0x600_0000),
             0x0700_0000..=0x07FF_FFFF => offs(&self.ppu_nomut().oam, a - 0x700_0000),
+            // GPIO registers are handled in the slow path
+            0x0800_0000..=0x0800_7FFF if self.cart.gpio_readable() => ptr::null::<u8>() as *mut u8,
+            // Mirrored ROM; does not go all the way due to EEPROM
+            0x0800_0000..=0x0CFF_FFFF if R && self.cart.is_mirrored() => {
+                offs(&self.cart.rom, (a - 0x800_0000) & self.cart.mirror_mask())
+            }
             0x0800_0000..=0x09FF_FFFF if R && self.cart.rom.len() >= (a - 0x800_0000) => {
                 offs(&self.cart.rom, a - 0x800_0000)
             }
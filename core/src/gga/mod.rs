@@ -7,7 +7,7 @@
 use std::mem;
 
 use audio::Apu;
-use cartridge::Cartridge;
+use cartridge::{Cartridge, SaveType};
 use cpu::CPU_CLOCK;
 use memory::Memory;
 use serde::{Deserialize, Serialize};
@@ -32,18 +32,23 @@ use crate::{
 
 pub mod addr;
 mod audio;
-mod cartridge;
+mod bios;
+pub mod cartridge;
 mod cpu;
 mod dma;
 pub mod graphics;
 mod input;
 mod memory;
+pub mod program;
 mod scheduling;
 mod timer;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub mod remote_debugger;
 
+/// Frames per second: 228 lines of 1232 cycles each.
+pub const FRAME_RATE: f64 = CPU_CLOCK as f64 / (228.0 * 1232.0);
+
 pub type GGADebugger = Debugger<u32>;
 
 /// Console struct representing a GGA. Contains all state and is used for system
@@ -97,15 +102,37 @@ impl GameGirlAdv {
         }
     }
 
+    /// Reset the console, while keeping the current cartridge inserted.
+    pub fn reset(&mut self) {
+        let old_self = mem::take(self);
+        self.restore_from(old_self, true);
+    }
+
     /// Restore state after a savestate load. `old_self` should be the
     /// system state before the state was loaded.
-    pub fn restore_from(&mut self, old_self: Self) {
-        let save = old_self.cart.make_save();
-        self.cart.load_rom(old_self.cart.rom);
+    /// If `restore_save`, the save data of the cart is taken from it too.
+    pub fn restore_from(&mut self, old_self: Self, restore_save: bool) {
+        // Keep the colour profile's table instead of building it again
+        let colours = old_self.ppu_nomut().colours.clone();
+        self.ppu().colours = colours;
+
+        let save = if restore_save {
+            old_self.cart.make_save()
+        } else {
+            None
+        };
+        // Save chip and peripheral state is part of the savestate, keep it
+        let save_type = mem::replace(&mut self.cart.save_type, SaveType::Nothing);
+        let (gpio, tilt) = (self.cart.gpio.take(), self.cart.tilt.take());
+        self.cart.load_rom(old_self.cart.rom, &old_self.config);
         if let Some(save) = save {
             self.cart.load_save(save);
         }
+        self.cart.save_type = save_type;
+        self.cart.gpio = gpio;
+        self.cart.tilt = tilt;
 
+        self.memory.bios = old_self.memory.bios;
         self.options = old_self.options;
         self.config = old_self.config;
         self.debugger = old_self.debugger;
@@ -113,8 +140,14 @@ impl GameGirlAdv {
     }
 
     pub fn skip_bootrom(&mut self) {
-        self.cpu.set_cpsr(0x1F);
-        self.cpu.registers[15] = 0x0800_0000;
+        self.skip_bootrom_to(0x0800_0000);
+    }
+
+    /// Skip the BIOS and start executing at the given address.
+    /// Odd addresses start in THUMB mode.
+    pub fn skip_bootrom_to(&mut self, entry: u32) {
+        self.cpu.set_cpsr(if entry & 1 != 0 { 0x3F } else { 0x1F });
+        self.cpu.registers[15] = entry & !1;
         self.cpu.sp[1] = 0x0300_7F00;
         self.cpu.sp[3] = 0x0300_7F00;
         self.cpu.sp[5] = 0x0300_7F00;
@@ -0,0 +1,235 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{ops::Range, path::Path};
+
+use elf_rs::{Elf, ElfFile, ProgramType};
+
+use crate::{gga::GameGirlAdv, numutil::NumExt};
+
+const EWRAM: u32 = 0x0200_0000;
+const IWRAM: u32 = 0x0300_0000;
+const ROM: u32 = 0x0800_0000;
+const ROM_END: u32 = 0x0E00_0000;
+/// Multiboot images have to fit into EWRAM.
+const MULTIBOOT_MAX_SIZE: usize = 256 * 1024;
+
+/// A program to run on the GGA, decoded from a cart dump, an ELF file or a
+/// multiboot image.
+pub struct Program {
+    /// Contents of the cart ROM. Empty for multiboot images.
+    pub rom: Vec<u8>,
+    /// Data to place in EWRAM/IWRAM before starting, with its address.
+    ram: Vec<(u32, Vec<u8>)>,
+    /// Address to start executing at without running the BIOS.
+    /// `None` for cart dumps, which are booted normally.
+    pub entry: Option<u32>,
+}
+
+impl Program {
+    /// Decode the given file. The path is used for detecting multiboot
+    /// images by their extension.
+    pub fn decode(file: Vec<u8>, path: Option<&Path>) -> Self {
+        if let Some(program) = Self::from_elf(&file) {
+            program
+        } else if Self::is_multiboot(&file, path) {
+            Self {
+                rom: Vec::new(),
+                ram: vec![(EWRAM, file)],
+                entry: Some(EWRAM),
+            }
+        } else {
+            Self {
+                rom: file,
+                ram: Vec::new(),
+                entry: None,
+            }
+        }
+    }
+
+    /// Decode an ELF file by placing all loadable segments at their load
+    /// address.
+    fn from_elf(file: &[u8]) -> Option<Self> {
+        let elf = Elf::from_bytes(file).ok()?;
+        let mut program = Self {
+            rom: Vec::new(),
+            ram: Vec::new(),
+            entry: Some(elf.elf_header().entry_point().u32()),
+        };
+
+        for segment in elf
+            .program_header_iter()
+            .filter(|s| s.ph_type() == ProgramType::LOAD && s.filesz() != 0)
+        {
+            let addr = segment.paddr().u32();
+            match addr {
+                EWRAM..=0x03FF_FFFF => program.ram.push((addr, segment.content().to_vec())),
+                ROM..=0x0DFF_FFFF => {
+                    let offset = (addr & 0x1FF_FFFF).us();
+                    let end = offset + segment.content().len();
+                    if program.rom.len() < end {
+                        program.rom.resize(end, 0);
+                    }
+                    program.rom[offset..end].copy_from_slice(segment.content());
+                }
+                _ => log::warn!("Ignoring ELF segment at unsupported address 0x{addr:08X}"),
+            }
+        }
+        Some(program)
+    }
+
+    /// Check if the file is a multiboot image, meant to be sent to EWRAM
+    /// over the link cable. These have a cart header, so the file extension
+    /// is checked; if it does not match, the startup code is checked for
+    /// being linked to EWRAM instead of ROM.
+    fn is_multiboot(file: &[u8], path: Option<&Path>) -> bool {
+        if file.len() > MULTIBOOT_MAX_SIZE {
+            return false;
+        }
+        if path
+            .and_then(Path::extension)
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("mb"))
+        {
+            return true;
+        }
+
+        // The startup code after the header loads addresses from literal
+        // pools. Cart code also refers to EWRAM, for example to copy data
+        // there, but always has some reference to ROM as well
+        let words = file
+            .get(0xC0..0x400.min(file.len()))
+            .unwrap_or_default()
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()));
+        let refers_to = |region: Range<u32>| words.clone().any(|word| region.contains(&word));
+        refers_to(EWRAM..IWRAM) && !refers_to(ROM..ROM_END)
+    }
+
+    /// Copy the RAM contents of this program into the system's memory.
+    pub fn load_ram(&self, gg: &mut GameGirlAdv) {
+        for (addr, data) in &self.ram {
+            let mem = if *addr < IWRAM {
+                &mut gg.memory.ewram[..]
+            } else {
+                &mut gg.memory.iwram[..]
+            };
+            let offset = addr.us() & (mem.len() - 1);
+            let len = data.len().min(mem.len() - offset);
+            if len < data.len() {
+                log::warn!("Segment at 0x{addr:08X} does not fit into RAM, truncating");
+            }
+            mem[offset..offset + len].copy_from_slice(&data[..len]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::gga::program::{Program, EWRAM, IWRAM, ROM};
+
+    /// A 32-bit ARM ELF executable with the given loadable segments.
+    fn elf(entry: u32, segments: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut file = b"\x7FELF\x01\x01\x01\0\0\0\0\0\0\0\0\0".to_vec();
+        // e_type, e_machine
+        for value in [2u16, 40] {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+        // e_version, e_entry, e_phoff, e_shoff, e_flags
+        for value in [1, entry, 52, 0, 0] {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+        // e_ehsize, e_phentsize, e_phnum, e_shentsize, e_shnum, e_shstrndx
+        for value in [52, 32, segments.len() as u16, 40, 0, 0] {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut offset = 52 + 32 * segments.len() as u32;
+        for (addr, data) in segments {
+            let len = data.len() as u32;
+            // p_type (LOAD), p_offset, p_vaddr, p_paddr, p_filesz, p_memsz, p_flags,
+            // p_align
+            for value in [1, offset, *addr, *addr, len, len, 7, 4] {
+                file.extend_from_slice(&value.to_le_bytes());
+            }
+            offset += len;
+        }
+        for (_, data) in segments {
+            file.extend_from_slice(data);
+        }
+        file
+    }
+
+    /// A small cart image with the given words after the header, where
+    /// the startup code and its literal pools are.
+    fn image(words: &[u32]) -> Vec<u8> {
+        let mut file = vec![0; 0x8000];
+        for (i, word) in words.iter().enumerate() {
+            file[0xC0 + i * 4..][..4].copy_from_slice(&word.to_le_bytes());
+        }
+        file
+    }
+
+    #[test]
+    fn elf_segments() {
+        let file = elf(
+            ROM,
+            &[
+                (ROM + 0x100, &[1, 2, 3, 4]),
+                (EWRAM + 0x10, &[5, 6]),
+                (IWRAM, &[7]),
+            ],
+        );
+        let program = Program::decode(file, None);
+        assert_eq!(program.entry, Some(ROM));
+        assert_eq!(program.rom.len(), 0x104);
+        assert_eq!(program.rom[0x100..], [1, 2, 3, 4]);
+        assert_eq!(program.ram, [(EWRAM + 0x10, vec![5, 6]), (IWRAM, vec![7])]);
+    }
+
+    #[test]
+    fn not_elf() {
+        assert!(Program::from_elf(&image(&[])).is_none());
+    }
+
+    #[test]
+    fn cart_with_ewram_references() {
+        // Cart startup code copying data to EWRAM, but also running from ROM
+        let file = image(&[0xE59F_0000, EWRAM + 0x40, ROM + 0x2000]);
+        assert!(!Program::is_multiboot(&file, None));
+        let program = Program::decode(file.clone(), Some(Path::new("game.gba")));
+        assert_eq!(program.rom, file);
+        assert_eq!(program.entry, None);
+    }
+
+    #[test]
+    fn multiboot_by_references() {
+        let file = image(&[0xE59F_0000, EWRAM + 0x40, IWRAM + 0x100]);
+        assert!(Program::is_multiboot(&file, None));
+        let program = Program::decode(file.clone(), None);
+        assert!(program.rom.is_empty());
+        assert_eq!(program.ram, [(EWRAM, file)]);
+        assert_eq!(program.entry, Some(EWRAM));
+    }
+
+    #[test]
+    fn multiboot_by_extension() {
+        let file = image(&[ROM]);
+        assert!(Program::is_multiboot(&file, Some(Path::new("demo.MB"))));
+        assert!(!Program::is_multiboot(&file, Some(Path::new("demo.gba"))));
+        assert!(!Program::is_multiboot(&file, None));
+    }
+
+    #[test]
+    fn multiboot_too_large() {
+        let mut file = image(&[EWRAM]);
+        assert!(Program::is_multiboot(&file, None));
+        file.resize(512 * 1024, 0);
+        assert!(!Program::is_multiboot(&file, None));
+        assert!(!Program::is_multiboot(&file, Some(Path::new("demo.mb"))));
+    }
+}
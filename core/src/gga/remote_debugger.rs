@@ -9,6 +9,8 @@ use std::{
     os::unix::ffi::OsStrExt,
     path::PathBuf,
     sync::{Arc, Mutex, MutexGuard, RwLock},
+    thread,
+    time::Duration,
 };
 
 use gdbstub::{
@@ -41,7 +43,7 @@ use gdbstub::{
 use gdbstub_arch::arm::{reg::id::ArmCoreRegId, Armv4t};
 
 use crate::{
-    components::debugger::Breakpoint,
+    components::debugger::{AccessKind, Breakpoint},
     gga::remote_debugger::DebuggerStatus::{Disconnected, Running, WaitingForConnection},
     numutil::NumExt,
     System,
@@ -195,7 +197,10 @@ impl SingleRegisterAccess<()> for SyncSys {
 
 impl SingleThreadResume for SyncSys {
     fn resume(&mut self, _signal: Option<Signal>) -> Result<(), Self::Error> {
-        self.0.lock().unwrap().gga_mut().options.running = true;
+        let mut gg = self.lock();
+        let gg = gg.gga_mut();
+        gg.debugger.breakpoint_hit = None;
+        gg.options.running = true;
         Ok(())
     }
 
@@ -232,9 +237,9 @@ impl SwBreakpoint for SyncSys {
         let gg = gg.gga_mut();
         gg.debugger.breakpoints.push(Breakpoint {
             value: Some(addr),
-            value_text: addr.to_string(),
+            value_text: format!("{addr:X}"),
             pc: true,
-            write: false,
+            ..Breakpoint::default()
         });
         Ok(true)
     }
@@ -249,7 +254,7 @@ impl SwBreakpoint for SyncSys {
         let len = gg.debugger.breakpoints.len();
         gg.debugger
             .breakpoints
-            .retain(|bp| !(bp.pc && bp.value == Some(addr)));
+            .retain(|bp| !(bp.pc && bp.value == Some(addr) && bp.end.is_none()));
         Ok(len != gg.debugger.breakpoints.len())
     }
 }
@@ -258,41 +263,47 @@ impl HwWatchpoint for SyncSys {
     fn add_hw_watchpoint(
         &mut self,
         addr: <Self::Arch as Arch>::Usize,
-        _len: <Self::Arch as Arch>::Usize,
+        len: <Self::Arch as Arch>::Usize,
         kind: WatchKind,
     ) -> TargetResult<bool, Self> {
-        if let WatchKind::Read | WatchKind::ReadWrite = kind {
-            return Ok(false);
-        }
-
         let mut gg = self.lock();
         let gg = gg.gga_mut();
-        gg.debugger.breakpoints.push(Breakpoint {
-            value: Some(addr),
-            value_text: addr.to_string(),
-            pc: false,
-            write: true,
-        });
+        gg.debugger.breakpoints.push(watchpoint(addr, len, kind));
         Ok(true)
     }
 
     fn remove_hw_watchpoint(
         &mut self,
         addr: <Self::Arch as Arch>::Usize,
-        _len: <Self::Arch as Arch>::Usize,
+        len: <Self::Arch as Arch>::Usize,
         kind: WatchKind,
     ) -> TargetResult<bool, Self> {
-        if let WatchKind::Read | WatchKind::ReadWrite = kind {
-            return Ok(false);
-        }
-
+        let wp = watchpoint(addr, len, kind);
         let mut gg = self.lock();
         let gg = gg.gga_mut();
-        let len = gg.debugger.breakpoints.len();
-        gg.debugger
-            .breakpoints
-            .retain(|bp| !(bp.write && bp.value == Some(addr)));
-        Ok(len != gg.debugger.breakpoints.len())
+        let count = gg.debugger.breakpoints.len();
+        gg.debugger.breakpoints.retain(|bp| {
+            bp.pc
+                || bp.read != wp.read
+                || bp.write != wp.write
+                || bp.value != wp.value
+                || bp.end != wp.end
+        });
+        Ok(count != gg.debugger.breakpoints.len())
+    }
+}
+
+/// Make a breakpoint for a GDB watchpoint of `len` bytes at `addr`.
+fn watchpoint(addr: u32, len: u32, kind: WatchKind) -> Breakpoint<u32> {
+    let end = (len > 1).then(|| addr.wrapping_add(len - 1));
+    Breakpoint {
+        value: Some(addr),
+        value_text: format!("{addr:X}"),
+        end,
+        end_text: end.map(|end| format!("{end:X}")).unwrap_or_default(),
+        read: matches!(kind, WatchKind::Read | WatchKind::ReadWrite),
+        write: matches!(kind, WatchKind::Write | WatchKind::ReadWrite),
+        ..Breakpoint::default()
     }
 }
 
@@ -337,16 +348,36 @@ impl BlockingEventLoop for EventLoop {
             <Self::Connection as Connection>::Error,
         >,
     > {
-        let hit_bp = target.0.lock().unwrap().as_gga().options.running;
-        match () {
-            _ if hit_bp => Ok(Event::TargetStopped(SingleThreadStopReason::SwBreak(()))),
-            _ if target.1 => {
+        loop {
+            let hit = target.lock().gga_mut().debugger.breakpoint_hit.take();
+            if let Some(hit) = hit {
+                let kind = match hit.kind {
+                    AccessKind::Execute => {
+                        return Ok(Event::TargetStopped(SingleThreadStopReason::SwBreak(())))
+                    }
+                    AccessKind::Read => WatchKind::Read,
+                    AccessKind::Write => WatchKind::Write,
+                };
+                return Ok(Event::TargetStopped(SingleThreadStopReason::Watch {
+                    tid: (),
+                    kind,
+                    addr: hit.addr,
+                }));
+            }
+            if target.1 {
                 target.1 = false;
-                Ok(Event::TargetStopped(SingleThreadStopReason::DoneStep))
+                return Ok(Event::TargetStopped(SingleThreadStopReason::DoneStep));
+            }
+            if conn
+                .peek()
+                .map_err(WaitForStopReasonError::Connection)?
+                .is_some()
+            {
+                return Ok(Event::IncomingData(
+                    conn.read().map_err(WaitForStopReasonError::Connection)?,
+                ));
             }
-            _ => Ok(Event::IncomingData(
-                conn.read().map_err(WaitForStopReasonError::Connection)?,
-            )),
+            thread::sleep(Duration::from_millis(10));
         }
     }
 
@@ -26,6 +26,8 @@ pub enum AdvEvent {
     ApuEvent(ApuEvent),
     /// A timer overflow.
     TimerOverflow(u8),
+    /// The flash save chip finished an operation.
+    FlashDone,
 }
 
 impl AdvEvent {
@@ -39,6 +41,7 @@ impl AdvEvent {
                 gg.scheduler.schedule(self, time);
             }
             TimerOverflow(idx) => Timers::handle_overflow_event(gg, idx, late_by),
+            FlashDone => gg.cart.flash_done(),
         }
     }
 }
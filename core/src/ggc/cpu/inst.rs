@@ -91,7 +91,7 @@ impl Inst {
 }
 
 pub(crate) fn get_next(gg: &mut GameGirl) -> Inst {
-    let first = gg.read8(gg.cpu.pc);
+    let first = gg.fetch8(gg.cpu.pc);
     let inst = match first {
         EXT => Inst(first, gg.arg8()),
         _ => Inst(first, 0),
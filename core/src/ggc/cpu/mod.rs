@@ -7,6 +7,7 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    components::debugger::{Debuggable, Debugger},
     ggc::{
         io::addr::{IE, IF},
         GameGirl,
@@ -31,7 +32,11 @@ pub struct Cpu {
 impl Cpu {
     /// Execute the next instruction, moving the entire system forward.
     pub(super) fn exec_next_inst(gg: &mut GameGirl) {
-        if !gg.debugger.should_execute(gg.cpu.pc) {
+        let pc = gg.cpu.pc;
+        if !gg.check_execute(pc) {
+            if gg.options.running {
+                gg.options.notify(format!("Breakpoint hit at {pc:04X}"));
+            }
             gg.options.running = false; // Pause emulation, we hit a BP
             return;
         }
@@ -118,12 +123,51 @@ impl Cpu {
         (high.u16() << 8) | low.u16()
     }
 
-    fn set_dreg(&mut self, reg: DReg, value: u16) {
+    pub fn set_dreg(&mut self, reg: DReg, value: u16) {
         self.set_reg(reg.low(), value.u8());
         self.set_reg(reg.high(), (value >> 8).u8());
     }
 }
 
+impl Debuggable for GameGirl {
+    type Ptr = u16;
+    const REGISTERS: &'static [&'static str] = &[
+        "a", "b", "c", "d", "e", "f", "h", "l", "af", "bc", "de", "hl", "sp", "pc",
+    ];
+
+    fn debugger(&self) -> &Debugger<u16> {
+        &self.debugger
+    }
+
+    fn debugger_mut(&mut self) -> &mut Debugger<u16> {
+        &mut self.debugger
+    }
+
+    fn register(&self, idx: usize) -> u32 {
+        const REGS: [Reg; 8] = [
+            Reg::A,
+            Reg::B,
+            Reg::C,
+            Reg::D,
+            Reg::E,
+            Reg::F,
+            Reg::H,
+            Reg::L,
+        ];
+        const DREGS: [DReg; 4] = [DReg::AF, DReg::BC, DReg::DE, DReg::HL];
+        match idx {
+            0..=7 => self.cpu.reg(REGS[idx]).u32(),
+            8..=11 => self.cpu.dreg(DREGS[idx - 8]).u32(),
+            12 => self.cpu.sp.u32(),
+            _ => self.cpu.pc.u32(),
+        }
+    }
+
+    fn peek(&self, addr: u32) -> u8 {
+        self.get8(addr.u16())
+    }
+}
+
 /// The CPU's registers.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Reg {
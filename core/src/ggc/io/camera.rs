@@ -0,0 +1,151 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::{Deserialize, Serialize};
+
+use crate::numutil::NumExt;
+
+/// Width of images captured by the camera.
+pub const CAMERA_WIDTH: usize = 128;
+/// Height of images captured by the camera.
+pub const CAMERA_HEIGHT: usize = 112;
+
+/// Amount of sensor registers, the rest of the register area is unused.
+const REG_COUNT: usize = 0x36;
+/// Start of the dithering matrix inside the registers.
+const MATRIX_START: usize = 6;
+/// Where in cart RAM bank 0 captured images are placed.
+const IMAGE_START: usize = 0x100;
+
+/// Gain multipliers, indexed by the 5 gain bits in register 1.
+const GAIN: [f32; 32] = [
+    0.880_939, 0.914_915, 0.945_75, 0.973_976, 1.0, 1.024_141, 1.046_654, 1.067_743, 1.087_579,
+    1.124_031, 1.156_891, 1.186_804, 1.214_256, 1.239_621, 1.274_384, 1.315_732, 1.352_519,
+    1.385_651, 1.415_79, 1.443_431, 1.468_957, 1.492_67, 1.514_809, 1.535_57, 1.555_116, 1.573_58,
+    1.591_076, 1.607_701, 1.623_537, 1.638_655, 1.653_118, 1.666_981,
+];
+/// Edge enhancement ratios, indexed by bits 4-6 of register 4.
+const EDGE_RATIO: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+/// The Mitsubishi M64282FP image sensor of the Pocket Camera, together with
+/// the cart's image processing.
+/// Registers are mapped at A000 when RAM bank 0x10 is selected.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CameraSensor {
+    #[serde(with = "serde_arrays")]
+    regs: [u8; REG_COUNT],
+    /// If the game started a capture that was not processed yet.
+    capture_requested: bool,
+}
+
+impl CameraSensor {
+    pub(super) fn read(&self, addr: u16) -> u8 {
+        // Only the control register can be read back
+        if addr & 0x7F == 0 {
+            self.regs[0]
+        } else {
+            0x00
+        }
+    }
+
+    pub(super) fn write(&mut self, addr: u16, value: u8) {
+        match (addr & 0x7F).us() {
+            0 => {
+                self.regs[0] = value & 0x07;
+                self.capture_requested |= Self::starts_capture(addr, value);
+            }
+            reg if reg < REG_COUNT => self.regs[reg] = value,
+            _ => (),
+        }
+    }
+
+    /// If writing `value` to the sensor register at `addr` starts a capture,
+    /// which is done by setting bit 0 of the control register.
+    pub(super) fn starts_capture(addr: u16, value: u8) -> bool {
+        addr & 0x7F == 0 && value.is_bit(0)
+    }
+
+    /// Process a pending capture, if there is one.
+    /// `image` is called to get the sensor input, which is a
+    /// grayscale image of size [CAMERA_WIDTH]x[CAMERA_HEIGHT].
+    /// The result is written into the given RAM bank 0, in GG tile format.
+    pub(super) fn capture(&mut self, ram: &mut [u8], image: &dyn Fn() -> Vec<u8>) {
+        if !self.capture_requested {
+            return;
+        }
+        self.capture_requested = false;
+        // The capture is finished immediately
+        self.regs[0] &= !1;
+
+        let image = image();
+        let exposure = (self.regs[2].u16() << 8 | self.regs[3].u16()) as f32;
+        let gain = GAIN[(self.regs[1] & 0x1F).us()];
+        let sensor = |x: usize, y: usize| {
+            let x = x.min(CAMERA_WIDTH - 1);
+            let y = y.min(CAMERA_HEIGHT - 1);
+            let pixel = image.get(y * CAMERA_WIDTH + x).copied().unwrap_or(0);
+            pixel as f32 * gain * exposure / 4096.0
+        };
+
+        // Exclusive 2D edge enhancement, other modes are not emulated
+        let edge = self.regs[1] & 0xE0 == 0xE0;
+        let ratio = EDGE_RATIO[((self.regs[4] >> 4) & 7).us()];
+
+        for y in 0..CAMERA_HEIGHT {
+            for x in 0..CAMERA_WIDTH {
+                let mut value = sensor(x, y);
+                if edge {
+                    let around = sensor(x.saturating_sub(1), y)
+                        + sensor(x + 1, y)
+                        + sensor(x, y.saturating_sub(1))
+                        + sensor(x, y + 1);
+                    value += (value * 4.0 - around) * ratio;
+                }
+
+                let matrix = MATRIX_START + ((x & 3) + (y & 3) * 4) * 3;
+                let thresholds = &self.regs[matrix..(matrix + 3)];
+                let colour = thresholds
+                    .iter()
+                    .position(|t| value < *t as f32)
+                    .map_or(0, |i| 3 - i as u8);
+
+                let tile = (y / 8) * (CAMERA_WIDTH / 8) + (x / 8);
+                let addr = IMAGE_START + tile * 16 + (y & 7) * 2;
+                let bit = 7 - (x & 7) as u16;
+                ram[addr] = ram[addr].set_bit(bit, colour.is_bit(0));
+                ram[addr + 1] = ram[addr + 1].set_bit(bit, colour.is_bit(1));
+            }
+        }
+    }
+
+    /// A generated test pattern that can be used as sensor input.
+    /// The top half is a horizontal brightness ramp, the bottom half
+    /// a checkerboard.
+    pub fn test_pattern() -> Vec<u8> {
+        (0..CAMERA_HEIGHT)
+            .flat_map(|y| {
+                (0..CAMERA_WIDTH).map(move |x| {
+                    if y < CAMERA_HEIGHT / 2 {
+                        (x * 255 / (CAMERA_WIDTH - 1)) as u8
+                    } else if (x / 16 + y / 16) % 2 == 0 {
+                        0xFF
+                    } else {
+                        0x20
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for CameraSensor {
+    fn default() -> Self {
+        Self {
+            regs: [0; REG_COUNT],
+            capture_requested: false,
+        }
+    }
+}
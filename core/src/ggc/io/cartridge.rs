@@ -4,14 +4,15 @@
 // If a copy of the MPL2 was not distributed with this file, you can
 // obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::{
-    iter,
-    time::{SystemTime, UNIX_EPOCH},
-};
+use std::iter;
 
 use serde::{Deserialize, Serialize};
 
-use crate::{components::storage::GameSave, ggc::io::cartridge::MBCKind::*, numutil::NumExt};
+use crate::{
+    components::{cart_info::CartInfo, clock::since_unix, storage::GameSave},
+    ggc::io::{camera::CameraSensor, cartridge::MBCKind::*},
+    numutil::{hword, NumExt, U16Ext},
+};
 
 const CGB_FLAG: u16 = 0x0143;
 const CGB_ONLY: u8 = 0xC0;
@@ -19,6 +20,8 @@ const KIND: u16 = 0x0147;
 const ROM_BANKS: u16 = 0x0148;
 const RAM_BANKS: u16 = 0x0149;
 const BANK_COUNT_1MB: u16 = 64;
+/// Size of the MBC7's 93LC56 EEPROM, in bytes.
+const EEPROM_SIZE: usize = 256;
 
 /// Struct representing the game cartridge.
 #[derive(Clone, Deserialize, Serialize)]
@@ -26,6 +29,11 @@ pub struct Cartridge {
     #[serde(skip)]
     #[serde(default)]
     pub rom: Vec<u8>,
+    /// Information from the cart header, parsed once when the ROM is
+    /// loaded since verifying the global checksum reads the whole ROM.
+    #[serde(skip)]
+    #[serde(default)]
+    pub info: CartInfo,
     /// Bank of the ROM area 0-4000. This is used by some MBCs.
     pub rom0_bank: u16,
     /// Bank of the ROM area 4000-8000.
@@ -51,6 +59,26 @@ impl Cartridge {
                     rtc,
                     ..
                 } => rtc.get(*reg).u8(),
+                MBC7 {
+                    ram_enable2: true,
+                    accel,
+                    eeprom,
+                } if self.ram_enable && addr < 0xB000 => match addr & 0xF0 {
+                    0x20 => accel.x.low(),
+                    0x30 => accel.x.high(),
+                    0x40 => accel.y.low(),
+                    0x50 => accel.y.high(),
+                    0x60 => 0x00,
+                    0x80 => eeprom.read(),
+                    _ => 0xFF,
+                },
+                MBC7 { .. } => 0xFF,
+                PocketCamera {
+                    sensor_mapped: true,
+                    sensor,
+                } => sensor.read(addr),
+                // RAM can always be read on the Pocket Camera
+                PocketCamera { .. } => self.ram[(a & 0x1FFF) + (0x2000 * self.ram_bank.us())],
                 _ if !self.ram.is_empty() && self.ram_enable => {
                     self.ram[(a & 0x1FFF) + (0x2000 * self.ram_bank.us())]
                 }
@@ -101,6 +129,42 @@ impl Cartridge {
                 rtc.set(*reg, value);
             }
 
+            // MBC7
+            (MBC7 { .. }, 0x2000..=0x3FFF) => {
+                self.rom1_bank = value.u16() % self.rom_bank_count();
+            }
+            (MBC7 { ram_enable2, .. }, 0x4000..=0x5FFF) => *ram_enable2 = value == 0x40,
+            (
+                MBC7 {
+                    ram_enable2: true,
+                    accel,
+                    eeprom,
+                },
+                0xA000..=0xAFFF,
+            ) if self.ram_enable => match addr & 0xF0 {
+                0x00 if value == 0x55 => accel.erase(),
+                0x10 if value == 0xAA => accel.latch(),
+                0x80 => eeprom.write(&mut self.ram, value),
+                _ => (),
+            },
+            (MBC7 { .. }, 0xA000..=0xBFFF) => (),
+
+            // Pocket Camera
+            (PocketCamera { .. }, 0x2000..=0x3FFF) => {
+                self.rom1_bank = (value & 0x3F).u16() % self.rom_bank_count();
+            }
+            (PocketCamera { sensor_mapped, .. }, 0x4000..=0x5FFF) => {
+                *sensor_mapped = value.is_bit(4);
+                self.ram_bank = (value & 0x0F) % self.ram_bank_count();
+            }
+            (
+                PocketCamera {
+                    sensor_mapped: true,
+                    sensor,
+                },
+                0xA000..=0xBFFF,
+            ) => sensor.write(addr, value),
+
             // Shared between all (except MBC2 and RTCs...)
             (_, 0x0000..=0x1FFF) | (MBC2, 0x0000..=0x3FFF) => {
                 self.ram_enable = (value & 0x0F) == 0x0A;
@@ -110,6 +174,13 @@ impl Cartridge {
             }
 
             // Shared between some
+            (MBC5Rumble { motor }, 0x4000..=0x5FFF) => {
+                // Bit 3 drives the rumble motor instead of selecting a bank.
+                // Rumble carts might have no RAM at all.
+                *motor = value.is_bit(3);
+                let bank = value & 0x07;
+                self.ram_bank = bank.checked_rem(self.ram_bank_count()).unwrap_or(0);
+            }
             (MBC3 | MBC5, 0x4000..=0x5FFF) => {
                 self.ram_bank = (value & 0x03) % self.ram_bank_count();
             }
@@ -137,10 +208,10 @@ impl Cartridge {
             }
 
             // MBC5
-            (MBC5, 0x2000..=0x2FFF) => {
+            (MBC5 | MBC5Rumble { .. }, 0x2000..=0x2FFF) => {
                 self.rom1_bank = (self.rom1_bank & 0x100) | (value.u16() % self.rom_bank_count());
             }
-            (MBC5, 0x3000..=0x3FFF) => {
+            (MBC5 | MBC5Rumble { .. }, 0x3000..=0x3FFF) => {
                 self.rom1_bank = self.rom1_bank.set_bit(8, value.is_bit(0)) % self.rom_bank_count();
             }
 
@@ -182,6 +253,30 @@ impl Cartridge {
         }
     }
 
+    /// If the cart's rumble motor is currently active.
+    pub fn rumble(&self) -> bool {
+        matches!(self.kind, MBC5Rumble { motor: true })
+    }
+
+    /// Set the tilt of the cartridge, for carts with an accelerometer.
+    /// Both axes range from -1.0 to 1.0, with positive values tilting
+    /// right/down respectively.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        if let MBC7 { accel, .. } = &mut self.kind {
+            accel.tilt = (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0));
+        }
+    }
+
+    /// Process a pending camera capture, if this is a Pocket Camera and
+    /// the game requested one. Only called when a write might have started
+    /// a capture; see [CameraSensor::starts_capture] and
+    /// [CameraSensor::capture].
+    pub(super) fn camera_capture(&mut self, image: &dyn Fn() -> Vec<u8>) {
+        if let PocketCamera { sensor, .. } = &mut self.kind {
+            sensor.capture(&mut self.ram, image);
+        }
+    }
+
     pub fn supports_cgb(&self) -> bool {
         self.rom[CGB_FLAG.us()].is_bit(7)
     }
@@ -207,6 +302,7 @@ impl Cartridge {
     pub fn from_rom(rom: Vec<u8>) -> Self {
         let kind = rom[KIND as usize];
         let mut cart = Self {
+            info: CartInfo::ggc(&rom),
             rom,
             kind: match kind {
                 0x01..=0x03 => MBC1 {
@@ -223,18 +319,33 @@ impl Cartridge {
                     latch_prepare: false,
                 },
                 0x11..=0x13 => MBC3,
-                0x19..=0x1E => MBC5,
+                0x19..=0x1B => MBC5,
+                0x1C..=0x1E => MBC5Rumble { motor: false },
+                0x22 => MBC7 {
+                    ram_enable2: false,
+                    accel: Accelerometer::default(),
+                    eeprom: Eeprom93LC56::default(),
+                },
+                0xFC => PocketCamera {
+                    sensor_mapped: false,
+                    sensor: CameraSensor::default(),
+                },
                 _ => NoMBC,
             },
             ..Self::dummy()
         };
-        cart.ram
-            .extend(iter::repeat(0).take(0x2000 * cart.ram_bank_count().us()));
+        if let MBC7 { .. } = cart.kind {
+            // The EEPROM is stored in RAM, making it be persisted like regular cart RAM.
+            cart.ram = vec![0xFF; EEPROM_SIZE];
+        } else {
+            cart.ram
+                .extend(iter::repeat(0).take(0x2000 * cart.ram_bank_count().us()));
+        }
         cart
     }
 
     pub fn make_save(&self) -> Option<GameSave> {
-        if !self.rom.is_empty() && self.ram_bank_count() > 0 {
+        if !self.rom.is_empty() && !self.ram.is_empty() {
             Some(GameSave {
                 ram: self.ram.clone(),
                 rtc: if let MBC3RTC { rtc, .. } = &self.kind {
@@ -252,13 +363,14 @@ impl Cartridge {
     pub fn load_save(&mut self, save: GameSave) {
         self.ram = save.ram;
         if let MBC3RTC { rtc, .. } = &mut self.kind {
-            rtc.start = save.rtc.unwrap_or_else(Rtc::since_unix);
+            rtc.start = save.rtc.unwrap_or_else(since_unix);
         }
     }
 
     pub fn dummy() -> Self {
         Self {
             rom: vec![],
+            info: CartInfo::default(),
             rom0_bank: 0,
             rom1_bank: 1,
             ram: vec![],
@@ -285,6 +397,18 @@ pub enum MBCKind {
         latch_prepare: bool,
     },
     MBC5,
+    MBC5Rumble {
+        motor: bool,
+    },
+    MBC7 {
+        ram_enable2: bool,
+        accel: Accelerometer,
+        eeprom: Eeprom93LC56,
+    },
+    PocketCamera {
+        sensor_mapped: bool,
+        sensor: CameraSensor,
+    },
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -295,7 +419,7 @@ pub struct Rtc {
 
 impl Rtc {
     fn latch(&mut self) {
-        self.latched_at = Some(Self::since_unix());
+        self.latched_at = Some(since_unix());
     }
 
     fn get(&self, idx: u8) -> u16 {
@@ -304,21 +428,226 @@ impl Rtc {
 
     fn set(&mut self, _idx: u8, _value: u8) {
         // TODO this is not how MBC3RTC works
-        self.start = Self::since_unix();
+        self.start = since_unix();
     }
 
     fn diff(&self) -> u64 {
-        self.latched_at
-            .unwrap_or_else(|| Self::since_unix() - self.start)
-    }
-
-    fn since_unix() -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
+        self.latched_at.unwrap_or_else(|| since_unix() - self.start)
     }
 }
 
 const RTC_DIVIDERS: &[u64] = &[1, 60, 3600, 86400];
 const RTC_MODULO: &[u64] = &[60, 60, 24, 511];
+
+/// The 2-axis accelerometer found on MBC7 carts.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Accelerometer {
+    /// Current tilt, as given by the frontend.
+    tilt: (f32, f32),
+    /// Latched X and Y values, as visible to the game.
+    x: u16,
+    y: u16,
+    /// If the latched values were erased and a new latch can happen.
+    erased: bool,
+}
+
+impl Accelerometer {
+    /// Value of an axis when the cart is held flat.
+    const CENTER: f32 = 0x81D0 as f32;
+    /// Change in value when tilting an axis by 90 degrees.
+    const GRAVITY: f32 = 0x70 as f32;
+
+    fn erase(&mut self) {
+        self.x = 0x8000;
+        self.y = 0x8000;
+        self.erased = true;
+    }
+
+    fn latch(&mut self) {
+        if self.erased {
+            self.x = (Self::CENTER + self.tilt.0 * Self::GRAVITY) as u16;
+            self.y = (Self::CENTER + self.tilt.1 * Self::GRAVITY) as u16;
+            self.erased = false;
+        }
+    }
+}
+
+impl Default for Accelerometer {
+    fn default() -> Self {
+        Self {
+            tilt: (0.0, 0.0),
+            x: 0x8000,
+            y: 0x8000,
+            erased: false,
+        }
+    }
+}
+
+/// The 93LC56 serial EEPROM found on MBC7 carts.
+/// It is organized as 128 16-bit words, and accessed bit-by-bit
+/// through a single register.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Eeprom93LC56 {
+    /// Chip select, clock, data in and data out lines.
+    cs: bool,
+    clk: bool,
+    di: bool,
+    out: bool,
+    /// Set by the EWEN command, required for any writes.
+    write_enable: bool,
+    state: EepromState,
+}
+
+impl Eeprom93LC56 {
+    fn read(&self) -> u8 {
+        ((self.cs as u8) << 7) | ((self.clk as u8) << 6) | ((self.di as u8) << 1) | self.out as u8
+    }
+
+    fn write(&mut self, ram: &mut [u8], value: u8) {
+        let cs = value.is_bit(7);
+        let clk = value.is_bit(6);
+        self.di = value.is_bit(1);
+
+        if !cs {
+            self.state = EepromState::Idle;
+        } else if !self.clk && clk {
+            // Rising clock edge, shift a bit in/out
+            self.clock(ram);
+        }
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    fn clock(&mut self, ram: &mut [u8]) {
+        let di = self.di as u16;
+        self.state = match self.state {
+            EepromState::Idle if self.di => {
+                // Start bit, a command follows
+                EepromState::Command { value: 0, bits: 0 }
+            }
+            EepromState::Idle => EepromState::Idle,
+
+            EepromState::Command { value, bits: 9 } => self.command(ram, (value << 1) | di),
+            EepromState::Command { value, bits } => EepromState::Command {
+                value: (value << 1) | di,
+                bits: bits + 1,
+            },
+
+            EepromState::Read { addr, bits: 0 } => {
+                // Sequential read, continue with the next word
+                let addr = (addr + 1) & 0x7F;
+                self.out = Self::get(ram, addr).is_bit(15);
+                EepromState::Read { addr, bits: 15 }
+            }
+            EepromState::Read { addr, bits } => {
+                self.out = Self::get(ram, addr).is_bit(bits.u16() - 1);
+                EepromState::Read {
+                    addr,
+                    bits: bits - 1,
+                }
+            }
+
+            EepromState::Write {
+                addr,
+                value,
+                bits: 15,
+            } => {
+                let value = (value << 1) | di;
+                if self.write_enable {
+                    match addr {
+                        Some(addr) => Self::set(ram, addr, value),
+                        None => (0..0x80).for_each(|addr| Self::set(ram, addr, value)),
+                    }
+                }
+                self.out = true;
+                EepromState::Idle
+            }
+            EepromState::Write { addr, value, bits } => EepromState::Write {
+                addr,
+                value: (value << 1) | di,
+                bits: bits + 1,
+            },
+        };
+    }
+
+    /// Execute a command, which consists of a 2-bit opcode and 8-bit address.
+    fn command(&mut self, ram: &mut [u8], command: u16) -> EepromState {
+        let addr = command.u8() & 0x7F;
+        match command >> 8 {
+            // READ, output starts with a dummy 0 bit
+            0b10 => {
+                self.out = false;
+                EepromState::Read { addr, bits: 16 }
+            }
+            // WRITE
+            0b01 => EepromState::Write {
+                addr: Some(addr),
+                value: 0,
+                bits: 0,
+            },
+            // ERASE
+            0b11 => {
+                if self.write_enable {
+                    Self::set(ram, addr, 0xFFFF);
+                }
+                self.out = true;
+                EepromState::Idle
+            }
+            _ => match (command >> 6) & 3 {
+                // EWDS
+                0b00 => {
+                    self.write_enable = false;
+                    EepromState::Idle
+                }
+                // WRAL
+                0b01 => EepromState::Write {
+                    addr: None,
+                    value: 0,
+                    bits: 0,
+                },
+                // ERAL
+                0b10 => {
+                    if self.write_enable {
+                        ram.fill(0xFF);
+                    }
+                    self.out = true;
+                    EepromState::Idle
+                }
+                // EWEN
+                _ => {
+                    self.write_enable = true;
+                    EepromState::Idle
+                }
+            },
+        }
+    }
+
+    fn get(ram: &[u8], addr: u8) -> u16 {
+        let addr = addr.us() * 2;
+        hword(ram[addr], ram[addr + 1])
+    }
+
+    fn set(ram: &mut [u8], addr: u8, value: u16) {
+        let addr = addr.us() * 2;
+        ram[addr] = value.low();
+        ram[addr + 1] = value.high();
+    }
+}
+
+/// State of the 93LC56's serial interface.
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize)]
+enum EepromState {
+    /// Waiting for a start bit.
+    #[default]
+    Idle,
+    /// Receiving command bits (opcode and address).
+    Command { value: u16, bits: u8 },
+    /// Shifting out the word at the given address, `bits` remaining.
+    Read { addr: u8, bits: u8 },
+    /// Receiving a word to write, to a single address or the entire chip.
+    Write {
+        addr: Option<u8>,
+        value: u16,
+        bits: u8,
+    },
+}
@@ -14,20 +14,28 @@ use serde::{Deserialize, Serialize};
 use super::GameGirl;
 use crate::{
     common::{CgbMode, SystemConfig},
-    components::memory::{MemoryMappedSystem, MemoryMapper},
-    ggc::io::{
-        addr::*,
-        apu::Apu,
-        cartridge::Cartridge,
-        dma::Hdma,
-        scheduling::{GGEvent, PpuEvent},
-        timer::Timer,
+    components::{
+        debugger::Debuggable,
+        memory::{MemoryMappedSystem, MemoryMapper},
+    },
+    ggc::{
+        io::{
+            addr::*,
+            apu::Apu,
+            camera::CameraSensor,
+            cartridge::Cartridge,
+            dma::Hdma,
+            scheduling::{GGEvent, PpuEvent},
+            timer::Timer,
+        },
+        model::GgcModel,
     },
     numutil::{hword, NumExt},
 };
 
 pub(super) mod addr;
 pub mod apu;
+pub mod camera;
 pub mod cartridge;
 pub mod dma;
 pub mod joypad;
@@ -64,6 +72,18 @@ pub struct Memory {
 
 impl GameGirl {
     pub fn read8(&mut self, addr: u16) -> u8 {
+        let value = self.fetch8(addr);
+        if !self.check_read(addr, value.u32()) && self.options.running {
+            self.options.running = false;
+            self.options
+                .notify(format!("Read breakpoint hit at {addr:04X}"));
+        }
+        value
+    }
+
+    /// Read a byte of the current instruction. Unlike [Self::read8],
+    /// this does not trigger read breakpoints.
+    pub fn fetch8(&mut self, addr: u16) -> u8 {
         self.advance_clock(1);
         self.get8(addr)
     }
@@ -73,7 +93,11 @@ impl GameGirl {
     }
 
     pub fn write8(&mut self, addr: u16, value: u8) {
-        self.options.running &= self.debugger.write_occurred(addr);
+        if !self.check_write(addr, value.u32()) && self.options.running {
+            self.options.running = false;
+            self.options
+                .notify(format!("Write breakpoint hit at {addr:04X}"));
+        }
         self.advance_clock(1);
         self.set8(addr, value);
     }
@@ -91,12 +115,14 @@ impl GameGirl {
 
     /// Get an 8-bit argument for the current CPU instruction.
     pub fn arg8(&mut self) -> u8 {
-        self.read8(self.cpu.pc + 1)
+        self.fetch8(self.cpu.pc + 1)
     }
 
     /// Get a 16-bit argument for the current CPU instruction.
     pub fn arg16(&mut self) -> u16 {
-        self.read16(self.cpu.pc + 1)
+        let low = self.fetch8(self.cpu.pc + 1);
+        let high = self.fetch8(self.cpu.pc + 2);
+        (high.u16() << 8) | low.u16()
     }
 
     /// Pop the current value off the SP.
@@ -150,7 +176,11 @@ impl GameGirl {
         let a = addr.us();
         match addr {
             0x0000..=0x7FFF => {
+                let rumble = self.cart.rumble();
                 self.cart.write(addr, value);
+                if rumble != self.cart.rumble() {
+                    (self.options.rumble)(!rumble);
+                }
                 // Refresh page offsets
                 for i in 0..4 {
                     self.mem.page_offsets[i] = self.cart.rom0_bank.u32() * 0x4000;
@@ -159,7 +189,12 @@ impl GameGirl {
                     self.mem.page_offsets[i] = self.cart.rom1_bank.u32() * 0x4000;
                 }
             }
-            0xA000..=0xBFFF => self.cart.write(addr, value),
+            0xA000..=0xBFFF => {
+                self.cart.write(addr, value);
+                if CameraSensor::starts_capture(addr, value) {
+                    self.cart.camera_capture(&*self.options.camera_image);
+                }
+            }
             0x8000..=0x9FFF => {
                 self.mem.vram[(a & 0x1FFF) + (self.mem.vram_bank.us() * 0x2000)] = value;
             }
@@ -231,11 +266,12 @@ impl GameGirl {
             CgbMode::Prefer => cart.supports_cgb(),
             CgbMode::Never => cart.requires_cgb(),
         };
-        self.mem.bootrom = Some(if self.cgb {
-            CGB_BOOTROM.to_vec()
-        } else {
-            BOOTIX_ROM.to_vec()
-        });
+        self.model = match (self.cgb, conf.dmg_model, conf.cgb_model) {
+            (false, model, _) if !model.is_cgb() => model,
+            (true, _, model) if model.is_cgb() => model,
+            (false, _, _) => GgcModel::Dmg,
+            (true, _, _) => GgcModel::Cgb,
+        };
         self.ppu.configure(self.cgb, conf.cgb_colour_correction);
         self.apu = Apu::new(self.cgb);
         self.cart = cart;
@@ -359,7 +395,7 @@ impl MemoryMappedSystem<256> for GameGirl {
                 offs(self.mem.bootrom.as_ref().unwrap(), a)
             }
             0x0200..=0x08FF if self.mem.bootrom.is_some() && self.cgb => {
-                offs(self.mem.bootrom.as_ref().unwrap(), a - 0x0100)
+                offs(self.mem.bootrom.as_ref().unwrap(), a)
             }
             0x0000..=0x3FFF => offs(&self.cart.rom, a),
             0x4000..=0x7FFF => offs(&self.cart.rom, a - 0x4000),
@@ -373,6 +373,25 @@ impl Ppu {
         }
     }
 
+    /// Move the PPU to 1 M-cycle into VBlank, which is where the boot ROM
+    /// leaves it.
+    pub(crate) fn skip_to_vblank(gg: &mut GameGirl) {
+        for evt in [
+            PpuEvent::OamScanEnd,
+            PpuEvent::UploadEnd,
+            PpuEvent::HblankEnd,
+            PpuEvent::VblankEnd,
+            PpuEvent::LYIncrement,
+        ] {
+            gg.scheduler.cancel(GGEvent::PpuEvent(evt));
+        }
+        gg.ppu.line = 144;
+        gg[LY] = 144;
+        gg[STAT] = gg[STAT] & 0xFC | PpuEvent::VblankEnd.ordinal();
+        gg.scheduler
+            .schedule(GGEvent::PpuEvent(PpuEvent::VblankEnd), 452);
+    }
+
     pub(super) fn configure(&mut self, cgb: bool, colour_correction: bool) {
         self.kind = if cgb {
             PpuKind::Cgb(Cgb::new(colour_correction))
@@ -119,7 +119,13 @@ impl Timer {
 
     /// Calculate the current value of DIV.
     fn div(gg: &GameGirl) -> u16 {
-        (gg.scheduler.now() - gg.timer.div_start).u16()
+        gg.scheduler.now().wrapping_sub(gg.timer.div_start).u16()
+    }
+
+    /// Set the internal DIV counter. Used when skipping the boot ROM.
+    pub(crate) fn set_div(gg: &mut GameGirl, value: u16) {
+        gg.timer.div_start = gg.scheduler.now().wrapping_sub(value.u32());
+        Self::reschedule(gg);
     }
 
     /// Reschedule the timer overflow event.
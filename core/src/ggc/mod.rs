@@ -10,11 +10,16 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     common::{self, EmulateOptions, SystemConfig},
-    components::{debugger::Debugger, memory::MemoryMapper, scheduler::Scheduler},
+    components::{
+        debugger::Debugger,
+        firmware::{Firmware, FirmwareError},
+        memory::MemoryMapper,
+        scheduler::Scheduler,
+    },
     ggc::{
         cpu::{Cpu, Interrupt},
         io::{
-            addr::{BOOTROM_DISABLE, IF, KEY1},
+            addr::{BOOTIX_ROM, BOOTROM_DISABLE, HIGH_START, IF, KEY1},
             apu::Apu,
             cartridge::Cartridge,
             dma::Hdma,
@@ -24,6 +29,7 @@ use crate::{
             timer::Timer,
             Memory,
         },
+        model::GgcModel,
     },
     numutil::NumExt,
     Colour,
@@ -31,8 +37,11 @@ use crate::{
 
 pub mod cpu;
 pub mod io;
+pub mod model;
 
 const T_CLOCK_HZ: u32 = 4_194_304;
+/// Frames per second: 154 lines of 456 T-cycles each.
+pub const FRAME_RATE: f64 = T_CLOCK_HZ as f64 / (154.0 * 456.0);
 
 pub type GGDebugger = Debugger<u16>;
 
@@ -44,6 +53,9 @@ pub struct GameGirl {
     pub mem: Memory,
 
     cgb: bool,
+    /// Hardware model being emulated.
+    #[serde(default)]
+    model: GgcModel,
     #[serde(skip)]
     #[serde(default)]
     pub debugger: GGDebugger,
@@ -103,14 +115,33 @@ impl GameGirl {
         self[IF] = self[IF].set_bit(ir.to_index(), true) as u8;
     }
 
+    /// Reset the console, while keeping the current cartridge inserted.
+    pub fn reset(&mut self) {
+        let old_self = mem::take(self);
+        self.load_cart_mem(old_self.cart, &old_self.config);
+        self.options = old_self.options;
+        self.config = old_self.config;
+        self.debugger = old_self.debugger;
+        self.mem.bootrom = old_self.mem.bootrom;
+        if self.mem.bootrom.is_some() {
+            MemoryMapper::init_pages(self);
+        } else {
+            self.init_post_boot();
+        }
+    }
+
     /// Restore state after a savestate load. `old_self` should be the
     /// system state before the state was loaded.
-    pub fn restore_from(&mut self, old_self: Self) {
-        let save = old_self.cart.make_save();
-        self.load_cart_mem(old_self.cart, &old_self.config);
-        if let Some(save) = save {
-            self.cart.load_save(save);
+    /// Everything not part of save states is taken from it, including the
+    /// save data of the cart if `restore_save`.
+    pub fn restore_from(&mut self, old_self: Self, restore_save: bool) {
+        if restore_save {
+            if let Some(save) = old_self.cart.make_save() {
+                self.cart.load_save(save);
+            }
         }
+        self.cart.rom = old_self.cart.rom;
+        self.cart.info = old_self.cart.info;
 
         self.options = old_self.options;
         self.config = old_self.config;
@@ -121,28 +152,49 @@ impl GameGirl {
 
     /// Load the given cartridge.
     /// `reset` indicates if the system should be reset before loading.
-    pub fn load_cart(&mut self, cart: Cartridge, config: &SystemConfig, reset: bool) {
+    /// If no boot ROM is set for the model, DMG models use Bootix, while CGB
+    /// models start in the state the boot ROM would leave them in.
+    pub fn load_cart(
+        &mut self,
+        cart: Cartridge,
+        config: &SystemConfig,
+        reset: bool,
+    ) -> Result<(), FirmwareError> {
         if reset {
-            let old_self = mem::take(self);
+            let mut old_self = mem::take(self);
             self.debugger = old_self.debugger;
-            self.options.frame_finished = old_self.options.frame_finished;
+            self.options.take_callbacks(&mut old_self.options);
         }
         self.load_cart_mem(cart, config);
         self.config = config.clone();
+
+        self.mem.bootrom = Firmware::GgcBoot(self.model)
+            .load_if_set(config)?
+            .map(Vec::from)
+            .or_else(|| (config.ggc_bootix && !self.model.is_cgb()).then(|| BOOTIX_ROM.to_vec()));
+        if self.mem.bootrom.is_some() {
+            MemoryMapper::init_pages(self);
+        } else {
+            self.init_post_boot();
+        }
+        Ok(())
     }
 
     /// Create a system with a cart already loaded.
     pub fn with_cart(rom: Vec<u8>) -> Self {
         let mut gg = Self::default();
-        gg.load_cart(Cartridge::from_rom(rom), &SystemConfig::default(), false);
+        gg.load_cart(Cartridge::from_rom(rom), &SystemConfig::default(), false)
+            .unwrap();
         gg.options.running = true;
         gg.options.rom_loaded = true;
         gg
     }
 
     pub fn skip_bootrom(&mut self) {
-        self.cpu.pc = 0x100;
-        self.set8(BOOTROM_DISABLE, 1);
+        if self.mem.bootrom.is_some() {
+            self.set8(HIGH_START + BOOTROM_DISABLE, 1);
+            self.init_post_boot();
+        }
     }
 }
 
@@ -155,6 +207,7 @@ impl Default for GameGirl {
             config: SystemConfig::default(),
 
             cgb: false,
+            model: GgcModel::default(),
             debugger,
             scheduler: Scheduler::default(),
 
@@ -0,0 +1,199 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+use GgcModel::*;
+
+use crate::{
+    ggc::{
+        cpu::DReg,
+        io::{addr::*, cartridge::Cartridge, ppu::Ppu, timer::Timer},
+        GameGirl,
+    },
+    numutil::NumExt,
+};
+
+/// Hardware models of the GG(C). They differ in their boot ROM and the state
+/// it leaves the system in, which some games (and many tests) depend on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum GgcModel {
+    Dmg0,
+    Dmg,
+    Mgb,
+    Sgb,
+    Sgb2,
+    Cgb0,
+    Cgb,
+    Agb,
+}
+
+impl GgcModel {
+    pub const ALL: [GgcModel; 8] = [Dmg0, Dmg, Mgb, Sgb, Sgb2, Cgb0, Cgb, Agb];
+    /// Models without CGB features.
+    pub const DMG_MODELS: [GgcModel; 5] = [Dmg0, Dmg, Mgb, Sgb, Sgb2];
+    /// Models with CGB features.
+    pub const CGB_MODELS: [GgcModel; 3] = [Cgb0, Cgb, Agb];
+
+    /// If this model has CGB features.
+    pub fn is_cgb(self) -> bool {
+        matches!(self, Cgb0 | Cgb | Agb)
+    }
+
+    /// Value of the CPU's double registers after the boot ROM finished,
+    /// in the order AF, BC, DE, HL.
+    fn post_boot_regs(self, cart: &Cartridge, cgb_cart: bool) -> [u16; 4] {
+        // Flags depend on the header checksum on these models
+        let checksum_flags = if cart.rom[0x14D] == 0 { 0x80 } else { 0xB0 };
+        match self {
+            Dmg0 => [0x0100, 0xFF13, 0x00C1, 0x8403],
+            Dmg => [0x0100 | checksum_flags, 0x0013, 0x00D8, 0x014D],
+            Mgb => [0xFF00 | checksum_flags, 0x0013, 0x00D8, 0x014D],
+            Sgb => [0x0100, 0x0014, 0x0000, 0xC060],
+            Sgb2 => [0xFF00, 0x0014, 0x0000, 0xC060],
+
+            Cgb0 | Cgb | Agb => {
+                let (b, de, hl) = if cgb_cart {
+                    (0, 0xFF56, 0x000D)
+                } else if Self::nintendo_licensee(cart) {
+                    // The boot ROM hashes the title to pick a palette
+                    let hash = cart.rom[0x134..=0x143]
+                        .iter()
+                        .fold(0u8, |a, b| a.wrapping_add(*b));
+                    (hash, 0x0008, 0x991A)
+                } else {
+                    (0, 0x0008, 0x007C)
+                };
+
+                if self == Agb {
+                    // The AGB boot ROM ends with an additional `inc b`
+                    let b = b.wrapping_add(1);
+                    let flags = ((b == 0) as u16 * 0x80) | ((b & 0xF == 0) as u16 * 0x20);
+                    [0x1100 | flags, b.u16() << 8, de, hl]
+                } else {
+                    [0x1180, b.u16() << 8, de, hl]
+                }
+            }
+        }
+    }
+
+    /// Internal DIV counter after the boot ROM finished.
+    /// The values for CGB carts are not covered by the test ROMs.
+    fn post_boot_div(self, cgb_cart: bool) -> u16 {
+        match self {
+            Dmg0 => 0x182C,
+            Dmg | Mgb => 0xABC8,
+            Sgb => 0xD85C,
+            Sgb2 => 0xD84C,
+            Cgb0 | Cgb if cgb_cart => 0x2F04,
+            Agb if cgb_cart => 0x2F08,
+            Cgb0 => 0x2880,
+            Cgb => 0x2674,
+            Agb => 0x2678,
+        }
+    }
+
+    /// If the cart uses Nintendo's licensee code, which makes the CGB boot ROM
+    /// pick a palette for it.
+    fn nintendo_licensee(cart: &Cartridge) -> bool {
+        match cart.rom[0x14B] {
+            0x01 => true,
+            0x33 => &cart.rom[0x144..=0x145] == b"01",
+            _ => false,
+        }
+    }
+}
+
+impl Default for GgcModel {
+    fn default() -> Self {
+        Dmg
+    }
+}
+
+impl Display for GgcModel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Dmg0 => "DMG0",
+            Dmg => "DMG",
+            Mgb => "MGB",
+            Sgb => "SGB",
+            Sgb2 => "SGB2",
+            Cgb0 => "CGB0",
+            Cgb => "CGB",
+            Agb => "AGB",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl GameGirl {
+    /// Set up the system in the state the boot ROM of the current model
+    /// would leave it in.
+    pub(super) fn init_post_boot(&mut self) {
+        let cgb_cart = self.cart.supports_cgb();
+        let regs = self.model.post_boot_regs(&self.cart, cgb_cart);
+        for (reg, value) in [DReg::AF, DReg::BC, DReg::DE, DReg::HL]
+            .into_iter()
+            .zip(regs)
+        {
+            self.cpu.set_dreg(reg, value);
+        }
+        self.cpu.sp = 0xFFFE;
+        self.cpu.pc = 0x100;
+
+        // Sound: Channel 1 is still on after playing the boot sound,
+        // except on the SGB which does not play it
+        let sgb = matches!(self.model, Sgb | Sgb2);
+        self.set8(0xFF26, 0x80); // NR52
+        for (addr, value) in [
+            (0xFF10, 0x80), // NR10
+            (0xFF11, 0xBF),
+            (0xFF12, 0xF3),
+            (0xFF13, 0xFF),
+            (0xFF14, if sgb { 0x3F } else { 0xBF }),
+            (0xFF16, 0x3F), // NR21
+            (0xFF17, 0x00),
+            (0xFF18, 0xFF),
+            (0xFF19, 0x3F),
+            (0xFF1A, 0x7F), // NR30
+            (0xFF1B, 0xFF),
+            (0xFF1C, 0x9F),
+            (0xFF1D, 0xFF),
+            (0xFF1E, 0x3F),
+            (0xFF20, 0xFF), // NR41
+            (0xFF21, 0x00),
+            (0xFF22, 0x00),
+            (0xFF23, 0x3F),
+            (0xFF24, 0x77), // NR50
+            (0xFF25, 0xF3),
+        ] {
+            self.set8(addr, value);
+        }
+
+        if self.cgb && !cgb_cart {
+            // DMG compatibility mode, with a greyscale palette
+            self.set8(HIGH_START + OPRI, 1);
+            for (cps, cpd, count) in [(BCPS, BCPD, 1), (OCPS, OCPD, 2)] {
+                self.set8(HIGH_START + cps, 0x80);
+                for _ in 0..count {
+                    for colour in [0x7FFF_u16, 0x5294, 0x294A, 0x0000] {
+                        self.set8(HIGH_START + cpd, colour.u8());
+                        self.set8(HIGH_START + cpd, (colour >> 8).u8());
+                    }
+                }
+            }
+        }
+
+        self.set8(HIGH_START + LCDC, 0x91);
+        self.set8(HIGH_START + BGP, 0xFC);
+        self.set8(HIGH_START + IF, 0xE1);
+        self[SC] = if self.cgb { 0x7F } else { 0x7E };
+        self[DMA] = if self.cgb { 0x00 } else { 0xFF };
+        Timer::set_div(self, self.model.post_boot_div(cgb_cart));
+        Ppu::skip_to_vblank(self);
+    }
+}
@@ -12,6 +12,7 @@
 #![feature(generic_const_exprs)]
 #![warn(clippy::pedantic)]
 #![allow(clippy::missing_panics_doc)]
+#![allow(clippy::missing_errors_doc)]
 #![allow(clippy::missing_safety_doc)]
 #![allow(clippy::must_use_candidate)]
 #![allow(clippy::cast_precision_loss)]
@@ -40,6 +41,8 @@ pub mod components;
 pub mod gga;
 pub mod ggc;
 pub mod nds;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod netplay;
 pub mod numutil;
 pub mod psx;
 
@@ -17,4 +17,8 @@ impl Cartridge {
     pub fn load_rom(&mut self, rom: Vec<u8>) {
         self.rom = rom;
     }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
 }
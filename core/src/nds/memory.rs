@@ -20,8 +20,6 @@ use crate::{
 
 pub const KB: usize = 1024;
 pub const MB: usize = KB * KB;
-pub const BIOS7: &[u8] = include_bytes!("bios7.bin");
-pub const BIOS9: &[u8] = include_bytes!("bios9.bin");
 
 /// Memory struct containing the NDS's memory regions along with page tables
 /// and other auxiliary cached information relating to memory.
@@ -42,6 +40,11 @@ pub struct Memory {
     inst_tcm: [u8; 32 * KB],
     #[serde(with = "serde_arrays")]
     data_tcm: [u8; 16 * KB],
+    /// BIOS images, loaded at runtime.
+    #[serde(skip)]
+    pub(super) bios7: Box<[u8]>,
+    #[serde(skip)]
+    pub(super) bios9: Box<[u8]>,
 
     mapper7: MemoryMapper<8192>,
     mapper9: MemoryMapper<8192>,
@@ -58,6 +61,12 @@ impl Nds {
         MemoryMapper::init_pages(&mut self.nds7());
         MemoryMapper::init_pages(&mut self.nds9());
     }
+
+    /// Set the BIOS images of both CPUs. Call [init_memory] afterwards.
+    pub fn set_bios(&mut self, bios7: Box<[u8]>, bios9: Box<[u8]>) {
+        self.memory.bios7 = bios7;
+        self.memory.bios9 = bios9;
+    }
 }
 
 impl Nds7 {
@@ -118,7 +127,8 @@ impl Nds9 {
             }
 
             0xFFFF_0000..=0xFFFF_FFFF => unsafe {
-                let ptr = BIOS9.as_ptr().add(addr.us() % BIOS9.len());
+                let bios = &self.memory.bios9;
+                let ptr = bios.as_ptr().add(addr.us() % bios.len());
                 ptr.cast::<T>().read()
             },
 
@@ -166,6 +176,8 @@ impl Default for Memory {
             wram7: [0; 64 * KB],
             inst_tcm: [0; 32 * KB],
             data_tcm: [0; 16 * KB],
+            bios7: vec![0; 16 * KB].into_boxed_slice(),
+            bios9: vec![0; 4 * KB].into_boxed_slice(),
 
             mapper7: MemoryMapper::default(),
             mapper9: MemoryMapper::default(),
@@ -217,7 +229,7 @@ impl MemoryMappedSystem<8192> for Nds7 {
         }
 
         match a {
-            0x0000_0000..=0x00FF_FFFF if R => offs(BIOS7, a),
+            0x0000_0000..=0x00FF_FFFF if R => offs(&self.memory.bios7, a),
             0x0200_0000..=0x02FF_FFFF => offs(&self.memory.psram, a - 0x200_0000),
             // TODO not quite right...
             0x0300_0000..=0x037F_FFFF => offs(&self.memory.wram, a - 0x300_0000),
@@ -80,6 +80,10 @@ macro_rules! deref {
 deref!(Nds7, mmio7);
 deref!(Nds9, mmio9);
 
+/// Frames per second: 263 lines of 2130 ARM7 cycles each, which run at half
+/// the ARM9 clock.
+pub const FRAME_RATE: f64 = NDS9_CLOCK as f64 / (263.0 * 2130.0 * 2.0);
+
 #[derive(Deserialize, Serialize)]
 pub struct Nds {
     cpu7: Cpu<Nds7>,
@@ -132,9 +136,17 @@ impl Nds {
         Nds9(self as *mut Nds)
     }
 
+    /// Reset the console, while keeping the current cartridge inserted.
+    pub fn reset(&mut self) {
+        let old_self = mem::take(self);
+        self.restore_from(old_self, true);
+    }
+
     /// Restore state after a savestate load. `old_self` should be the
     /// system state before the state was loaded.
-    pub fn restore_from(&mut self, old_self: Self) {
+    /// Carts do not have save data yet, so `_restore_save` is unused.
+    pub fn restore_from(&mut self, old_self: Self, _restore_save: bool) {
+        self.set_bios(old_self.memory.bios7, old_self.memory.bios9);
         self.options = old_self.options;
         self.config = old_self.config;
         self.debugger = old_self.debugger;
@@ -0,0 +1,650 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Two-player netplay, where both players control the same system.
+//! Both sides run their own copy of the system and only exchange inputs
+//! over TCP. Since waiting for the other player's inputs every frame would
+//! make the game run only as fast as the connection allows, their input
+//! is predicted to stay the same; once it arrives and the prediction turns
+//! out to be wrong, the system is rolled back to the frame the input was for
+//! using save states and emulated again with the correct input.
+//!
+//! To catch the systems going out of sync anyway (for example due to the
+//! game using an RTC), both sides regularly exchange hashes of their state.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::{self, Display, Formatter},
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{Button, SystemKind},
+    System,
+};
+
+/// Frames between state hashes being compared.
+pub const HASH_INTERVAL: u32 = 120;
+/// Maximum amount of frames the local system may run ahead of the last
+/// frame the remote input is known for. If it gets further ahead, emulation
+/// stalls until the other player catches up.
+pub const MAX_ROLLBACK: u32 = 8;
+/// Time emulated per frame, in seconds. Both sides must use the same.
+const FRAME_DELTA: f32 = 1.0 / 60.0;
+/// Maximum size of a packet, to not run out of memory on invalid data.
+/// The largest packet is the compressed state sent at the start.
+const MAX_PACKET_SIZE: u64 = 16 * 1024 * 1024;
+/// Maximum size of the state sent at the start, once decompressed.
+const MAX_STATE_SIZE: u64 = 64 * 1024 * 1024;
+/// Time to wait between checking for a player connecting or hosting being
+/// cancelled.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A netplay session with another player.
+pub struct Netplay {
+    /// Connection to the other player.
+    stream: TcpStream,
+    /// Address of the other player.
+    peer: SocketAddr,
+    /// Data received that does not form a full packet yet.
+    received: Vec<u8>,
+    /// Data that could not be sent yet.
+    outgoing: Vec<u8>,
+
+    /// Frames local inputs are delayed by. A higher delay means fewer
+    /// rollbacks on slower connections.
+    delay: u32,
+    /// The next frame to be emulated.
+    frame: u32,
+    /// Local inputs by frame, as button bitmasks.
+    local: BTreeMap<u32, u16>,
+    /// Remote inputs by frame, as button bitmasks.
+    remote: BTreeMap<u32, u16>,
+    /// Amount of frames the remote input is known for.
+    confirmed: u32,
+    /// The last known remote input, used as prediction for later frames.
+    last_remote: u16,
+    /// States from the start of frames that were emulated with a predicted
+    /// remote input, oldest first.
+    history: VecDeque<Prediction>,
+
+    /// The next frame to compare state hashes at.
+    next_hash: u32,
+    /// Local state hashes the remote one is not known for yet, by frame.
+    local_hashes: HashMap<u32, u64>,
+    /// Remote state hashes the local one is not known for yet, by frame.
+    remote_hashes: HashMap<u32, u64>,
+    /// First frame a state hash mismatch was found at.
+    desync: Option<u32>,
+    /// Amount of rollbacks done so far.
+    rollbacks: u64,
+}
+
+impl Netplay {
+    /// Wait for another player to connect on the given port, then send them
+    /// the current state of the system. Blocks until a player connected, or
+    /// until `cancel` is set.
+    pub fn host(
+        port: u16,
+        delay: u32,
+        gg: &Mutex<System>,
+        cancel: &AtomicBool,
+    ) -> Result<Self, NetplayError> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let stream = loop {
+            match listener.accept() {
+                Ok((stream, _)) => break stream,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    if cancel.load(Ordering::Relaxed) {
+                        return Err(NetplayError::Cancelled);
+                    }
+                    thread::sleep(ACCEPT_POLL_INTERVAL);
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        };
+        // Streams might inherit being non-blocking from the listener
+        stream.set_nonblocking(false)?;
+
+        let mut gg = gg.lock().unwrap();
+        Self::check_system(&mut gg)?;
+        // Load the state that is sent to make sure both sides start
+        // from exactly the same point
+        let state = gg.save_raw_state();
+        gg.load_raw_state(&state);
+        let start = Packet::Start {
+            kind: gg.kind(),
+            rom_crc: rom_crc(&gg),
+            title: gg.cart_info().map(|info| info.title).unwrap_or_default(),
+            state: zstd::encode_all(&state[..], 3)?,
+        };
+        let mut netplay = Self::new(stream, delay)?;
+        netplay.outgoing = start.encode();
+        netplay.flush(true)?;
+        netplay.start(&mut gg)?;
+        Ok(netplay)
+    }
+
+    /// Connect to a player hosting at the given address and load the state
+    /// of their system, which must be running the same game.
+    pub fn join(
+        addr: impl ToSocketAddrs,
+        delay: u32,
+        gg: &Mutex<System>,
+    ) -> Result<Self, NetplayError> {
+        let stream = TcpStream::connect(addr)?;
+        let mut netplay = Self::new(stream, delay)?;
+        let packet = netplay.read_packet(true)?;
+
+        let mut gg = gg.lock().unwrap();
+        Self::check_system(&mut gg)?;
+        match packet {
+            Some(Packet::Start { kind, .. }) if kind != gg.kind() => {
+                return Err(NetplayError::WrongSystem(kind))
+            }
+            Some(Packet::Start {
+                rom_crc: crc,
+                title,
+                ..
+            }) if crc != rom_crc(&gg) => return Err(NetplayError::WrongGame(title)),
+            Some(Packet::Start { state, .. }) => {
+                let mut decoded = Vec::new();
+                zstd::stream::Decoder::new(&state[..])?
+                    .take(MAX_STATE_SIZE)
+                    .read_to_end(&mut decoded)?;
+                gg.try_load_raw_state(&decoded)
+                    .map_err(|_| NetplayError::InvalidPacket)?;
+            }
+            _ => return Err(NetplayError::InvalidPacket),
+        }
+        netplay.start(&mut gg)?;
+        Ok(netplay)
+    }
+
+    fn new(stream: TcpStream, delay: u32) -> Result<Self, NetplayError> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            peer: stream.peer_addr()?,
+            stream,
+            received: Vec::new(),
+            outgoing: Vec::new(),
+            delay,
+            frame: 0,
+            local: BTreeMap::new(),
+            remote: BTreeMap::new(),
+            confirmed: 0,
+            last_remote: 0,
+            history: VecDeque::new(),
+            next_hash: 0,
+            local_hashes: HashMap::new(),
+            remote_hashes: HashMap::new(),
+            desync: None,
+            rollbacks: 0,
+        })
+    }
+
+    /// Make sure the system can be used for netplay.
+    fn check_system(gg: &mut System) -> Result<(), NetplayError> {
+        match gg.kind() {
+            _ if !gg.options().rom_loaded => Err(NetplayError::NoRom),
+            SystemKind::Ggc | SystemKind::Gga => Ok(()),
+            kind => Err(NetplayError::Unsupported(kind)),
+        }
+    }
+
+    /// Prepare the system for the session, after both sides have the same
+    /// state.
+    fn start(&mut self, gg: &mut System) -> Result<(), NetplayError> {
+        gg.options().running = true;
+        // The other player needs inputs for the frames before the delay
+        // kicks in as well
+        for frame in 0..self.delay {
+            self.add_local(frame, 0);
+        }
+        self.stream.set_nonblocking(true)?;
+        self.flush(false)
+    }
+
+    /// Emulate the next frame with the given local input, a bitmask of
+    /// [Button]s. Does nothing if the system is paused, or if the other
+    /// player fell too far behind; in that case, the system is only advanced
+    /// once they caught up.
+    /// Rolls back and emulates previous frames again if their predicted
+    /// remote input was wrong.
+    pub fn advance(&mut self, gg: &mut System, local: u16) -> Result<(), NetplayError> {
+        self.flush(false)?;
+        while let Some(packet) = self.read_packet(false)? {
+            self.receive(&packet)?;
+        }
+
+        // Hash states of frames that are confirmed now and are not
+        // emulated again by the rollback below
+        let rollback = self
+            .history
+            .iter()
+            .find(|p| p.frame < self.confirmed && p.remote != self.remote[&p.frame])
+            .map(|p| p.frame);
+        while self.next_hash <= self.confirmed
+            && self.next_hash < self.frame
+            && rollback.map_or(true, |frame| self.next_hash < frame)
+        {
+            let now = gg.save_raw_state();
+            let frame = self.next_hash;
+            let prediction = self.history.iter().find(|p| p.frame == frame).unwrap();
+            gg.load_raw_state_with_save(&prediction.state);
+            self.add_hash(gg);
+            gg.load_raw_state_with_save(&now);
+        }
+
+        if let Some(frame) = rollback {
+            let idx = self.history.iter().position(|p| p.frame == frame).unwrap();
+            gg.load_raw_state_with_save(&self.history[idx].state);
+            self.history.truncate(idx);
+            self.rollbacks += 1;
+            for frame in frame..self.frame {
+                self.emulate(gg, frame);
+            }
+        }
+        let confirmed = self.confirmed;
+        self.history.retain(|p| p.frame >= confirmed);
+
+        if !gg.options().running || self.frame >= self.confirmed + MAX_ROLLBACK {
+            return self.flush(false);
+        }
+        self.add_local(self.frame + self.delay, local);
+        self.emulate(gg, self.frame);
+        self.frame += 1;
+
+        let done = self.frame.min(self.confirmed);
+        self.local.retain(|frame, _| *frame >= done);
+        self.remote.retain(|frame, _| *frame >= done);
+        self.flush(false)
+    }
+
+    /// Emulate the given frame, saving the state beforehand if the remote
+    /// input has to be predicted.
+    fn emulate(&mut self, gg: &mut System, frame: u32) {
+        if frame == self.next_hash && frame <= self.confirmed {
+            self.add_hash(gg);
+        }
+
+        let remote = if let Some(remote) = self.remote.get(&frame) {
+            *remote
+        } else {
+            self.history.push_back(Prediction {
+                frame,
+                state: gg.save_raw_state(),
+                remote: self.last_remote,
+            });
+            self.last_remote
+        };
+        let buttons = self.local.get(&frame).copied().unwrap_or(0) | remote;
+        for (idx, button) in Button::BUTTONS.iter().enumerate() {
+            gg.set_button(*button, (buttons >> idx) & 1 != 0);
+        }
+        gg.options().speed_multiplier = 1.0;
+        gg.advance_delta(FRAME_DELTA);
+    }
+
+    /// Hash the system's state at the start of frame [next_hash], which
+    /// all inputs before are known for, and send it to the other player.
+    fn add_hash(&mut self, gg: &mut System) {
+        let frame = self.next_hash;
+        let hash = gg.state_hash();
+        self.next_hash += HASH_INTERVAL;
+        self.send(&Packet::Hash { frame, hash });
+        match self.remote_hashes.remove(&frame) {
+            Some(remote) => self.compare_hashes(frame, hash, remote),
+            None => {
+                self.local_hashes.insert(frame, hash);
+            }
+        }
+    }
+
+    /// Record and send a local input.
+    fn add_local(&mut self, frame: u32, buttons: u16) {
+        if self.local.insert(frame, buttons).is_none() {
+            self.send(&Packet::Input { frame, buttons });
+        }
+    }
+
+    /// Handle a packet received during the session.
+    fn receive(&mut self, packet: &Packet) -> Result<(), NetplayError> {
+        match *packet {
+            Packet::Input { frame, buttons } if frame == self.confirmed => {
+                self.remote.insert(frame, buttons);
+                self.confirmed += 1;
+                self.last_remote = buttons;
+            }
+            Packet::Hash { frame, hash } => match self.local_hashes.remove(&frame) {
+                Some(local) => self.compare_hashes(frame, local, hash),
+                None => {
+                    self.remote_hashes.insert(frame, hash);
+                }
+            },
+            _ => return Err(NetplayError::InvalidPacket),
+        }
+        Ok(())
+    }
+
+    fn compare_hashes(&mut self, frame: u32, local: u64, remote: u64) {
+        if local != remote && self.desync.is_none() {
+            log::error!("Netplay desync detected at frame {frame}!");
+            self.desync = Some(frame);
+        }
+    }
+
+    fn send(&mut self, packet: &Packet) {
+        self.outgoing.extend(packet.encode());
+    }
+
+    /// Send as much outgoing data as possible, or all of it if `block`.
+    fn flush(&mut self, block: bool) -> Result<(), NetplayError> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(NetplayError::Disconnected),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock && !block => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the next packet, if one was received.
+    fn read_packet(&mut self, block: bool) -> Result<Option<Packet>, NetplayError> {
+        loop {
+            if let Some(packet) = Packet::decode(&mut self.received)? {
+                return Ok(Some(packet));
+            }
+
+            let mut buf = [0; 4096];
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(NetplayError::Disconnected),
+                Ok(len) => self.received.extend_from_slice(&buf[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock && !block => return Ok(None),
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Address of the other player.
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// The next frame to be emulated.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Amount of frames emulated with a predicted remote input.
+    pub fn frames_ahead(&self) -> u32 {
+        self.frame.saturating_sub(self.confirmed)
+    }
+
+    /// Amount of rollbacks done so far.
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    /// First frame the systems were found to be out of sync at, if any.
+    pub fn desync(&self) -> Option<u32> {
+        self.desync
+    }
+}
+
+/// A frame that was emulated with a predicted remote input.
+struct Prediction {
+    frame: u32,
+    /// State at the start of the frame.
+    state: Vec<u8>,
+    /// The predicted remote input.
+    remote: u16,
+}
+
+/// A packet exchanged between players.
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    /// Sent by the host once the other player connected.
+    Start {
+        kind: SystemKind,
+        /// CRC32 of the host's ROM, to make sure both run the same game.
+        rom_crc: u32,
+        /// Title of the host's game, shown if it is not the same.
+        title: String,
+        /// zstd-compressed raw state of the host's system.
+        state: Vec<u8>,
+    },
+    /// Input of a player for a frame. Sent for every frame in order.
+    Input { frame: u32, buttons: u16 },
+    /// Hash of the state at the start of the given frame.
+    Hash { frame: u32, hash: u64 },
+}
+
+impl Packet {
+    /// Encode the packet as bincode, prefixed with its length.
+    fn encode(&self) -> Vec<u8> {
+        let data = bincode_options().serialize(self).unwrap();
+        let mut out = (data.len() as u32).to_le_bytes().to_vec();
+        out.extend(data);
+        out
+    }
+
+    /// Decode and remove the first packet in the given buffer, if it
+    /// was received in full.
+    fn decode(buf: &mut Vec<u8>) -> Result<Option<Self>, NetplayError> {
+        if buf.len() < 4 {
+            return Ok(None);
+        }
+        let len = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        if len as u64 > MAX_PACKET_SIZE {
+            return Err(NetplayError::InvalidPacket);
+        }
+        if buf.len() < 4 + len {
+            return Ok(None);
+        }
+        let packet = bincode_options().deserialize(&buf[4..4 + len]);
+        buf.drain(..4 + len);
+        packet.map(Some).map_err(|_| NetplayError::InvalidPacket)
+    }
+}
+
+/// Options used for encoding packets, limiting their size since they
+/// come from the network.
+fn bincode_options() -> impl Options {
+    bincode::options().with_limit(MAX_PACKET_SIZE)
+}
+
+/// CRC32 of the ROM the system is running.
+fn rom_crc(gg: &System) -> u32 {
+    match gg {
+        System::GGC(gg) => crc32fast::hash(&gg.cart.rom),
+        System::GGA(gg) => crc32fast::hash(&gg.cart.rom),
+        _ => 0,
+    }
+}
+
+/// Error ending a netplay session.
+#[derive(Debug)]
+pub enum NetplayError {
+    /// The connection failed.
+    Io(io::Error),
+    /// The other player closed the connection.
+    Disconnected,
+    /// The other player sent something unexpected.
+    InvalidPacket,
+    /// The other player is running a game for a different system.
+    WrongSystem(SystemKind),
+    /// The other player is running a different game or ROM, with the
+    /// given title.
+    WrongGame(String),
+    /// There is no game running to play.
+    NoRom,
+    /// Netplay is not supported on the system.
+    Unsupported(SystemKind),
+    /// Hosting was cancelled before a player connected.
+    Cancelled,
+}
+
+impl Display for NetplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NetplayError::Io(err) => write!(f, "Connection failed: {err}"),
+            NetplayError::Disconnected => write!(f, "The other player disconnected."),
+            NetplayError::InvalidPacket => write!(f, "The other player sent invalid data."),
+            NetplayError::WrongSystem(kind) => write!(
+                f,
+                "The other player is playing a {kind} game. Load the same game to join."
+            ),
+            NetplayError::WrongGame(title) => write!(
+                f,
+                "The other player is playing '{title}', or another version of this game. Load the same ROM to join."
+            ),
+            NetplayError::NoRom => write!(f, "Load a game before hosting or joining."),
+            NetplayError::Unsupported(kind) => write!(f, "Netplay is not supported on {kind}."),
+            NetplayError::Cancelled => write!(f, "Hosting was cancelled."),
+        }
+    }
+}
+
+impl std::error::Error for NetplayError {}
+
+impl From<io::Error> for NetplayError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::Ipv4Addr,
+        sync::{atomic::AtomicBool, Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        ggc::GameGirl,
+        netplay::{Netplay, NetplayError},
+        System,
+    };
+
+    /// Run the given function on a new thread, since loading states of the
+    /// systems needs a lot of stack space.
+    fn with_stack<T: Send + 'static>(
+        inner: impl FnOnce() -> T + Send + 'static,
+    ) -> thread::JoinHandle<T> {
+        thread::Builder::new()
+            .stack_size(32 << 20)
+            .spawn(inner)
+            .unwrap()
+    }
+
+    /// A player's session, together with their system.
+    type Side = Result<(Netplay, Arc<Mutex<System>>), NetplayError>;
+
+    fn system(rom: Vec<u8>) -> Arc<Mutex<System>> {
+        Arc::new(Mutex::new(System::GGC(Box::new(GameGirl::with_cart(rom)))))
+    }
+
+    /// Host on the given port in another thread and join from this one.
+    /// Inputs are not delayed, so that predictions are needed.
+    fn connect(port: u16, host_rom: Vec<u8>, join_rom: Vec<u8>) -> (Side, Side) {
+        let host_gg = system(host_rom);
+        let host = {
+            let gg = host_gg.clone();
+            with_stack(move || Netplay::host(port, 0, &gg, &AtomicBool::new(false)))
+        };
+
+        let join_gg = system(join_rom);
+        let joined = loop {
+            match Netplay::join((Ipv4Addr::LOCALHOST, port), 0, &join_gg) {
+                // The host might not be listening yet
+                Err(NetplayError::Io(_)) => thread::sleep(Duration::from_millis(10)),
+                result => break result,
+            }
+        };
+        let host = host.join().unwrap();
+        (
+            host.map(|host| (host, host_gg)),
+            joined.map(|join| (join, join_gg)),
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        with_stack(round_trip_inner).join().unwrap();
+    }
+
+    fn round_trip_inner() {
+        let (host, join) = connect(47_311, vec![0; 0x8000], vec![0; 0x8000]);
+        let (mut host, host_gg) = host.unwrap();
+        let (mut join, join_gg) = join.unwrap();
+        let mut host_gg = host_gg.lock().unwrap();
+        let mut join_gg = join_gg.lock().unwrap();
+
+        // Different inputs on each side, to make the predictions wrong
+        for frame in 0..40 {
+            host.advance(&mut host_gg, (frame / 4) & 1).unwrap();
+            join.advance(&mut join_gg, (frame / 3) & 2).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        // Pause to let both sides receive all inputs and roll back
+        host_gg.options().running = false;
+        join_gg.options().running = false;
+        while host.frames_ahead() > 0 || join.frames_ahead() > 0 {
+            host.advance(&mut host_gg, 0).unwrap();
+            join.advance(&mut join_gg, 0).unwrap();
+        }
+
+        assert!(host.frame() > 0 && join.frame() > 0);
+        assert!(host.rollbacks() + join.rollbacks() > 0);
+        // The hashes of the first frame were exchanged and matched
+        for side in [&host, &join] {
+            assert!(side.next_hash > 0);
+            assert!(!side.local_hashes.contains_key(&0));
+            assert!(!side.remote_hashes.contains_key(&0));
+            assert_eq!(side.desync(), None);
+        }
+    }
+
+    #[test]
+    fn wrong_rom() {
+        with_stack(|| {
+            let mut other = vec![0; 0x8000];
+            other[0x150] = 1;
+            let (_, join) = connect(47_312, vec![0; 0x8000], other);
+            assert!(matches!(join, Err(NetplayError::WrongGame(_))));
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn cancel_host() {
+        let gg = system(vec![0; 0x8000]);
+        let result = Netplay::host(47_313, 2, &gg, &AtomicBool::new(true));
+        assert!(matches!(result, Err(NetplayError::Cancelled)));
+    }
+}
@@ -13,7 +13,6 @@ use crate::{
 
 const KB: usize = 1024;
 const MB: usize = KB * KB;
-const BIOS: &[u8] = include_bytes!("bios.bin");
 
 #[derive(Deserialize, Serialize)]
 pub struct Memory {
@@ -23,6 +22,9 @@ pub struct Memory {
     scratchpad: [u8; KB],
     #[serde(with = "serde_arrays")]
     pub mmio: [u8; 8 * KB],
+    /// BIOS image, loaded at runtime. Empty until then.
+    #[serde(skip)]
+    pub(super) bios: Box<[u8]>,
 }
 
 impl PlayStation {
@@ -32,7 +34,7 @@ impl PlayStation {
             0x1F80_0000..=0x1F80_03FF => self.memory.scratchpad[addr.us() - 0x1F80_0000],
             0x1F80_1000..=0x1F80_1FFF => self.memory.mmio[addr.us() - 0x1F80_1000],
 
-            0x1FC0_0000..=0x1FC7_FFFF => BIOS[addr.us() - 0xBFC0_0000],
+            0x1FC0_0000..=0x1FC7_FFFF => self.memory.bios[addr.us() - 0xBFC0_0000],
             unknown => {
                 log::warn!(
                     "Read from unmapped address {addr} (physical address {unknown}), reading 0xFF"
@@ -94,6 +96,7 @@ impl Default for Memory {
             ram: [0; 2 * MB],
             scratchpad: [0; KB],
             mmio: [0; 8 * KB],
+            bios: Box::default(),
         }
     }
 }
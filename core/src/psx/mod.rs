@@ -27,6 +27,10 @@ mod scheduling;
 
 pub type PsxDebugger = Debugger<u32>;
 
+/// Frames per second. The GPU is not emulated far enough to have timing yet,
+/// so this is only what NTSC roughly runs at.
+pub const FRAME_RATE: f64 = 60.0;
+
 /// System state representing entire console.
 #[derive(Default, Deserialize, Serialize)]
 pub struct PlayStation {
@@ -61,9 +65,17 @@ impl PlayStation {
         }
     }
 
+    /// Reset the console, while keeping the current cartridge inserted.
+    pub fn reset(&mut self) {
+        let old_self = mem::take(self);
+        self.restore_from(old_self, true);
+    }
+
     /// Restore state after a savestate load. `old_self` should be the
     /// system state before the state was loaded.
-    pub fn restore_from(&mut self, old_self: Self) {
+    /// Carts do not have save data yet, so `_restore_save` is unused.
+    pub fn restore_from(&mut self, old_self: Self, _restore_save: bool) {
+        self.memory.bios = old_self.memory.bios;
         self.options = old_self.options;
         self.config = old_self.config;
         self.debugger = old_self.debugger;
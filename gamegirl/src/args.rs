@@ -0,0 +1,172 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Command line arguments of `gamegirl_bin`.
+
+use core::common::{CgbMode, SystemConfig};
+use std::path::PathBuf;
+
+use crate::gui::SLOTS;
+
+/// Usage text printed for `--help` and invalid arguments.
+pub const USAGE: &str = "\
+Usage: gamegirl_bin [OPTIONS] [ROM]
+
+Arguments:
+  [ROM]                      ROM to boot
+
+Options:
+  -f, --fullscreen           Start in fullscreen, showing only the game
+  -s, --slot <SLOT>          Load the given save state slot after booting ROM (1-10)
+      --cgb-mode <MODE>      When to run GG carts in colour mode: always, prefer, never
+      --cached-interpreter   Enable the cached interpreter
+      --no-cached-interpreter
+                             Disable the cached interpreter
+  -h, --help                 Print this help";
+
+/// Options given on the command line, applied when starting the GUI.
+#[derive(Default)]
+pub struct Args {
+    /// ROM to boot right away.
+    pub rom: Option<PathBuf>,
+    /// If only the game should be shown, in fullscreen.
+    pub fullscreen: bool,
+    /// Save state slot to load after booting the ROM, starting at 0.
+    pub slot: Option<usize>,
+    /// Overrides for the configured system options.
+    pub overrides: ConfigOverrides,
+}
+
+impl Args {
+    /// Parse the given arguments, without the program name.
+    /// Returns `Ok(None)` if help was requested.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {name}"))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-f" | "--fullscreen" => parsed.fullscreen = true,
+                "-s" | "--slot" => {
+                    let slot = value(&arg)?;
+                    match slot.parse::<usize>() {
+                        Ok(slot @ 1..=SLOTS) => parsed.slot = Some(slot - 1),
+                        _ => return Err(format!("Invalid slot '{slot}', expected 1-{SLOTS}")),
+                    }
+                }
+                "--cgb-mode" => {
+                    let mode = value(&arg)?;
+                    parsed.overrides.cgb_mode = Some(match mode.to_lowercase().as_str() {
+                        "always" => CgbMode::Always,
+                        "prefer" => CgbMode::Prefer,
+                        "never" => CgbMode::Never,
+                        _ => return Err(format!("Invalid CGB mode '{mode}'")),
+                    });
+                }
+                "--cached-interpreter" => parsed.overrides.cached_interpreter = Some(true),
+                "--no-cached-interpreter" => parsed.overrides.cached_interpreter = Some(false),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option '{arg}'")),
+                _ if parsed.rom.is_none() => parsed.rom = Some(arg.into()),
+                _ => return Err(format!("Unexpected argument '{arg}'")),
+            }
+        }
+        if parsed.slot.is_some() && parsed.rom.is_none() {
+            return Err("A save state slot can only be loaded with a ROM".to_string());
+        }
+        Ok(Some(parsed))
+    }
+}
+
+/// Overrides for the system options set in the GUI, which are not saved.
+#[derive(Default)]
+pub struct ConfigOverrides {
+    pub cgb_mode: Option<CgbMode>,
+    pub cached_interpreter: Option<bool>,
+}
+
+impl ConfigOverrides {
+    /// Apply the overrides to the given config.
+    pub fn apply(&self, config: &mut SystemConfig) {
+        if let Some(mode) = &self.cgb_mode {
+            config.mode = mode.clone();
+        }
+        if let Some(cached) = self.cached_interpreter {
+            config.cached_interpreter = cached;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::common::CgbMode;
+
+    use crate::{args::Args, gui::SLOTS};
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        Args::parse(args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn rom_and_options() {
+        let args = parse(&["-f", "game.gba", "--slot", "3", "--cgb-mode", "Never"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(args.rom.unwrap().to_str(), Some("game.gba"));
+        assert!(args.fullscreen);
+        assert_eq!(args.slot, Some(2));
+        assert!(matches!(args.overrides.cgb_mode, Some(CgbMode::Never)));
+        assert_eq!(args.overrides.cached_interpreter, None);
+    }
+
+    #[test]
+    fn help() {
+        assert!(parse(&["game.gba", "--help"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn slot_range() {
+        assert!(parse(&["game.gba", "-s", "1"]).is_ok());
+        assert!(parse(&["game.gba", "-s", &SLOTS.to_string()]).is_ok());
+        for slot in ["0", &(SLOTS + 1).to_string(), "-1", "one"] {
+            let err = parse(&["game.gba", "-s", slot]).err().unwrap();
+            assert_eq!(err, format!("Invalid slot '{slot}', expected 1-{SLOTS}"));
+        }
+        assert_eq!(
+            parse(&["game.gba", "--slot"]).err().unwrap(),
+            "Missing value for --slot"
+        );
+    }
+
+    #[test]
+    fn slot_without_rom() {
+        assert!(parse(&["-s", "1"]).is_err());
+        assert!(parse(&["-s", "1", "game.gba"]).is_ok());
+    }
+
+    #[test]
+    fn unknown_option() {
+        assert_eq!(
+            parse(&["game.gba", "--turbo"]).err().unwrap(),
+            "Unknown option '--turbo'"
+        );
+        assert_eq!(
+            parse(&["--cgb-mode", "sometimes"]).err().unwrap(),
+            "Invalid CGB mode 'sometimes'"
+        );
+    }
+
+    #[test]
+    fn extra_argument() {
+        assert_eq!(
+            parse(&["game.gba", "other.gba"]).err().unwrap(),
+            "Unexpected argument 'other.gba'"
+        );
+    }
+}
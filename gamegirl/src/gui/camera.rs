@@ -0,0 +1,48 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use core::{
+    common::System,
+    ggc::io::camera::{CameraSensor, CAMERA_HEIGHT, CAMERA_WIDTH},
+};
+
+use png::{Decoder, Transformations};
+
+/// Set the image seen by the GGC camera sensor.
+/// `None` makes the camera see a test pattern instead.
+pub fn set_image(gg: &mut System, image: Option<Vec<u8>>) {
+    let image = image.unwrap_or_else(CameraSensor::test_pattern);
+    gg.options().camera_image = Box::new(move || image.clone());
+}
+
+/// Decode a PNG file into a grayscale image suitable for the camera sensor,
+/// scaled to the sensor's size.
+pub fn decode(content: &[u8]) -> Option<Vec<u8>> {
+    let mut decoder = Decoder::new(content);
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).ok()?;
+
+    let samples = info.color_type.samples();
+    let (width, height) = (info.width as usize, info.height as usize);
+    let mut image = Vec::with_capacity(CAMERA_WIDTH * CAMERA_HEIGHT);
+    for y in 0..CAMERA_HEIGHT {
+        for x in 0..CAMERA_WIDTH {
+            let offs = (y * height / CAMERA_HEIGHT) * info.line_size
+                + (x * width / CAMERA_WIDTH) * samples;
+            let pixel = &buf[offs..(offs + samples)];
+            // Ignore alpha, if present
+            let luma = match pixel {
+                [r, g, b, ..] => (*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000,
+                [l, ..] => *l as u32,
+                [] => 0,
+            };
+            image.push(luma as u8);
+        }
+    }
+    Some(image)
+}
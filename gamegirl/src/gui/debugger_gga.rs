@@ -5,12 +5,12 @@
 // obtain one at https://mozilla.org/MPL/2.0/.
 
 use core::{
-    components::debugger::Breakpoint,
-    gga::{addr::IME, GameGirlAdv},
+    components::cart_info::CartInfo,
+    gga::{addr::IME, cartridge::database::SaveKind, GameGirlAdv},
     numutil::NumExt,
 };
 
-use eframe::egui::{Context, Label, RichText, TextEdit, Ui};
+use eframe::egui::{Context, Label, RichText, Ui};
 
 use crate::{gui::App, Colour};
 
@@ -97,28 +97,7 @@ pub fn debugger(gg: &mut GameGirlAdv, ui: &mut Ui) {
 
 /// Window for configuring active breakpoints.
 pub fn breakpoints(gg: &mut GameGirlAdv, ui: &mut Ui) {
-    for bp in gg.debugger.breakpoints.iter_mut() {
-        ui.horizontal(|ui| {
-            ui.label("0x");
-            if ui
-                .add(TextEdit::singleline(&mut bp.value_text).desired_width(80.0))
-                .changed()
-            {
-                bp.value = u32::from_str_radix(&bp.value_text, 16).ok();
-            }
-            ui.checkbox(&mut bp.pc, "PC");
-            ui.checkbox(&mut bp.write, "Write");
-        });
-    }
-
-    ui.horizontal(|ui| {
-        if ui.button("Add").clicked() {
-            gg.debugger.breakpoints.push(Breakpoint::default());
-        }
-        if ui.button("Clear").clicked() {
-            gg.debugger.breakpoints.clear();
-        }
-    });
+    super::breakpoints(gg, ui, 80.0);
 }
 
 /// Memory viewer showing the entire GG's address space.
@@ -134,7 +113,30 @@ pub fn cart_info(gg: &mut GameGirlAdv, ui: &mut Ui) {
     }
     ui.label(format!("Reported Title: {}", gg.cart.title()));
     ui.label(format!("Reported Game Code: AGB-{}", gg.cart.game_code()));
-    ui.label(format!("Detected Save Type: {:?}", gg.cart.save_type));
+    super::cart_header(&CartInfo::gga(&gg.cart.rom), ui);
+    ui.separator();
+
+    let detection = &gg.cart.detection;
+    let save = detection.save;
+    ui.label(format!("Save Type: {:?} ({})", save.value, save.source));
+    if matches!(save.value, SaveKind::Flash64 | SaveKind::Flash128) {
+        let chip = detection.flash_chip.value;
+        let [manufacturer, device] = chip.id();
+        ui.label(format!(
+            "Flash Chip: {chip}, ID {manufacturer:02X} {device:02X} ({})",
+            detection.flash_chip.source
+        ));
+    }
+    ui.label(format!(
+        "Peripherals: {:?} ({})",
+        detection.peripherals.value, detection.peripherals.source
+    ));
+    ui.label(format!(
+        "ROM Mirroring: {} ({})",
+        detection.mirror.value, detection.mirror.source
+    ));
+    ui.separator();
+    ui.label(format!("Save State: {:?}", gg.cart.save_type));
 }
 
 /// Window showing status of the remote debugger.
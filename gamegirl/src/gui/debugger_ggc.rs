@@ -6,7 +6,6 @@
 
 use core::{
     common::System,
-    components::debugger::Breakpoint,
     ggc::{
         cpu::{inst, DReg},
         io::{ppu, ppu::Ppu},
@@ -102,29 +101,7 @@ pub fn debugger(gg: &mut GameGirl, ui: &mut Ui) {
 
 /// Window for configuring active breakpoints.
 pub fn breakpoints(gg: &mut GameGirl, ui: &mut Ui) {
-    let bps = &mut gg.debugger.breakpoints;
-    for bp in bps.iter_mut() {
-        ui.horizontal(|ui| {
-            ui.label("0x");
-            if ui
-                .add(TextEdit::singleline(&mut bp.value_text).desired_width(40.0))
-                .changed()
-            {
-                bp.value = u16::from_str_radix(&bp.value_text, 16).ok();
-            }
-            ui.checkbox(&mut bp.pc, "PC");
-            ui.checkbox(&mut bp.write, "Write");
-        });
-    }
-
-    ui.horizontal(|ui| {
-        if ui.button("Add").clicked() {
-            bps.push(Breakpoint::default());
-        }
-        if ui.button("Clear").clicked() {
-            bps.clear();
-        }
-    });
+    super::breakpoints(gg, ui, 40.0);
 }
 
 /// Memory viewer showing the entire GG's address space.
@@ -199,6 +176,8 @@ pub fn cart_info(gg: &mut GameGirl, ui: &mut Ui) {
         _ if gg.cart.supports_cgb() => ui.label("GB Colour compatibility: Supported"),
         _ => ui.label("GB Colour compatibility: Unsupported"),
     };
+    ui.separator();
+    super::cart_header(&gg.cart.info, ui);
 
     ui.separator();
     ui.label(format!("Current ROM0 bank: {}", gg.cart.rom0_bank));
@@ -0,0 +1,315 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use core::{
+    common::{System, SystemKind, SAMPLE_RATE},
+    components::filter::PostProcess,
+    Colour,
+};
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    thread,
+    time::Instant,
+};
+
+use eframe::{egui::Color32, epaint::ColorImage};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::gui::netplay::NetplayState;
+use crate::{
+    gui::{pacing::Pacing, rewind::Rewinding, screen::NdsLayout},
+    queue::{RingProducer, TripleWriter},
+};
+
+/// Minimum amount of audio to keep buffered, in samples. Also at least twice
+/// the amount the audio device asks for at once is kept.
+const MIN_AUDIO_LATENCY: usize = (SAMPLE_RATE as usize / 30) * 2;
+/// Maximum amount audio is sped up or slowed down by when syncing to the
+/// display, to keep the amount of buffered audio steady.
+const MAX_AUDIO_CORRECTION: f64 = 0.005;
+/// Time without a display refresh after which the GUI is considered stalled
+/// and the system is advanced without waiting for refreshes.
+#[cfg(not(target_arch = "wasm32"))]
+const STALL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// State deciding how the system is advanced, shared between the GUI and
+/// the emulation thread.
+/// When locking both this and the system, always lock this first.
+#[derive(Default)]
+pub struct Control {
+    /// Rewinder state.
+    pub rewinder: Rewinding,
+    /// Netplay session state.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub netplay: NetplayState,
+    /// Frames to run ahead of the real timeline, by system.
+    pub run_ahead: HashMap<SystemKind, usize>,
+    /// Time played since the GUI last took it.
+    pub played: Duration,
+    /// Error that stopped something during emulation, to show to the user.
+    pub error: Option<String>,
+    /// How the system is currently paced, decided by the GUI.
+    pub pacing: Pacing,
+    /// Frames produced since the GUI last took them.
+    pub frames: u32,
+    /// Frames to step while paused, requested with frame advance.
+    pub frame_advance: u32,
+    /// Post-processing applied to frames before they are shown.
+    pub post_process: PostProcess,
+    /// Arrangement of the NDS screens.
+    pub nds_layout: NdsLayout,
+    /// If the NDS screens trade places in the layout.
+    pub swap_screens: bool,
+}
+
+impl Control {
+    /// Advance the system by the given delta in seconds and return the
+    /// frame to show, if a new one was produced.
+    fn step(&mut self, gg: &mut System, delta: f32) -> Option<Vec<Colour>> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.netplay.session.is_some() {
+            if gg.options().running {
+                self.played += Duration::from_secs_f32(delta);
+            }
+            if let Err(err) = self.netplay.advance(gg) {
+                self.netplay.disconnect();
+                self.error = Some(err.to_string());
+            }
+            return gg.last_frame();
+        }
+
+        if self.frame_advance > 0 && !gg.options().running {
+            self.frame_advance -= 1;
+            return gg.advance_frame();
+        }
+
+        if self.rewinder.rewinding {
+            if let Some(state) = self.rewinder.rewind_buffer.lock().unwrap().pop() {
+                gg.load_raw_state_with_save(state);
+                gg.options().invert_audio_samples = true;
+                return gg.produce_frame();
+            }
+            self.rewinder.rewinding = false;
+            gg.options().invert_audio_samples = false;
+            return gg.last_frame();
+        }
+
+        if gg.options().running {
+            self.played += Duration::from_secs_f32(delta);
+        }
+        let frames = self.run_ahead.get(&gg.kind()).copied();
+        gg.advance_run_ahead(delta, frames.unwrap_or(0))
+    }
+}
+
+/// Runs the system, separately from the GUI: Frames are published to
+/// a triple buffer the GUI reads from, audio is pushed to a ring buffer
+/// played by the audio device.
+pub struct Emulation {
+    gg: Arc<Mutex<System>>,
+    control: Arc<Mutex<Control>>,
+    frames: TripleWriter<ColorImage>,
+    audio: RingProducer,
+    /// Display refreshes since the last frame, when syncing to the display.
+    refreshes: u32,
+    /// Resampler for audio, to play it at regular speed.
+    resampler: Resampler,
+}
+
+impl Emulation {
+    pub fn new(
+        gg: Arc<Mutex<System>>,
+        control: Arc<Mutex<Control>>,
+        frames: TripleWriter<ColorImage>,
+        audio: RingProducer,
+    ) -> Self {
+        Self {
+            gg,
+            control,
+            frames,
+            audio,
+            refreshes: 0,
+            resampler: Resampler::default(),
+        }
+    }
+
+    /// Advance the system by the given delta in seconds, or one of its
+    /// frames if `None`, publishing produced output. Returns the amount of
+    /// audio samples produced.
+    pub fn advance(&mut self, delta: Option<f32>) -> usize {
+        let mut control = self.control.lock().unwrap();
+        let mut gg = self.gg.lock().unwrap();
+        let frame_rate = gg.frame_rate();
+        let delta = delta.unwrap_or((1.0 / frame_rate) as f32);
+        if let Some(mut pixels) = control.step(&mut gg, delta) {
+            let mut size = gg.screen_size();
+            if gg.kind() == SystemKind::Nds {
+                pixels = control.nds_layout.arrange(pixels, control.swap_screens);
+                size = control.nds_layout.size(size);
+            }
+            let pixels = control.post_process.apply(pixels, size);
+            control.frames += 1;
+            self.frames.write(ColorImage {
+                size: control.post_process.filters.output_size(size),
+                pixels: unsafe { mem::transmute::<Vec<Colour>, Vec<Color32>>(pixels) },
+            });
+        }
+
+        let mut samples = gg.take_samples();
+        let mut ratio = 1.0 / gg.options().speed_multiplier as f64;
+        if let Pacing::Display {
+            every,
+            refresh_rate,
+        } = control.pacing
+        {
+            // The system runs as fast as the display instead of its
+            // own speed, stretch audio to match
+            let speed = refresh_rate / every as f64 / frame_rate;
+            ratio *= self.audio_correction() / speed;
+        }
+        samples = self.resampler.resample(&samples, ratio);
+        self.audio.push(&samples);
+        samples.len()
+    }
+
+    /// Called on every display refresh when syncing to the display.
+    pub fn refresh(&mut self, every: u32) {
+        self.refreshes += 1;
+        if self.refreshes >= every {
+            self.refreshes = 0;
+            self.advance(None);
+        }
+    }
+
+    /// Amount of audio to keep buffered, in samples.
+    fn audio_latency(&self) -> usize {
+        (self.audio.last_request() * 2)
+            .max(MIN_AUDIO_LATENCY)
+            .min(self.audio.capacity() / 2)
+    }
+
+    /// Factor to stretch audio by to move the amount of buffered audio
+    /// towards [Self::audio_latency].
+    fn audio_correction(&self) -> f64 {
+        let target = self.audio_latency() as f64;
+        let error = (target - self.audio.buffered() as f64) / target;
+        1.0 + (error * MAX_AUDIO_CORRECTION).clamp(-MAX_AUDIO_CORRECTION, MAX_AUDIO_CORRECTION)
+    }
+
+    /// Run the emulation thread. When syncing to the display, the system is
+    /// advanced on the display refreshes received from the GUI. Otherwise,
+    /// or if the GUI stalls, it is advanced whenever the audio device is
+    /// about to run out of samples if `audio_paced`, or by a timer if not.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run(mut self, refreshes: Receiver<()>, audio_paced: bool) {
+        let mut next = Instant::now();
+        let mut last_refresh = Instant::now();
+        loop {
+            let pacing = self.control.lock().unwrap().pacing;
+            if let Pacing::Display { every, .. } = pacing {
+                match refreshes.recv_timeout(Duration::from_millis(1)) {
+                    Ok(()) => {
+                        // Skip refreshes missed while the system was slower
+                        // than the display
+                        while refreshes.try_recv().is_ok() {}
+                        last_refresh = Instant::now();
+                        self.refresh(every);
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Timeout) if last_refresh.elapsed() < STALL_TIMEOUT => {
+                        continue
+                    }
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            } else {
+                while refreshes.try_recv().is_ok() {}
+            }
+
+            if audio_paced {
+                if self.audio.buffered() >= self.audio_latency() {
+                    thread::sleep(Duration::from_millis(1));
+                    continue;
+                }
+            } else {
+                let frame_rate = self.gg.lock().unwrap().frame_rate();
+                let slice = Duration::from_secs_f64(1.0 / frame_rate);
+                let now = Instant::now();
+                if next > now {
+                    thread::sleep(next - now);
+                }
+                // Do not try to catch up after a long stall
+                next = (next + slice).max(now - slice);
+            }
+
+            if self.advance(None) == 0 {
+                // Paused or waiting on something, try again a bit later
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+}
+
+/// Start the emulation thread.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn spawn(emulation: Emulation, refreshes: Receiver<()>, audio_paced: bool) {
+    thread::Builder::new()
+        .name("emulation".into())
+        // Loading states of the systems needs a lot of stack space
+        .stack_size(32 << 20)
+        .spawn(move || emulation.run(refreshes, audio_paced))
+        .unwrap();
+}
+
+/// Linear resampler for interleaved stereo audio. Keeps its position
+/// between calls, so that audio resampled in chunks does not crackle.
+#[derive(Default)]
+struct Resampler {
+    /// Position in the next input, in stereo frames.
+    /// Can be negative, in which case it is between the last frame of the
+    /// previous input and the first one of the next.
+    pos: f64,
+    /// Last frame of the previous input.
+    last: [f32; 2],
+}
+
+impl Resampler {
+    /// Resample the given samples, producing `ratio` times as many.
+    fn resample(&mut self, samples: &[f32], ratio: f64) -> Vec<f32> {
+        let len = samples.len() / 2;
+        if len == 0 {
+            return Vec::new();
+        }
+        let last = self.last;
+        let frame = |idx: isize| {
+            if idx < 0 {
+                last
+            } else {
+                [samples[idx as usize * 2], samples[idx as usize * 2 + 1]]
+            }
+        };
+
+        let mut out = Vec::with_capacity((len as f64 * ratio) as usize * 2 + 2);
+        while self.pos < (len - 1) as f64 {
+            let idx = self.pos.floor();
+            let t = (self.pos - idx) as f32;
+            let (a, b) = (frame(idx as isize), frame(idx as isize + 1));
+            out.push(a[0] + (b[0] - a[0]) * t);
+            out.push(a[1] + (b[1] - a[1]) * t);
+            self.pos += 1.0 / ratio;
+        }
+        self.pos -= len as f64;
+        self.last = frame(len as isize - 1);
+        out
+    }
+}
@@ -4,6 +4,7 @@
 // If a copy of the MPL2 was not distributed with this file, you can
 // obtain one at https://mozilla.org/MPL/2.0/.
 
+use core::components::firmware::Firmware;
 use std::{future::Future, path::PathBuf, sync::mpsc};
 
 use rfd::FileHandle;
@@ -21,8 +22,50 @@ pub struct File {
 /// Open a file dialog. This operation is async and returns immediately,
 /// sending a [Message] once the user has picked a file.
 pub fn open(sender: mpsc::Sender<Message>) {
+    pick(
+        sender,
+        "GameGirl games",
+        &["gb", "gbc", "gba", "elf", "mb"],
+        Message::FileOpen,
+    );
+}
+
+/// Open a file dialog for an image to be used as GGC camera input.
+/// Sends [Message::CameraImage] once the user has picked a file.
+pub fn open_camera_image(sender: mpsc::Sender<Message>) {
+    pick(sender, "PNG images", &["png"], Message::CameraImage);
+}
+
+/// Open a file dialog for the given firmware image.
+/// Sends [Message::Firmware] once the user has picked a file.
+pub fn open_firmware(sender: mpsc::Sender<Message>, firmware: Firmware) {
+    pick(sender, "Firmware images", &["bin", "rom"], move |file| {
+        Message::Firmware(firmware, file)
+    });
+}
+
+/// Open a dialog for the directory to store save states in.
+/// Sends [Message::StateDir] once the user has picked a directory.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_state_dir(sender: mpsc::Sender<Message>) {
+    let task = rfd::AsyncFileDialog::new().pick_folder();
+    execute(async move {
+        if let Some(dir) = task.await {
+            sender
+                .send(Message::StateDir(dir.path().to_path_buf()))
+                .ok();
+        }
+    });
+}
+
+fn pick(
+    sender: mpsc::Sender<Message>,
+    name: &str,
+    extensions: &[&str],
+    message: impl FnOnce(File) -> Message + Send + 'static,
+) {
     let task = rfd::AsyncFileDialog::new()
-        .add_filter("GameGirl games", &["gb", "gbc", "gba", "elf"])
+        .add_filter(name, extensions)
         .pick_file();
 
     execute(async move {
@@ -30,7 +73,7 @@ pub fn open(sender: mpsc::Sender<Message>) {
         if let Some(file) = file {
             let path = path(&file);
             let content = file.read().await;
-            sender.send(Message::FileOpen(File { content, path })).ok();
+            sender.send(message(File { content, path })).ok();
         }
     });
 }
@@ -25,32 +25,107 @@ pub(super) const HOTKEYS: &[(&str, HotkeyFn)] = &[
         pressed(a, p, |app| {
             let mut gg = app.gg.lock().unwrap();
             gg.options().running = !gg.options().running && gg.options().rom_loaded;
+            if gg.options().rom_loaded {
+                app.osd.push(if gg.options().running {
+                    "Resumed"
+                } else {
+                    "Paused"
+                });
+            }
         })
     }),
-    ("Save", |a, p| pressed(a, p, |app| app.save_game())),
+    ("Save", |a, p| pressed(a, p, |app| app.save_game_notify())),
     ("Fast Forward (Hold)", |app, pressed| {
         let mut gg = app.gg.lock().unwrap();
         if pressed {
             gg.options().speed_multiplier = app.state.options.fast_forward_hold_speed;
         } else {
-            gg.options().speed_multiplier = 1;
+            gg.options().speed_multiplier = 1.0;
         }
     }),
     ("Fast Forward (Toggle)", |a, p| {
         pressed(a, p, |app| {
             let mut gg = app.gg.lock().unwrap();
             app.fast_forward_toggled = !app.fast_forward_toggled;
+            app.slow_motion_toggled = false;
             if app.fast_forward_toggled {
                 gg.options().speed_multiplier = app.state.options.fast_forward_toggle_speed;
+                app.osd.push(format!(
+                    "Fast forward: {}x",
+                    app.state.options.fast_forward_toggle_speed
+                ));
             } else {
-                gg.options().speed_multiplier = 1;
+                gg.options().speed_multiplier = 1.0;
+                app.osd.push("Fast forward: Off");
             }
         });
     }),
     ("Rewind (Hold)", |app, pressed| {
-        app.rewinder.rewinding = pressed;
+        app.control.lock().unwrap().rewinder.rewinding = pressed;
         app.gg.lock().unwrap().options().invert_audio_samples = pressed;
     }),
+    ("Tilt Up (Hold)", |app, pressed| tilt(app, pressed, 1, -1.0)),
+    ("Tilt Down (Hold)", |app, pressed| {
+        tilt(app, pressed, 1, 1.0)
+    }),
+    ("Tilt Left (Hold)", |app, pressed| {
+        tilt(app, pressed, 0, -1.0)
+    }),
+    ("Tilt Right (Hold)", |app, pressed| {
+        tilt(app, pressed, 0, 1.0)
+    }),
+    ("Rotate Left (Hold)", |app, pressed| {
+        rotate(app, pressed, -1.0)
+    }),
+    ("Rotate Right (Hold)", |app, pressed| {
+        rotate(app, pressed, 1.0)
+    }),
+    ("Slow Motion (Hold)", |app, pressed| {
+        let mut gg = app.gg.lock().unwrap();
+        if pressed {
+            gg.options().speed_multiplier = app.state.options.slow_motion_hold_speed;
+        } else {
+            gg.options().speed_multiplier = 1.0;
+        }
+    }),
+    ("Slow Motion (Toggle)", |a, p| {
+        pressed(a, p, |app| {
+            let mut gg = app.gg.lock().unwrap();
+            app.slow_motion_toggled = !app.slow_motion_toggled;
+            app.fast_forward_toggled = false;
+            if app.slow_motion_toggled {
+                gg.options().speed_multiplier = app.state.options.slow_motion_toggle_speed;
+                app.osd.push(format!(
+                    "Slow motion: {}x",
+                    app.state.options.slow_motion_toggle_speed
+                ));
+            } else {
+                gg.options().speed_multiplier = 1.0;
+                app.osd.push("Slow motion: Off");
+            }
+        });
+    }),
+    ("Frame Advance", |a, p| {
+        pressed(a, p, |app| {
+            // Pauses first if running, then steps a frame on every press
+            let mut control = app.control.lock().unwrap();
+            let mut gg = app.gg.lock().unwrap();
+            if gg.options().running {
+                gg.options().running = false;
+            } else if gg.options().rom_loaded {
+                control.frame_advance += 1;
+            }
+        });
+    }),
+    ("Game Only (Fullscreen)", |a, p| {
+        pressed(a, p, |app| app.toggle_game_only())
+    }),
+    ("Swap Screens", |a, p| {
+        pressed(a, p, |app| {
+            let mut control = app.control.lock().unwrap();
+            control.swap_screens = !control.swap_screens;
+        })
+    }),
 ];
 
 fn pressed(app: &mut App, pressed: bool, inner: fn(&mut App)) {
@@ -59,6 +134,18 @@ fn pressed(app: &mut App, pressed: bool, inner: fn(&mut App)) {
     }
 }
 
+/// Tilt the cart on the given axis while a tilt hotkey is held.
+fn tilt(app: &mut App, pressed: bool, axis: usize, value: f32) {
+    app.tilt[axis] = if pressed { value } else { 0.0 };
+    app.gg.lock().unwrap().set_tilt(app.tilt[0], app.tilt[1]);
+}
+
+/// Rotate the cart while a rotation hotkey is held.
+fn rotate(app: &mut App, pressed: bool, value: f32) {
+    let rotation = if pressed { value } else { 0.0 };
+    app.gg.lock().unwrap().set_rotation(rotation);
+}
+
 /// Input configuration struct.
 #[derive(Deserialize, Serialize)]
 pub struct Input {
@@ -112,6 +199,7 @@ impl Input {
                 (Key::A, Button(L)),
                 (Key::S, Button(R)),
                 (Key::R, Hotkey(4)),
+                (Key::F11, Hotkey(16)),
             ]),
             pending: None,
         }
@@ -4,51 +4,75 @@
 // If a copy of the MPL2 was not distributed with this file, you can
 // obtain one at https://mozilla.org/MPL/2.0/.
 
+mod camera;
 mod debugger_gga;
 mod debugger_ggc;
+mod emulation;
 mod file_dialog;
 mod input;
+mod netplay;
 mod options;
+mod osd;
+mod pacing;
 mod rewind;
+mod rumble;
+mod savestates;
+mod screen;
 
-#[cfg(not(target_arch = "wasm32"))]
-use core::gga::remote_debugger::DebuggerStatus;
 use core::{
-    common::{BorrowedSystem, System},
+    common::{BorrowedSystem, Button, System},
+    components::{
+        cart_info::CartInfo,
+        debugger::{Breakpoint, Condition, Debuggable},
+        firmware::Firmware,
+        patch,
+        patch::PatchFormat,
+    },
     gga::GameGirlAdv,
     ggc::GameGirl,
 };
 #[cfg(not(target_arch = "wasm32"))]
+use core::{
+    gga::remote_debugger::DebuggerStatus,
+    netplay::{Netplay, NetplayError},
+};
+#[cfg(not(target_arch = "wasm32"))]
 use std::sync::RwLock;
 use std::{
     fs, mem,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{mpsc, Arc, Mutex},
-    time::Duration,
 };
 
 use eframe::{
     egui::{
-        self, util::History, vec2, widgets, Context, Event, ImageData, Layout, TextureFilter, Ui,
-        Vec2,
+        self, util::History, vec2, widgets, Color32, Context, Direction, DragValue, Event,
+        ImageData, Key, Layout, Response, RichText, ScrollArea, TextEdit, TextureFilter, Ui, Vec2,
     },
     epaint::{ColorImage, ImageDelta, TextureId},
     CreationContext, Frame, Storage, Theme,
 };
+pub(crate) use savestates::SLOTS;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    args::{Args, ConfigOverrides},
     gui::{
-        debugger_ggc::VisualDebugState, file_dialog::File, input::InputAction, options::Options,
-        rewind::Rewinding,
+        debugger_ggc::VisualDebugState,
+        emulation::{Control, Emulation},
+        file_dialog::File,
+        input::InputAction,
+        options::Options,
+        osd::Osd,
+        pacing::{Pacer, Pacing},
+        rumble::Rumble,
+        savestates::SaveStates,
+        screen::Scaling,
     },
+    queue::{self, RingProducer, TripleReader},
     Colour,
 };
 
-/// How long a frame takes, and how much the GG should be advanced
-/// each frame. TODO: This assumption only holds for 60hz devices!
-const FRAME_LEN: Duration = Duration::from_secs_f64(1.0 / 60.0);
-
 /// Total count of windows in GUI.
 const WINDOW_COUNT: usize = DBG_WINDOW_COUNT + APP_WINDOW_COUNT;
 
@@ -75,7 +99,7 @@ const DBG_WINDOWS: [(&str, DbgFn<GameGirl>, DbgFn<GameGirlAdv>); DBG_WINDOW_COUN
 /// Function signature for an app window
 type AppFn = fn(&mut App, &Context, &mut Ui);
 /// Count of GUI windows that take the App as a parameter.
-const APP_WINDOW_COUNT: usize = 5;
+const APP_WINDOW_COUNT: usize = 6;
 /// GUI windows that take the App as a parameter.
 const APP_WINDOWS: [(&str, AppFn); APP_WINDOW_COUNT] = [
     ("Options", options::options),
@@ -83,45 +107,108 @@ const APP_WINDOWS: [(&str, AppFn); APP_WINDOW_COUNT] = [
     ("VRAM", debugger_ggc::vram_viewer),
     ("Background Map", debugger_ggc::bg_map_viewer),
     ("Remote Debugger", debugger_gga::remote_debugger),
+    ("Netplay", netplay::netplay),
 ];
 
 /// Start the GUI. Since this is native, this call will never return.
+/// The system is run on a separate thread, pushing audio to the given buffer.
+/// If `audio_paced`, the buffer is played by an audio device, which then
+/// decides the speed of emulation unless it is synced to the display.
 #[cfg(not(target_arch = "wasm32"))]
-pub fn start(gg: Arc<Mutex<System>>) {
+pub fn start(gg: Arc<Mutex<System>>, audio: RingProducer, audio_paced: bool, args: Args) {
     let options = eframe::NativeOptions {
         transparent: true,
         default_theme: Theme::Dark,
         ..Default::default()
     };
-    eframe::run_native("gamegirl", options, Box::new(|ctx| make_app(ctx, gg)))
+    eframe::run_native(
+        "gamegirl",
+        options,
+        Box::new(move |ctx| {
+            let (mut app, emulation) = make_app(ctx, gg, audio, args);
+            let (sender, refreshes) = mpsc::channel();
+            app.refreshes = Some(sender);
+            emulation::spawn(emulation, refreshes, audio_paced);
+            app
+        }),
+    )
 }
 
 /// Start the GUI. Since this is WASM, this call will return.
+/// The system is run by the GUI, pushing audio to the given buffer.
 #[cfg(target_arch = "wasm32")]
-pub fn start(gg: Arc<Mutex<System>>, canvas_id: &str) -> Result<(), eframe::wasm_bindgen::JsValue> {
+pub fn start(
+    gg: Arc<Mutex<System>>,
+    audio: RingProducer,
+    canvas_id: &str,
+    args: Args,
+) -> Result<(), eframe::wasm_bindgen::JsValue> {
     let options = eframe::WebOptions {
         default_theme: Theme::Dark,
         ..Default::default()
     };
-    eframe::start_web(canvas_id, options, Box::new(|ctx| make_app(ctx, gg)))
+    eframe::start_web(
+        canvas_id,
+        options,
+        Box::new(|ctx| {
+            let (mut app, emulation) = make_app(ctx, gg, audio, args);
+            app.emulation = Some(emulation);
+            app
+        }),
+    )
 }
 
-fn make_app(ctx: &CreationContext<'_>, gg: Arc<Mutex<System>>) -> Box<App> {
+fn make_app(
+    ctx: &CreationContext<'_>,
+    gg: Arc<Mutex<System>>,
+    audio: RingProducer,
+    args: Args,
+) -> (Box<App>, Emulation) {
     let state: State = ctx
         .storage
         .and_then(|s| eframe::get_value(s, "gamegirl_data"))
         .unwrap_or_default();
     let texture = App::make_screen_texture(&ctx.egui_ctx, [160, 144], state.options.tex_filter);
+    let mut control = Control {
+        run_ahead: state.options.run_ahead.clone(),
+        ..Control::default()
+    };
+    control.post_process.filters = state.options.filters;
+    control.nds_layout = state.options.nds_layout;
+    let control = Arc::new(Mutex::new(control));
+    let (frame_writer, frames) = queue::triple_buffer();
+    let emulation = Emulation::new(gg.clone(), control.clone(), frame_writer, audio);
+
     let mut app = App {
         gg,
+        control,
+        frames,
+        #[cfg(target_arch = "wasm32")]
+        emulation: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        refreshes: None,
+        pacer: Pacer::default(),
         current_rom_path: None,
-        rewinder: Rewinding::default(),
+        save_path: None,
+        overrides: args.overrides,
+        pending_slot: args.slot,
+        save_states: SaveStates::default(),
         visual_debug: VisualDebugState::default(),
         #[cfg(not(target_arch = "wasm32"))]
         remote_dbg: Arc::new(RwLock::new(DebuggerStatus::NotActive)),
         fast_forward_toggled: false,
+        slow_motion_toggled: false,
+        game_only: false,
+        #[cfg(not(target_arch = "wasm32"))]
+        set_fullscreen: None,
+        tilt: [0.0; 2],
+        stick_tilt: [0.0; 2],
+        rumble: Rumble::default(),
+        osd: Osd::default(),
+        error: None,
 
         texture,
+        last_screen: None,
         window_states: [false; WINDOW_COUNT],
         message_channel: mpsc::channel(),
         frame_times: History::new(0..120, 2.0),
@@ -129,17 +216,157 @@ fn make_app(ctx: &CreationContext<'_>, gg: Arc<Mutex<System>>) -> Box<App> {
         state,
     };
     app.setup_rewind();
-    Box::new(app)
+    app.setup_camera();
+    app.rumble.setup(&mut app.gg.lock().unwrap());
+    app.osd.setup(&mut app.gg.lock().unwrap());
+
+    if args.fullscreen {
+        app.toggle_game_only();
+    }
+    if let Some(path) = args.rom {
+        match fs::read(&path) {
+            Ok(content) => app
+                .message_channel
+                .0
+                .send(Message::FileOpen(File {
+                    content,
+                    path: Some(path),
+                }))
+                .unwrap(),
+            Err(err) => app.error = Some(format!("Failed to read '{}': {err}", path.display())),
+        }
+    }
+    (Box::new(app), emulation)
+}
+
+/// Show information read from a cart's header, with verification results.
+fn cart_header(info: &CartInfo, ui: &mut Ui) {
+    ui.label(format!("Maker Code: {}", info.maker));
+    ui.label(format!("Version: {}", info.version));
+    for check in &info.checks {
+        let (mark, colour) = if check.passed {
+            ("✔", Colour::GREEN)
+        } else {
+            ("✖", Colour::RED)
+        };
+        ui.label(RichText::new(format!("{mark} {}  {}", check.kind, check.detail)).color(colour));
+    }
+}
+
+/// Breakpoint list of the breakpoint windows, followed by the log of
+/// tracepoint hits. `addr_width` is the width of address text fields.
+fn breakpoints<S: Debuggable>(gg: &mut S, ui: &mut Ui, addr_width: f32) {
+    let parse_addr = |text: &str| {
+        u32::from_str_radix(text, 16)
+            .ok()
+            .and_then(|addr| S::Ptr::try_from(addr).ok())
+    };
+    let dbg = gg.debugger_mut();
+    let mut remove = None;
+    for (idx, bp) in dbg.breakpoints.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label("0x");
+            if ui
+                .add(TextEdit::singleline(&mut bp.value_text).desired_width(addr_width))
+                .changed()
+            {
+                bp.value = parse_addr(&bp.value_text);
+            }
+            ui.label("to 0x");
+            if ui
+                .add(
+                    TextEdit::singleline(&mut bp.end_text)
+                        .desired_width(addr_width)
+                        .hint_text("end"),
+                )
+                .changed()
+            {
+                bp.end = parse_addr(&bp.end_text);
+            }
+            ui.checkbox(&mut bp.pc, "PC");
+            ui.checkbox(&mut bp.read, "Read");
+            ui.checkbox(&mut bp.write, "Write");
+
+            let colour = bp.condition_invalid().then_some(Colour::RED);
+            if ui
+                .add(
+                    TextEdit::singleline(&mut bp.condition_text)
+                        .desired_width(100.0)
+                        .hint_text("condition")
+                        .text_color_opt(colour),
+                )
+                .on_hover_text("Only trigger if the condition holds, like 'r0 == 5', '0x2000100:16 != 0' or 'value > 0x80'. The breakpoint is disabled while the condition is invalid.")
+                .changed()
+            {
+                bp.condition = Condition::parse(&bp.condition_text, S::REGISTERS);
+            }
+            ui.label("Ignore");
+            ui.add(DragValue::new(&mut bp.ignore_count));
+            ui.checkbox(&mut bp.log_only, "Log only")
+                .on_hover_text("Only log hits to the trace log, without stopping.");
+            ui.label(format!("Hits: {}", bp.hit_count));
+            if ui.small_button("✖").clicked() {
+                remove = Some(idx);
+            }
+        });
+    }
+    if let Some(idx) = remove {
+        dbg.breakpoints.remove(idx);
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Add").clicked() {
+            dbg.breakpoints.push(Breakpoint::default());
+        }
+        if ui.button("Clear").clicked() {
+            dbg.breakpoints.clear();
+        }
+    });
+    ui.separator();
+
+    ui.horizontal(|ui| {
+        ui.label("Trace log");
+        if ui.button("Clear").clicked() {
+            dbg.trace_log.clear();
+        }
+    });
+    ScrollArea::vertical()
+        .max_height(150.0)
+        .stick_to_bottom(true)
+        .show(ui, |ui| {
+            for message in &dbg.trace_log {
+                ui.monospace(message);
+            }
+        });
 }
 
 /// The App state.
 struct App {
     /// The GG currently running.
     gg: Arc<Mutex<System>>,
+    /// State deciding how the system is advanced, shared with emulation.
+    control: Arc<Mutex<Control>>,
+    /// Frames produced by the system.
+    frames: TripleReader<ColorImage>,
+    /// Emulation, which is run by the GUI on WASM, since there are no threads.
+    #[cfg(target_arch = "wasm32")]
+    emulation: Option<Emulation>,
+    /// Display refreshes sent to the emulation thread, for syncing to them.
+    #[cfg(not(target_arch = "wasm32"))]
+    refreshes: Option<mpsc::Sender<()>>,
+    /// Decides how the system is paced based on the display's refresh rate.
+    pacer: Pacer,
     /// The path to the ROM currently running, if any. Always None on WASM.
     current_rom_path: Option<PathBuf>,
-    /// Rewinder state.
-    rewinder: Rewinding,
+    /// The path the save data and save states of the running ROM are
+    /// stored by. Differs from the ROM's path when it was patched.
+    save_path: Option<PathBuf>,
+    /// Overrides for the system options, from the command line.
+    overrides: ConfigOverrides,
+    /// Save state slot to load once the next ROM is loaded.
+    pending_slot: Option<usize>,
+    /// Save state slots of the current ROM.
+    save_states: SaveStates,
     /// State for visual debugging tools.
     visual_debug: VisualDebugState,
     /// Remote debugger status.
@@ -147,9 +374,29 @@ struct App {
     remote_dbg: Arc<RwLock<DebuggerStatus>>,
     /// If the emulator is fast-forwarding using the toggle hotkey.
     fast_forward_toggled: bool,
+    /// If the emulator is in slow motion using the toggle hotkey.
+    slow_motion_toggled: bool,
+    /// If only the screen is shown, without navbar or other windows.
+    /// Borderless fullscreen on native.
+    game_only: bool,
+    /// Fullscreen state to set the window to on the next update.
+    #[cfg(not(target_arch = "wasm32"))]
+    set_fullscreen: Option<bool>,
+    /// Current tilt set using the keyboard, for carts with an accelerometer.
+    tilt: [f32; 2],
+    /// Tilt set using a gamepad's left stick at the last update.
+    stick_tilt: [f32; 2],
+    /// Gamepad force feedback for carts with a rumble motor.
+    rumble: Rumble,
+    /// On-screen display over the screen.
+    osd: Osd,
+    /// Error to show to the user, if any.
+    error: Option<String>,
 
     /// Texture for the GG's PPU output.
     texture: TextureId,
+    /// Last frame shown on the screen, used for save state thumbnails.
+    last_screen: Option<ColorImage>,
     /// Open/closed states of all windows.
     window_states: [bool; WINDOW_COUNT],
     /// Message channel for reacting to some async events, see [Message].
@@ -163,31 +410,101 @@ struct App {
 
 impl App {
     fn setup_rewind(&mut self) {
-        self.rewinder
-            .set_rw_buf_size(self.state.options.rewind_buffer_size);
-        let buffer = self.rewinder.rewind_buffer.clone();
+        let mut control = self.control.lock().unwrap();
+        control.rewinder.set_rw_buf_size(
+            self.state.options.rewind_buffer_size,
+            self.state.options.rewind_granularity,
+        );
+        let buffer = control.rewinder.rewind_buffer.clone();
         if self.state.options.enable_rewind {
             self.gg.lock().unwrap().options().frame_finished = Box::new(move |gg| {
                 // Kinda ugly duplication but it works ig?
                 match gg {
-                    BorrowedSystem::GGC(gg) if !gg.options.invert_audio_samples => {
-                        buffer.lock().unwrap().push(gg.save_state())
-                    }
-                    BorrowedSystem::GGA(gg) if !gg.options.invert_audio_samples => {
-                        buffer.lock().unwrap().push(gg.save_state())
-                    }
+                    BorrowedSystem::GGC(gg) if !gg.options.invert_audio_samples => buffer
+                        .lock()
+                        .unwrap()
+                        .frame_finished(|| gg.save_raw_state()),
+                    BorrowedSystem::GGA(gg) if !gg.options.invert_audio_samples => buffer
+                        .lock()
+                        .unwrap()
+                        .frame_finished(|| gg.save_raw_state()),
                     _ => (),
                 }
             });
+        } else {
+            self.gg.lock().unwrap().options().frame_finished = Box::new(|_| ());
         }
     }
 }
 
+impl App {
+    fn setup_camera(&mut self) {
+        let image = self
+            .state
+            .options
+            .camera_image
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|content| camera::decode(&content));
+        camera::set_image(&mut self.gg.lock().unwrap(), image);
+    }
+}
+
 impl eframe::App for App {
     fn update(&mut self, ctx: &Context, frame: &mut Frame) {
-        let size = self.update_gg(ctx, FRAME_LEN);
+        let size = self.update_gg(ctx);
+        self.dropped_files(ctx);
         self.process_messages();
+        self.rumble.update();
+        self.stick_tilt();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(fullscreen) = self.set_fullscreen.take() {
+            frame.set_fullscreen(fullscreen);
+        }
+
+        if self.game_only {
+            // A fixed scale makes no sense when filling the window
+            let scaling = match self.state.options.scaling {
+                Scaling::Fixed => Scaling::Integer,
+                scaling => scaling,
+            };
+            egui::CentralPanel::default()
+                .frame(egui::Frame::none().fill(Color32::BLACK))
+                .show(ctx, |ui| self.screen(ctx, ui, size, scaling));
+        } else {
+            self.windows(ctx, frame, size);
+        }
+
+        if let Some(error) = &self.error {
+            let mut open = true;
+            egui::Window::new("Error")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| ui.label(error));
+            if !open {
+                self.error = None;
+            }
+        }
+
+        // Immediately repaint, since the GG will have a new frame.
+        // egui will automatically bind the framerate to VSYNC.
+        ctx.request_repaint();
+    }
 
+    fn save(&mut self, storage: &mut dyn Storage) {
+        self.save_game();
+        eframe::set_value(storage, "gamegirl_data", &self.state);
+    }
+
+    fn max_size_points(&self) -> Vec2 {
+        vec2(4000.0, 4000.0)
+    }
+}
+
+impl App {
+    /// Paint the navbar, the screen's window and all other open windows.
+    fn windows(&mut self, ctx: &Context, frame: &mut Frame, size: [usize; 2]) {
         egui::TopBottomPanel::top("navbar").show(ctx, |ui| {
             ui.horizontal_wrapped(|ui| {
                 ui.visuals_mut().button_frame = false;
@@ -196,17 +513,12 @@ impl eframe::App for App {
             });
         });
 
+        let scaling = self.state.options.scaling;
+        let fixed_size = Scaling::Fixed.size(size, self.state.options.display_scale, Vec2::ZERO);
         egui::Window::new("GameGirl")
-            .resizable(false)
-            .show(ctx, |ui| {
-                ui.image(
-                    self.texture,
-                    [
-                        (size[0] * self.state.options.display_scale) as f32,
-                        (size[1] * self.state.options.display_scale) as f32,
-                    ],
-                );
-            });
+            .resizable(scaling != Scaling::Fixed)
+            .default_size(fixed_size)
+            .show(ctx, |ui| self.screen(ctx, ui, size, scaling));
 
         let mut states = self.window_states;
         for ((name, runner), state) in APP_WINDOWS
@@ -228,45 +540,86 @@ impl eframe::App for App {
                 _ => todo!(),
             };
         }
-
-        // Immediately repaint, since the GG will have a new frame.
-        // egui will automatically bind the framerate to VSYNC.
-        ctx.request_repaint();
     }
 
-    fn save(&mut self, storage: &mut dyn Storage) {
-        self.save_game();
-        eframe::set_value(storage, "gamegirl_data", &self.state);
+    /// Show the screen, scaled to the space available.
+    fn screen(&mut self, ctx: &Context, ui: &mut Ui, size: [usize; 2], scaling: Scaling) {
+        let scale = self.state.options.display_scale;
+        let response = if scaling == Scaling::Fixed {
+            ui.image(self.texture, scaling.size(size, scale, Vec2::ZERO))
+        } else {
+            let image_size = scaling.size(size, scale, ui.available_size());
+            ui.with_layout(Layout::centered_and_justified(Direction::TopDown), |ui| {
+                ui.image(self.texture, image_size)
+            })
+            .inner
+        };
+        self.mouse_tilt(ctx, &response);
+
+        let speed = self.gg.lock().unwrap().options().speed_multiplier;
+        let mut status = format!("{:.1} FPS", self.pacer.frame_rate());
+        if speed != 1.0 {
+            status += &format!(", {speed}x");
+        }
+        let now = ctx.input().time;
+        let painter = ui.painter_at(response.rect);
+        let options = self.state.options.osd;
+        self.osd
+            .paint(&painter, response.rect, now, options, &status);
     }
 
-    fn max_size_points(&self) -> Vec2 {
-        vec2(4000.0, 4000.0)
+    /// Toggle showing only the screen, in fullscreen.
+    fn toggle_game_only(&mut self) {
+        self.game_only = !self.game_only;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.set_fullscreen = Some(self.game_only);
+        }
     }
-}
 
-impl App {
-    /// Update the system's state.
+    /// Process inputs, then show the newest frame produced by the system.
     /// Returns screen dimensions.
-    fn update_gg(&mut self, ctx: &Context, advance_by: Duration) -> [usize; 2] {
-        let (frame, size) = self.get_gg_frame(ctx, advance_by);
-        if let Some(pixels) = frame {
-            let img = ImageDelta::full(
-                ImageData::Color(ColorImage { size, pixels }),
-                self.state.options.tex_filter,
-            );
+    fn update_gg(&mut self, ctx: &Context) -> [usize; 2] {
+        self.process_input(ctx);
+
+        {
+            let mut control = self.control.lock().unwrap();
+            self.pacer
+                .refresh(ctx.input().time, mem::take(&mut control.frames));
+            control.pacing = self.pacer.pacing(self.gg.lock().unwrap().frame_rate());
+            self.save_states.play_time += mem::take(&mut control.played);
+            if let Some(error) = control.error.take() {
+                self.error = Some(error);
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(refreshes) = &self.refreshes {
+            refreshes.send(()).ok();
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Some(emulation) = &mut self.emulation {
+            let pacing = self.control.lock().unwrap().pacing;
+            match pacing {
+                Pacing::Display { every, .. } => emulation.refresh(every),
+                // Do not try to catch up after the tab was in the background
+                Pacing::Audio => {
+                    emulation.advance(Some(ctx.input().unstable_dt.min(0.1)));
+                }
+            }
+        }
+
+        if let Some(image) = self.frames.read() {
+            let image = mem::take(image);
+            self.last_screen = Some(image.clone());
+            let img = ImageDelta::full(ImageData::Color(image), self.state.options.tex_filter);
             let manager = ctx.tex_manager();
             manager.write().set(self.texture, img);
         }
-        size
+        screen::screen_size(&self.gg.lock().unwrap(), self.state.options.nds_layout)
     }
 
-    /// Process keyboard inputs and return the GG's next frame, if one was
-    /// produced.
-    fn get_gg_frame(
-        &mut self,
-        ctx: &Context,
-        advance_by: Duration,
-    ) -> (Option<Vec<Colour>>, [usize; 2]) {
+    /// Process keyboard inputs.
+    fn process_input(&mut self, ctx: &Context) {
         for event in &ctx.input().events {
             if let Event::Key { key, pressed, .. } = event {
                 if let Some(action) = self.state.options.input.pending.take() {
@@ -274,10 +627,15 @@ impl App {
                     continue;
                 }
 
-                match self.state.options.input.get_key(*key) {
-                    Some(InputAction::Button(btn)) => {
-                        self.gg.lock().unwrap().set_button(btn, *pressed)
+                let action = self.state.options.input.get_key(*key);
+                if self.game_only && *key == Key::Escape && action.is_none() {
+                    if *pressed {
+                        self.toggle_game_only();
                     }
+                    continue;
+                }
+                match action {
+                    Some(InputAction::Button(btn)) => self.set_button(btn, *pressed),
                     Some(InputAction::Hotkey(idx)) => {
                         input::HOTKEYS[idx as usize].1(self, *pressed)
                     }
@@ -285,55 +643,262 @@ impl App {
                 }
             }
         }
+    }
 
-        let mut gg = self.gg.lock().unwrap();
-        let size = gg.screen_size();
-        if self.rewinder.rewinding {
-            if let Some(state) = self.rewinder.rewind_buffer.lock().unwrap().pop() {
-                gg.load_state(state);
-                gg.options().invert_audio_samples = true;
-                return (
-                    gg.produce_frame().map(|p| unsafe { mem::transmute(p) }),
-                    size,
-                );
-            } else {
-                self.rewinder.rewinding = false;
-                gg.options().invert_audio_samples = false;
+    /// Set a button on the joypad.
+    fn set_button(&mut self, btn: Button, pressed: bool) {
+        self.osd.set_button(btn, pressed);
+        // During netplay, inputs may only be applied by the session
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut control = self.control.lock().unwrap();
+            if control.netplay.session.is_some() {
+                control.netplay.set_button(btn, pressed);
+                return;
             }
-        } else {
-            gg.advance_delta(advance_by.as_secs_f32());
         }
-        (gg.last_frame().map(|p| unsafe { mem::transmute(p) }), size)
+        self.gg.lock().unwrap().set_button(btn, pressed);
+    }
+
+    /// Tilt the cart using the mouse while the primary button is held
+    /// over the screen, relative to the screen's center.
+    fn mouse_tilt(&mut self, ctx: &Context, screen: &Response) {
+        let input = ctx.input();
+        match screen.hover_pos() {
+            Some(pos) if input.pointer.primary_down() => {
+                let rel = (pos - screen.rect.center()) / (screen.rect.size() / 2.0);
+                self.gg.lock().unwrap().set_tilt(rel.x, rel.y);
+            }
+            _ if input.pointer.any_released() => {
+                self.gg.lock().unwrap().set_tilt(self.tilt[0], self.tilt[1]);
+            }
+            _ => (),
+        }
+    }
+
+    /// Tilt the cart using the left stick of a gamepad, when it moved.
+    /// Keyboard tilt applies again once the stick is centered.
+    fn stick_tilt(&mut self) {
+        let stick = self.rumble.left_stick();
+        if stick == self.stick_tilt {
+            return;
+        }
+        self.stick_tilt = stick;
+        let [x, y] = if stick == [0.0; 2] { self.tilt } else { stick };
+        self.gg.lock().unwrap().set_tilt(x, y);
     }
 
     /// Process all async messages that came in during this frame.
     fn process_messages(&mut self) {
-        while let Ok(Message::FileOpen(file)) = self.message_channel.1.try_recv() {
-            self.save_game();
-            self.gg.lock().unwrap().load_cart(
-                file.content,
-                file.path.clone(),
-                &self.state.options.gg,
-            );
+        while let Ok(message) = self.message_channel.1.try_recv() {
+            match message {
+                Message::FileOpen(file) => self.open_file(file),
+
+                Message::CameraImage(file) => {
+                    if let Some(image) = camera::decode(&file.content) {
+                        camera::set_image(&mut self.gg.lock().unwrap(), Some(image));
+                        self.state.options.camera_image = file.path;
+                    } else {
+                        log::error!("Failed to decode camera image!");
+                    }
+                }
+
+                Message::Firmware(firmware, file) => {
+                    if file.content.len() != firmware.size() {
+                        self.error = Some(format!(
+                            "{firmware} has the wrong size: expected {} bytes, got {}",
+                            firmware.size(),
+                            file.content.len()
+                        ));
+                    } else if let Some(path) = file.path {
+                        if firmware.identify(&file.content).is_none() {
+                            self.error = Some(format!(
+                                "{firmware} is not a known dump. It will be used anyway, but might not work."
+                            ));
+                        }
+                        self.state.options.gg.firmware.insert(firmware, path);
+                    }
+                }
 
-            self.current_rom_path = file.path.clone();
-            if let Some(path) = file.path {
-                if let Some(existing) = self.state.last_opened.iter().position(|p| *p == path) {
-                    self.state.last_opened.swap(0, existing);
-                } else {
-                    self.state.last_opened.insert(0, path);
-                    self.state.last_opened.truncate(10);
+                Message::StateDir(dir) => self.state.options.state_dir = Some(dir),
+
+                #[cfg(not(target_arch = "wasm32"))]
+                Message::Netplay(result) => {
+                    let mut control = self.control.lock().unwrap();
+                    if let Err(err) = control.netplay.started(result) {
+                        log::error!("Failed to start netplay: {err}");
+                        self.error = Some(err.to_string());
+                    }
                 }
             }
         }
     }
 
+    /// Open a file picked or dropped by the user: Patches are applied to the
+    /// current ROM, save states are loaded, anything else is loaded as a ROM.
+    fn open_file(&mut self, file: File) {
+        if PatchFormat::detect(&file.content).is_some() {
+            self.apply_patch(file);
+        } else if matches!(&file.path, Some(path) if savestates::is_state_file(path)) {
+            self.load_state_file(file);
+        } else {
+            self.load_rom(file);
+        }
+    }
+
+    /// Load a ROM, replacing the current one. Returns if it was loaded.
+    fn load_rom(&mut self, file: File) -> bool {
+        let save_path = file.path.clone();
+        self.load_rom_with_save(file, save_path)
+    }
+
+    /// Load a ROM, storing its save data and save states by the given path
+    /// instead of the ROM's. Returns if it was loaded.
+    fn load_rom_with_save(&mut self, file: File, save_path: Option<PathBuf>) -> bool {
+        self.save_game();
+        {
+            let mut control = self.control.lock().unwrap();
+            #[cfg(not(target_arch = "wasm32"))]
+            control.netplay.disconnect();
+            control.post_process.reset();
+        }
+        let mut config = self.state.options.gg.clone();
+        self.overrides.apply(&mut config);
+        let mut gg = self.gg.lock().unwrap();
+        let result = gg.load_cart(
+            file.content,
+            save_path.clone(),
+            self.state.options.system,
+            &config,
+        );
+        gg.set_light(self.state.options.light_level);
+        drop(gg);
+        if let Err(err) = result {
+            log::error!("Failed to load ROM: {err}");
+            self.error = Some(err.to_string());
+            return false;
+        }
+
+        self.setup_rewind();
+        self.save_states.open(
+            save_path.as_deref(),
+            self.state.options.state_dir.as_deref(),
+        );
+        self.last_screen = None;
+        self.current_rom_path = file.path.clone();
+        self.save_path = save_path;
+        if let Some(path) = file.path {
+            if let Some(existing) = self.state.last_opened.iter().position(|p| *p == path) {
+                self.state.last_opened.swap(0, existing);
+            } else {
+                self.state.last_opened.insert(0, path);
+                self.state.last_opened.truncate(10);
+            }
+        }
+
+        if let Some(slot) = self.pending_slot.take() {
+            let mut gg = self.gg.lock().unwrap();
+            let compress = self.state.options.gg.compress_savestates;
+            match self.save_states.load(slot, &mut gg, None, compress) {
+                Ok(true) => self
+                    .osd
+                    .push(format!("Loaded state from slot {}", slot + 1)),
+                Ok(false) => self.error = Some(format!("Slot {} is empty.", slot + 1)),
+                Err(err) => self.error = Some(format!("Failed to load slot {}: {err}", slot + 1)),
+            }
+        }
+        true
+    }
+
+    /// Apply a patch to the current ROM, loading it again.
+    /// The ROM is read from its file, so patches do not stack.
+    fn apply_patch(&mut self, patch: File) {
+        let path = match &self.current_rom_path {
+            Some(path) => path.clone(),
+            None => {
+                self.error = Some("Open a ROM from a file before applying a patch.".to_string());
+                return;
+            }
+        };
+        let result = fs::read(&path)
+            .map_err(|err| format!("Failed to read '{}': {err}", path.display()))
+            .and_then(|rom| patch::apply(&rom, &patch.content).map_err(|err| err.to_string()));
+        match result {
+            Ok(content) => {
+                let save_path = patched_path(&path, patch.path.as_deref());
+                let file = File {
+                    content,
+                    path: Some(path),
+                };
+                let loaded = self.load_rom_with_save(file, Some(save_path));
+                if loaded {
+                    let format = PatchFormat::detect(&patch.content).unwrap();
+                    self.osd.push(format!("Applied {format} patch"));
+                }
+            }
+            Err(err) => {
+                log::error!("Failed to apply patch: {err}");
+                self.error = Some(err);
+            }
+        }
+    }
+
+    /// Load a save state file that is not one of the current ROM's slots.
+    fn load_state_file(&mut self, file: File) {
+        let mut gg = self.gg.lock().unwrap();
+        if !gg.options().rom_loaded {
+            self.error = Some("Open the ROM the state is for before loading it.".to_string());
+            return;
+        }
+        let compress = self.state.options.gg.compress_savestates;
+        let size = screen::screen_size(&gg, self.state.options.nds_layout);
+        let screen = self.last_screen.as_ref().map(|s| (s, size));
+        match self
+            .save_states
+            .load_file(&file.content, &mut gg, screen, compress)
+        {
+            Ok(()) => self.osd.push("Loaded state from file"),
+            Err(err) => self.error = Some(format!("Failed to load the save state: {err}")),
+        }
+    }
+
+    /// Open files dropped onto the window.
+    fn dropped_files(&mut self, ctx: &Context) {
+        let dropped = mem::take(&mut ctx.input_mut().raw.dropped_files);
+        for file in dropped {
+            let content = match (file.bytes, &file.path) {
+                (Some(bytes), _) => bytes.to_vec(),
+                (None, Some(path)) => match fs::read(path) {
+                    Ok(content) => content,
+                    Err(err) => {
+                        self.error = Some(format!("Failed to read '{}': {err}", path.display()));
+                        continue;
+                    }
+                },
+                (None, None) => continue,
+            };
+            self.message_channel
+                .0
+                .send(Message::FileOpen(File {
+                    content,
+                    path: file.path,
+                }))
+                .ok();
+        }
+    }
+
     /// Save the system cart RAM, if a cart is loaded and it has RAM.
-    fn save_game(&self) {
-        self.gg
-            .lock()
-            .unwrap()
-            .save_game(self.current_rom_path.clone());
+    /// Returns if it was saved.
+    fn save_game(&self) -> bool {
+        self.gg.lock().unwrap().save_game(self.save_path.clone())
+    }
+
+    /// Save the cart RAM on the user's request, notifying them of it.
+    /// Automatic saves are silent, since they happen periodically.
+    fn save_game_notify(&mut self) {
+        if self.save_game() {
+            self.osd.push("Game saved");
+        }
     }
 
     /// Paint the navbar.
@@ -368,12 +933,11 @@ impl App {
             ui.separator();
 
             if ui.button("Save").clicked() {
-                self.save_game();
+                self.save_game_notify();
                 ui.close_menu();
             }
             if ui.button("Pause").clicked() {
-                let mut gg = self.gg.lock().unwrap();
-                gg.options().running = !gg.options().running && gg.options().rom_loaded;
+                input::HOTKEYS[2].1(self, true);
                 ui.close_menu();
             }
             if ui.button("Reset").clicked() {
@@ -381,6 +945,15 @@ impl App {
                 ui.close_menu();
             }
 
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                ui.separator();
+                if ui.button("Netplay").clicked() {
+                    self.window_states[9] = true;
+                    ui.close_menu();
+                }
+            }
+
             #[cfg(not(target_arch = "wasm32"))]
             {
                 ui.separator();
@@ -406,36 +979,21 @@ impl App {
             }
         });
 
-        ui.menu_button("Savestates", |ui| {
-            for (i, state) in self.rewinder.save_states.iter_mut().enumerate() {
-                if ui.button(format!("Save State {}", i + 1)).clicked() {
-                    *state = Some(self.gg.lock().unwrap().save_state());
-                    ui.close_menu();
-                }
-            }
-            ui.separator();
-
-            for (i, state) in self
-                .rewinder
-                .save_states
-                .iter()
-                .filter_map(|s| s.as_ref())
-                .enumerate()
-            {
-                if ui.button(format!("Load State {}", i + 1)).clicked() {
-                    let mut gg = self.gg.lock().unwrap();
-                    self.rewinder.before_last_ss_load = Some(gg.save_state());
-                    gg.load_state(state);
-                    ui.close_menu();
-                }
-            }
-        });
+        ui.menu_button("Savestates", |ui| savestates::menu(self, ui));
 
         ui.menu_button("Options", |ui| {
             if ui.button("Options").clicked() {
                 self.window_states[4] = true;
                 ui.close_menu();
             }
+            if ui
+                .button("Game only")
+                .on_hover_text("Show only the screen, in fullscreen. Press Escape to leave.")
+                .clicked()
+            {
+                self.toggle_game_only();
+                ui.close_menu();
+            }
             if ui.button("About").clicked() {
                 self.window_states[5] = true;
                 ui.close_menu();
@@ -451,6 +1009,23 @@ impl App {
                 self.frame_times.average().unwrap_or(0.0) * 1000.0
             ));
             ui.label("Frame time: ");
+            ui.separator();
+
+            ui.monospace(format!("{:.1}", self.pacer.frame_rate()));
+            ui.label("FPS: ");
+            ui.separator();
+
+            let refresh_rate = self
+                .pacer
+                .refresh_rate()
+                .map_or_else(|| "?".to_string(), |rate| format!("{rate:.2}"));
+            let pacing = self.control.lock().unwrap().pacing;
+            ui.label(format!("{pacing}, {refresh_rate} Hz")).on_hover_text(
+                match pacing {
+                    Pacing::Display { .. } => "One frame is emulated per display refresh, which is smoothest. Audio is stretched slightly to fit.",
+                    Pacing::Audio => "Emulation is paced by audio, since the display's refresh rate does not fit the system's. Some frames are shown longer than others.",
+                },
+            );
         });
     }
 
@@ -466,6 +1041,25 @@ impl App {
     }
 }
 
+/// Path to store the save data and save states of a patched ROM by, to keep
+/// them apart from the unpatched ROM's: The patch's file name, or the ROM's
+/// with ".patched" added, followed by the ROM's extension.
+fn patched_path(rom: &Path, patch: Option<&Path>) -> PathBuf {
+    let mut name = match patch.and_then(Path::file_name) {
+        Some(name) => name.to_os_string(),
+        None => {
+            let mut name = rom.file_stem().unwrap_or_default().to_os_string();
+            name.push(".patched");
+            name
+        }
+    };
+    if let Some(ext) = rom.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    patch.unwrap_or(rom).with_file_name(name)
+}
+
 /// State that is persisted on app reboot.
 #[derive(Default, Serialize, Deserialize)]
 pub struct State {
@@ -478,7 +1072,17 @@ pub struct State {
 
 /// A message that can be sent from some async context.
 pub enum Message {
-    /// A file picked by the user to be opend as a ROM, from the "Open ROM" file
-    /// picker dialog.
+    /// A file to be opened as a ROM, picked by the user in the "Open ROM" file
+    /// picker dialog, dropped onto the window or given on the command line.
+    /// Can also be a patch or save state to apply to the current ROM.
     FileOpen(File),
+    /// An image picked by the user to be used as GGC camera input.
+    CameraImage(File),
+    /// A firmware image picked by the user.
+    Firmware(Firmware, File),
+    /// A directory picked by the user to store save states in.
+    StateDir(PathBuf),
+    /// A netplay session that was hosted or joined.
+    #[cfg(not(target_arch = "wasm32"))]
+    Netplay(Result<Box<Netplay>, NetplayError>),
 }
@@ -0,0 +1,164 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+#[cfg(not(target_arch = "wasm32"))]
+use core::{
+    common::{Button, System},
+    netplay::{Netplay, NetplayError},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use eframe::egui::{Color32, Slider, TextEdit};
+use eframe::egui::{Context, Ui};
+
+use crate::gui::App;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::gui::Message;
+
+/// State of netplay in the GUI.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct NetplayState {
+    /// The current session, if any.
+    pub session: Option<Box<Netplay>>,
+    /// If currently hosting or connecting to another player.
+    connecting: bool,
+    /// Set to stop waiting for another player while hosting.
+    cancel_host: Arc<AtomicBool>,
+    /// Buttons currently held by the local player, as a bitmask.
+    buttons: u16,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl NetplayState {
+    /// Set a button of the local player.
+    pub fn set_button(&mut self, btn: Button, pressed: bool) {
+        let bit = 1 << btn as u16;
+        if pressed {
+            self.buttons |= bit;
+        } else {
+            self.buttons &= !bit;
+        }
+    }
+
+    /// Emulate the next frame of the session.
+    pub fn advance(&mut self, gg: &mut System) -> Result<(), NetplayError> {
+        match &mut self.session {
+            Some(session) => session.advance(gg, self.buttons),
+            None => Ok(()),
+        }
+    }
+
+    /// Handle a session being started or failing to start.
+    pub fn started(
+        &mut self,
+        result: Result<Box<Netplay>, NetplayError>,
+    ) -> Result<(), NetplayError> {
+        self.connecting = false;
+        match result {
+            Ok(session) => self.session = Some(session),
+            Err(NetplayError::Cancelled) => (),
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+
+    /// End the current session, if any.
+    pub fn disconnect(&mut self) {
+        self.session = None;
+    }
+}
+
+/// Show the netplay window.
+#[cfg(not(target_arch = "wasm32"))]
+pub(super) fn netplay(app: &mut App, _ctx: &Context, ui: &mut Ui) {
+    let opt = &mut app.state.options;
+    let mut control = app.control.lock().unwrap();
+    let state = &mut control.netplay;
+
+    if let Some(session) = &state.session {
+        ui.label(format!("Playing with {}.", session.peer()));
+        ui.label(format!("Frame: {}", session.frame()));
+        ui.label(format!(
+            "Predicted frames: {}, rollbacks: {}",
+            session.frames_ahead(),
+            session.rollbacks()
+        ));
+        if let Some(frame) = session.desync() {
+            ui.colored_label(
+                Color32::RED,
+                format!("Out of sync since frame {frame}! Restart the session to fix this."),
+            );
+        }
+        if ui.button("Disconnect").clicked() {
+            state.disconnect();
+        }
+        return;
+    }
+    if state.connecting {
+        ui.label(format!(
+            "Waiting for the other player... (Port {})",
+            opt.netplay_port
+        ));
+        if ui.button("Cancel").clicked() {
+            state.cancel_host.store(true, Ordering::Relaxed);
+        }
+        return;
+    }
+
+    ui.label("Both players need to have the same game loaded.");
+    ui.horizontal(|ui| {
+        ui.add(Slider::new(&mut opt.netplay_delay, 0..=8))
+            .on_hover_text("Frames your inputs are delayed by. Increase this if there are a lot of rollbacks, which happens on slow connections.");
+        ui.label("Input delay");
+    });
+    ui.separator();
+
+    ui.horizontal(|ui| {
+        ui.add(Slider::new(&mut opt.netplay_port, 1024..=u16::MAX));
+        ui.label("Port");
+    });
+    if ui.button("Host").clicked() {
+        let (gg, sender) = (app.gg.clone(), app.message_channel.0.clone());
+        let (port, delay) = (opt.netplay_port, opt.netplay_delay);
+        state.cancel_host = Arc::new(AtomicBool::new(false));
+        let cancel = state.cancel_host.clone();
+        std::thread::spawn(move || {
+            sender
+                .send(Message::Netplay(
+                    Netplay::host(port, delay, &gg, &cancel).map(Box::new),
+                ))
+                .ok();
+        });
+        state.connecting = true;
+    }
+    ui.separator();
+
+    ui.horizontal(|ui| {
+        ui.add(TextEdit::singleline(&mut opt.netplay_address).desired_width(150.0));
+        ui.label("Address");
+    });
+    if ui.button("Join").clicked() {
+        let (gg, sender) = (app.gg.clone(), app.message_channel.0.clone());
+        let (addr, delay) = (opt.netplay_address.clone(), opt.netplay_delay);
+        std::thread::spawn(move || {
+            sender
+                .send(Message::Netplay(
+                    Netplay::join(addr, delay, &gg).map(Box::new),
+                ))
+                .ok();
+        });
+        state.connecting = true;
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(super) fn netplay(_app: &mut App, _ctx: &Context, _ui: &mut Ui) {}
@@ -4,7 +4,22 @@
 // If a copy of the MPL2 was not distributed with this file, you can
 // obtain one at https://mozilla.org/MPL/2.0/.
 
-use core::common::{Button, CgbMode, SystemConfig};
+use core::{
+    common::{Button, CgbMode, System, SystemConfig, SystemKind},
+    components::{
+        filter::{Blending, Filters, Mask, Upscaler},
+        firmware::Firmware,
+    },
+    gga::{
+        cartridge::{
+            database::{CartOverride, Peripherals, SaveKind},
+            flash::FlashChip,
+        },
+        graphics::palette::ColourProfile,
+    },
+    ggc::model::GgcModel,
+};
+use std::{collections::HashMap, path::PathBuf};
 
 use eframe::{
     egui,
@@ -13,7 +28,10 @@ use eframe::{
 use serde::{Deserialize, Serialize};
 
 use crate::gui::{
+    camera, file_dialog,
     input::{Input, InputAction, HOTKEYS},
+    osd::OsdOptions,
+    screen::{NdsLayout, Scaling},
     App,
 };
 
@@ -24,20 +42,50 @@ pub struct Options {
     pub gg: SystemConfig,
     /// Input configuration.
     pub input: Input,
+    /// System to load ROMs on. Detected from the ROM if `None`.
+    pub system: Option<SystemKind>,
 
     /// Fast forward speed for the hold button.
-    pub fast_forward_hold_speed: usize,
+    pub fast_forward_hold_speed: f32,
     /// Fast forward speed for the toggle button.
-    pub fast_forward_toggle_speed: usize,
+    pub fast_forward_toggle_speed: f32,
+    /// Slow motion speed for the hold button.
+    pub slow_motion_hold_speed: f32,
+    /// Slow motion speed for the toggle button.
+    pub slow_motion_toggle_speed: f32,
     /// Enable rewinding.
     pub enable_rewind: bool,
+    /// Frames to run ahead of the real timeline, by system.
+    pub run_ahead: HashMap<SystemKind, usize>,
+    /// Directory to store save states in. Next to the ROM if `None`.
+    pub state_dir: Option<PathBuf>,
     /// Rewind buffer size (if enabled), in seconds.
     pub rewind_buffer_size: usize,
+    /// Amount of frames between states stored in the rewind buffer.
+    pub rewind_granularity: usize,
+    /// Image used as GGC camera input. Uses a test pattern if `None`.
+    pub camera_image: Option<PathBuf>,
+    /// Light level seen by GGA solar sensors.
+    pub light_level: u8,
+    /// Port to host netplay sessions on.
+    pub netplay_port: u16,
+    /// Address of the last netplay session joined.
+    pub netplay_address: String,
+    /// Frames local inputs are delayed by during netplay.
+    pub netplay_delay: u32,
 
     /// Scale of the GG display.
     pub display_scale: usize,
+    /// How the display is scaled to its window.
+    pub scaling: Scaling,
+    /// Arrangement of the NDS screens.
+    pub nds_layout: NdsLayout,
+    /// Post-processing applied to frames.
+    pub filters: Filters,
     /// Texture filter applied to the display.
     pub tex_filter: TextureFilter,
+    /// What the on-screen display shows.
+    pub osd: OsdOptions,
 }
 
 impl Default for Options {
@@ -45,12 +93,27 @@ impl Default for Options {
         Self {
             gg: Default::default(),
             input: Input::new(),
-            fast_forward_hold_speed: 2,
-            fast_forward_toggle_speed: 2,
+            system: None,
+            fast_forward_hold_speed: 2.0,
+            fast_forward_toggle_speed: 2.0,
+            slow_motion_hold_speed: 0.5,
+            slow_motion_toggle_speed: 0.5,
             enable_rewind: true,
-            rewind_buffer_size: 10,
+            run_ahead: HashMap::new(),
+            state_dir: None,
+            rewind_buffer_size: 60,
+            rewind_granularity: 1,
+            camera_image: None,
+            light_level: 0,
+            netplay_port: 17634,
+            netplay_address: "localhost:17634".to_string(),
+            netplay_delay: 1,
             display_scale: 2,
+            scaling: Scaling::Fixed,
+            nds_layout: NdsLayout::Vertical,
+            filters: Filters::default(),
             tex_filter: TextureFilter::Nearest,
+            osd: OsdOptions::default(),
         }
     }
 }
@@ -59,6 +122,16 @@ impl Default for Options {
 pub(super) fn options(app: &mut App, ctx: &Context, ui: &mut Ui) {
     let opt = &mut app.state.options;
     CollapsingHeader::new("Emulation").show(ui, |ui| {
+        ComboBox::from_label("System")
+            .selected_text(opt.system.map_or("Detect".to_string(), |kind| kind.to_string()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut opt.system, None, "Detect");
+                for kind in SystemKind::ALL {
+                    ui.selectable_value(&mut opt.system, Some(kind), kind.to_string());
+                }
+            })
+            .response
+            .on_hover_text("System to load ROMs on. 'Detect' picks it by the ROM's header and file extension.");
         ComboBox::from_label("GB Colour mode")
             .selected_text(format!("{:?}", opt.gg.mode))
             .show_ui(ui, |ui| {
@@ -66,33 +139,142 @@ pub(super) fn options(app: &mut App, ctx: &Context, ui: &mut Ui) {
                 ui.selectable_value(&mut opt.gg.mode, CgbMode::Prefer, "Prefer");
                 ui.selectable_value(&mut opt.gg.mode, CgbMode::Never, "Never");
             });
+        ComboBox::from_label("GB model")
+            .selected_text(opt.gg.dmg_model.to_string())
+            .show_ui(ui, |ui| {
+                for model in GgcModel::DMG_MODELS {
+                    ui.selectable_value(&mut opt.gg.dmg_model, model, model.to_string());
+                }
+            });
+        ComboBox::from_label("GBC model")
+            .selected_text(opt.gg.cgb_model.to_string())
+            .show_ui(ui, |ui| {
+                for model in GgcModel::CGB_MODELS {
+                    ui.selectable_value(&mut opt.gg.cgb_model, model, model.to_string());
+                }
+            });
+        ui.checkbox(&mut opt.gg.ggc_bootix, "GGC: Use Bootix")
+            .on_hover_text("Run the bundled Bootix boot ROM on DMG models if no boot ROM is set under Firmware, instead of skipping it.\nLoad a ROM to apply changes to this.");
         ui.checkbox(&mut opt.gg.cached_interpreter, "GGA: Enable Cached Interpreter")
             .on_hover_text("Enables caching in the interpreter. Speeds up emulation at the cost of RAM usage. Also breaks breakpoints.");
+        ui.checkbox(&mut opt.gg.force_gga_rtc, "GGA: Force RTC")
+            .on_hover_text("Always connect a real-time clock to GGA carts, even if the game is not known to have one.\nLoad a ROM to apply changes to this.");
+        ui.checkbox(&mut opt.gg.gga_hle_bios, "GGA: Use HLE BIOS")
+            .on_hover_text("Emulate BIOS calls directly instead of running a BIOS dump. Skips the boot animation.\nAlways used if no GGA BIOS is set under Firmware.\nLoad a ROM to apply changes to this.");
+        ui.horizontal(|ui| {
+            if ui.button("Pick image").clicked() {
+                file_dialog::open_camera_image(app.message_channel.0.clone());
+            }
+            if ui.button("Use test pattern").clicked() {
+                opt.camera_image = None;
+                camera::set_image(&mut app.gg.lock().unwrap(), None);
+            }
+            ui.label("GB Camera input");
+        });
+        ui.horizontal(|ui| {
+            if ui.add(Slider::new(&mut opt.light_level, 0..=255)).changed() {
+                app.gg.lock().unwrap().set_light(opt.light_level);
+            }
+            ui.label("GGA: Solar sensor light level");
+        });
         ui.separator();
 
         ui.horizontal(|ui| {
-            ui.add(Slider::new(&mut opt.fast_forward_hold_speed, 2..=10));
+            ui.add(Slider::new(&mut opt.fast_forward_hold_speed, 1.1..=10.0));
             ui.label("Fast forward speed (Hold)");
         });
         ui.horizontal(|ui| {
-            ui.add(Slider::new(&mut opt.fast_forward_toggle_speed, 2..=10));
+            ui.add(Slider::new(&mut opt.fast_forward_toggle_speed, 1.1..=10.0));
             ui.label("Fast forward speed (Toggle)");
         });
+        ui.horizontal(|ui| {
+            ui.add(Slider::new(&mut opt.slow_motion_hold_speed, 0.1..=0.9));
+            ui.label("Slow motion speed (Hold)");
+        });
+        ui.horizontal(|ui| {
+            ui.add(Slider::new(&mut opt.slow_motion_toggle_speed, 0.1..=0.9));
+            ui.label("Slow motion speed (Toggle)");
+        });
         ui.separator();
 
-        ui.checkbox(&mut opt.gg.compress_savestates, "Compress save states/rewinding")
-            .on_hover_text("Heavily reduces rewinding memory usage, but requires a lot of performance.\nLoad a ROM to apply changes to this.");
+        CollapsingHeader::new("Run-ahead").show(ui, |ui| {
+            for kind in SystemKind::ALL {
+                ui.horizontal(|ui| {
+                    let frames = opt.run_ahead.entry(kind).or_insert(0);
+                    if ui.add(Slider::new(frames, 0..=4)).changed() {
+                        app.control.lock().unwrap().run_ahead.insert(kind, *frames);
+                    }
+                    ui.label(format!("{kind} frames"));
+                });
+            }
+        })
+        .header_response
+        .on_hover_text("Emulate frames ahead of the real timeline to hide input lag of the game itself.\nSet this to the amount of frames the game takes to react to inputs; higher values cause visual glitches.\nUses more performance for every frame.");
+        ui.separator();
+
+        ui.checkbox(&mut opt.gg.compress_savestates, "Compress save states")
+            .on_hover_text("Heavily reduces the size of save states stored on disk, but makes saving slower.");
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.horizontal(|ui| {
+            if ui.button("Pick directory").clicked() {
+                file_dialog::open_state_dir(app.message_channel.0.clone());
+            }
+            if ui.button("Next to ROM").clicked() {
+                opt.state_dir = None;
+            }
+            match &opt.state_dir {
+                Some(dir) => ui.label(format!("Save states in '{}'", dir.display())),
+                None => ui.label("Save states next to ROM"),
+            }
+            .on_hover_text("Load a ROM to apply changes to this.");
+        });
         ui.checkbox(&mut opt.enable_rewind, "Enable Rewinding");
         if opt.enable_rewind {
             ui.horizontal(|ui| {
-                ui.add(Slider::new(&mut opt.rewind_buffer_size, 1..=60))
-                    .on_hover_text(format!(
-                        "Uses about ~{}MB of RAM",
-                        opt.rewind_buffer_size + opt.rewind_buffer_size * (!opt.gg.compress_savestates as usize * 4),
-                    ));
+                ui.add(Slider::new(&mut opt.rewind_buffer_size, 1..=600));
                 ui.label("Rewind time in seconds");
             });
+            ui.horizontal(|ui| {
+                ui.add(Slider::new(&mut opt.rewind_granularity, 1..=10))
+                    .on_hover_text("Store a state every N frames. Higher values use less memory and performance, but make rewinding coarser.");
+                ui.label("Rewind granularity in frames");
+            });
+            let usage = app.control.lock().unwrap().rewinder.rewind_buffer.lock().unwrap().memory_usage();
+            ui.label(format!("Currently using {:.1}MB of RAM", usage as f32 / 1_000_000.0));
+            ui.label("Load a ROM to apply changes to rewinding.");
+        }
+    });
+
+    let game_code = match &*app.gg.lock().unwrap() {
+        System::GGA(gg) if gg.options.rom_loaded => Some(gg.cart.game_code()),
+        _ => None,
+    };
+    CollapsingHeader::new("GGA Cart Overrides").show(ui, |ui| match game_code {
+        Some(code) => cart_overrides(ui, opt, code),
+        None => {
+            ui.label("Load a GGA ROM to set overrides for it.");
+        }
+    });
+
+    #[cfg(not(target_arch = "wasm32"))]
+    CollapsingHeader::new("Firmware").show(ui, |ui| {
+        for firmware in Firmware::ALL {
+            ui.horizontal(|ui| {
+                if ui.button("Pick").clicked() {
+                    file_dialog::open_firmware(app.message_channel.0.clone(), firmware);
+                }
+                if ui.button("Clear").clicked() {
+                    opt.gg.firmware.remove(&firmware);
+                }
+                let path = opt
+                    .gg
+                    .firmware
+                    .get(&firmware)
+                    .map_or("Not set".to_string(), |p| p.display().to_string());
+                ui.label(format!("{firmware}: {path}"));
+            });
         }
+        ui.label("Load a ROM to apply changes to this.");
     });
 
     CollapsingHeader::new("Graphics").show(ui, |ui| {
@@ -101,6 +283,19 @@ pub(super) fn options(app: &mut App, ctx: &Context, ui: &mut Ui) {
             "Enable GBC colour correction",
         )
         .on_hover_text("Adjust colours to be more accurate to a real GBC screen.");
+        ComboBox::from_label("GBA colour correction")
+            .selected_text(opt.gg.gga_colour_correction.to_string())
+            .show_ui(ui, |ui| {
+                for profile in ColourProfile::ALL {
+                    ui.selectable_value(
+                        &mut opt.gg.gga_colour_correction,
+                        profile,
+                        profile.to_string(),
+                    );
+                }
+            })
+            .response
+            .on_hover_text("Adjust colours to look like the screen of a real GBA model.\nLoad a ROM to apply changes to this.");
 
         ComboBox::from_label("Texture filter")
             .selected_text(format!("{:?}", opt.tex_filter))
@@ -109,10 +304,74 @@ pub(super) fn options(app: &mut App, ctx: &Context, ui: &mut Ui) {
                 ui.selectable_value(&mut opt.tex_filter, TextureFilter::Linear, "Linear");
             });
 
-        ui.horizontal(|ui| {
-            ui.add(Slider::new(&mut opt.display_scale, 1..=10));
-            ui.label("Screen scale");
+        ComboBox::from_label("Screen scaling")
+            .selected_text(opt.scaling.to_string())
+            .show_ui(ui, |ui| {
+                for scaling in Scaling::ALL {
+                    ui.selectable_value(&mut opt.scaling, scaling, scaling.to_string());
+                }
+            })
+            .response
+            .on_hover_text("How the screen is scaled to fit its window. Anything but 'Fixed' makes the window resizable.");
+        ui.add_enabled_ui(opt.scaling == Scaling::Fixed, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(Slider::new(&mut opt.display_scale, 1..=10));
+                ui.label("Screen scale");
+            });
         });
+        ComboBox::from_label("NDS screen layout")
+            .selected_text(opt.nds_layout.to_string())
+            .show_ui(ui, |ui| {
+                for layout in NdsLayout::ALL {
+                    if ui
+                        .selectable_value(&mut opt.nds_layout, layout, layout.to_string())
+                        .changed()
+                    {
+                        app.control.lock().unwrap().nds_layout = layout;
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Use the 'Swap Screens' hotkey to switch which screen is shown first, or at all.");
+        ui.separator();
+
+        let filters = opt.filters;
+        ComboBox::from_label("Upscaler")
+            .selected_text(opt.filters.upscaler.to_string())
+            .show_ui(ui, |ui| {
+                for upscaler in Upscaler::ALL {
+                    ui.selectable_value(&mut opt.filters.upscaler, upscaler, upscaler.to_string());
+                }
+            })
+            .response
+            .on_hover_text("Smooth edges of pixel art while keeping it sharp.");
+        ComboBox::from_label("Screen mask")
+            .selected_text(opt.filters.mask.to_string())
+            .show_ui(ui, |ui| {
+                for mask in Mask::ALL {
+                    ui.selectable_value(&mut opt.filters.mask, mask, mask.to_string());
+                }
+            })
+            .response
+            .on_hover_text("Darken the gaps between pixels or lines, like the original screens.");
+        ComboBox::from_label("Frame blending")
+            .selected_text(opt.filters.blending.to_string())
+            .show_ui(ui, |ui| {
+                for blending in Blending::ALL {
+                    ui.selectable_value(&mut opt.filters.blending, blending, blending.to_string());
+                }
+            })
+            .response
+            .on_hover_text("Blend consecutive frames, which is needed for effects that flicker objects to make them look transparent.\nGhosting imitates the slow original LCDs.");
+        if opt.filters != filters {
+            app.control.lock().unwrap().post_process.filters = opt.filters;
+        }
+        ui.separator();
+
+        ui.checkbox(&mut opt.osd.messages, "Show notifications")
+            .on_hover_text("Show messages over the screen, like when a state is saved or a breakpoint is hit.");
+        ui.checkbox(&mut opt.osd.status, "Show FPS and speed");
+        ui.checkbox(&mut opt.osd.inputs, "Show pressed buttons");
 
         CollapsingHeader::new("egui Configuration").show(ui, |ui| ctx.settings_ui(ui));
     });
@@ -148,6 +407,76 @@ pub(super) fn options(app: &mut App, ctx: &Context, ui: &mut Ui) {
     ui.label("Some options require a restart to apply.");
 }
 
+/// Edit the user overrides for the GGA cart with the given game code.
+fn cart_overrides(ui: &mut Ui, opt: &mut Options, code: String) {
+    ui.label(format!("Overrides for game code {code}."));
+    let entry = opt.gg.gga_overrides.entry(code.clone()).or_default();
+
+    ComboBox::from_label("Save type")
+        .selected_text(
+            entry
+                .save
+                .map_or("Detect".to_string(), |s| format!("{s:?}")),
+        )
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut entry.save, None, "Detect");
+            for kind in SaveKind::ALL {
+                ui.selectable_value(&mut entry.save, Some(kind), format!("{kind:?}"));
+            }
+        });
+
+    ComboBox::from_label("Flash chip")
+        .selected_text(
+            entry
+                .flash_chip
+                .map_or("Detect".to_string(), |c| c.to_string()),
+        )
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut entry.flash_chip, None, "Detect");
+            for chip in FlashChip::ALL {
+                ui.selectable_value(&mut entry.flash_chip, Some(chip), chip.to_string());
+            }
+        });
+
+    let mut override_peripherals = entry.peripherals.is_some();
+    ui.checkbox(&mut override_peripherals, "Override peripherals");
+    if !override_peripherals {
+        entry.peripherals = None;
+    } else {
+        let peripherals = entry.peripherals.get_or_insert_with(Peripherals::empty);
+        ui.horizontal(|ui| {
+            for (flag, name) in [
+                (Peripherals::RTC, "RTC"),
+                (Peripherals::SOLAR, "Solar"),
+                (Peripherals::GYRO, "Gyro"),
+                (Peripherals::RUMBLE, "Rumble"),
+                (Peripherals::TILT, "Tilt"),
+            ] {
+                let mut enabled = peripherals.contains(flag);
+                ui.checkbox(&mut enabled, name);
+                peripherals.set(flag, enabled);
+            }
+        });
+    }
+
+    ComboBox::from_label("ROM mirroring")
+        .selected_text(match entry.mirror {
+            None => "Detect",
+            Some(true) => "On",
+            Some(false) => "Off",
+        })
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut entry.mirror, None, "Detect");
+            ui.selectable_value(&mut entry.mirror, Some(true), "On");
+            ui.selectable_value(&mut entry.mirror, Some(false), "Off");
+        });
+
+    if *entry == CartOverride::default() {
+        opt.gg.gga_overrides.remove(&code);
+    }
+    ui.label("Load the ROM again to apply changes.");
+}
+
 fn input_section(
     ui: &mut Ui,
     name: &'static str,
@@ -0,0 +1,157 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use core::common::{Button, System};
+use std::{
+    collections::VecDeque,
+    mem,
+    sync::{Arc, Mutex},
+};
+
+use eframe::egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// Time a message is shown for, in seconds.
+const MESSAGE_DURATION: f64 = 3.0;
+/// Time at the end of [MESSAGE_DURATION] during which a message fades out.
+const FADE_DURATION: f64 = 0.5;
+/// Maximum amount of messages shown at once; older ones are dropped.
+const MAX_MESSAGES: usize = 5;
+/// Distance of the OSD to the edges of the screen.
+const MARGIN: f32 = 6.0;
+/// Padding around text, inside of its background.
+const PADDING: f32 = 3.0;
+/// Size of OSD text.
+const FONT_SIZE: f32 = 13.0;
+
+/// What the OSD shows.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct OsdOptions {
+    /// Timed messages, like save states being saved.
+    pub messages: bool,
+    /// Frame rate and emulation speed.
+    pub status: bool,
+    /// Buttons currently held.
+    pub inputs: bool,
+}
+
+impl Default for OsdOptions {
+    fn default() -> Self {
+        Self {
+            messages: true,
+            status: false,
+            inputs: false,
+        }
+    }
+}
+
+/// A message on the OSD.
+struct Notice {
+    text: String,
+    /// Time the message was first shown, `None` if it wasn't yet.
+    shown_at: Option<f64>,
+}
+
+/// On-screen display, drawn over the screen. Shows timed messages pushed by
+/// the GUI and the system, along with optional status and input display.
+#[derive(Default)]
+pub struct Osd {
+    /// Messages sent by the system, which might be on another thread.
+    incoming: Arc<Mutex<Vec<String>>>,
+    /// Messages currently shown, oldest first.
+    messages: VecDeque<Notice>,
+    /// Buttons currently held, indexed by [Button] discriminant.
+    held: [bool; Button::BUTTONS.len()],
+}
+
+impl Osd {
+    /// Make the system send its notifications to the OSD.
+    pub fn setup(&self, gg: &mut System) {
+        let incoming = self.incoming.clone();
+        gg.options().notification = Box::new(move |text| incoming.lock().unwrap().push(text));
+    }
+
+    /// Show a message.
+    pub fn push(&mut self, text: impl Into<String>) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Notice {
+            text: text.into(),
+            shown_at: None,
+        });
+    }
+
+    /// Set a button's state for the input display.
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        self.held[button as usize] = pressed;
+    }
+
+    /// Paint the OSD over the screen at the given rect. `status` is shown
+    /// in the top right corner if enabled.
+    pub fn paint(
+        &mut self,
+        painter: &Painter,
+        rect: Rect,
+        now: f64,
+        options: OsdOptions,
+        status: &str,
+    ) {
+        let incoming = mem::take(&mut *self.incoming.lock().unwrap());
+        for text in incoming {
+            self.push(text);
+        }
+        if !options.messages {
+            self.messages.clear();
+        }
+        self.messages
+            .retain(|msg| !matches!(msg.shown_at, Some(time) if now - time >= MESSAGE_DURATION));
+
+        let mut pos = rect.left_bottom() + Vec2::new(MARGIN, -MARGIN);
+        for msg in self.messages.iter_mut().rev() {
+            let age = now - *msg.shown_at.get_or_insert(now);
+            let alpha = ((MESSAGE_DURATION - age) / FADE_DURATION).min(1.0) as f32;
+            let size = text(painter, pos, Align2::LEFT_BOTTOM, &msg.text, alpha);
+            pos.y -= size.y + PADDING;
+        }
+
+        if options.status {
+            let pos = rect.right_top() + Vec2::new(-MARGIN, MARGIN);
+            text(painter, pos, Align2::RIGHT_TOP, status, 1.0);
+        }
+
+        if options.inputs {
+            let held = Button::BUTTONS
+                .iter()
+                .filter(|btn| self.held[**btn as usize])
+                .map(|btn| format!("{btn:?}"))
+                .collect::<Vec<_>>();
+            if !held.is_empty() {
+                let pos = rect.right_bottom() + Vec2::new(-MARGIN, -MARGIN);
+                text(painter, pos, Align2::RIGHT_BOTTOM, &held.join(" "), 1.0);
+            }
+        }
+    }
+}
+
+/// Paint text on a translucent background, anchored at the given position.
+/// Returns the size of the background.
+fn text(painter: &Painter, pos: Pos2, anchor: Align2, text: &str, alpha: f32) -> Vec2 {
+    let galley = painter.layout_no_wrap(
+        text.to_string(),
+        FontId::proportional(FONT_SIZE),
+        Color32::WHITE.linear_multiply(alpha),
+    );
+    let size = galley.size() + Vec2::splat(PADDING * 2.0);
+    let rect = anchor.anchor_rect(Rect::from_min_size(pos, size));
+    painter.rect_filled(
+        rect,
+        PADDING,
+        Color32::from_black_alpha(160).linear_multiply(alpha),
+    );
+    painter.galley(rect.min + Vec2::splat(PADDING), galley);
+    size
+}
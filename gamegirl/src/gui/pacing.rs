@@ -0,0 +1,120 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::{Display, Formatter};
+
+use eframe::egui::util::History;
+
+/// Maximum difference between the rate frames can be shown at on the display
+/// and the system's frame rate, relative to the latter, for syncing to the
+/// display. Audio has to be resampled by this much to keep up.
+const MAX_DEVIATION: f64 = 0.01;
+/// Amount of refreshes needed before the display's refresh rate is trusted.
+const MIN_REFRESHES: usize = 60;
+/// Maximum difference of a refresh interval to the median, relative to it,
+/// for it to not be considered a stall.
+const STALL_DEVIATION: f32 = 0.1;
+
+/// How the emulation thread decides when to advance the system.
+#[derive(Copy, Clone, Default, PartialEq)]
+pub enum Pacing {
+    /// Emulate exactly one frame every `every` display refreshes. Used when
+    /// the display refreshes at (a multiple of) about the system's frame rate,
+    /// which gives perfectly smooth motion. Audio is resampled to fit.
+    Display { every: u32, refresh_rate: f64 },
+    /// Emulate whenever the audio device is about to run out of samples,
+    /// or using a timer if there is no audio device. Frames are shown on the
+    /// next refresh, so some will be shown for longer than others.
+    #[default]
+    Audio,
+}
+
+impl Display for Pacing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pacing::Display { every: 1, .. } => write!(f, "Display sync"),
+            Pacing::Display { every, .. } => write!(f, "Display sync ({every}x)"),
+            Pacing::Audio => write!(f, "Audio sync"),
+        }
+    }
+}
+
+/// Measures the display's refresh rate and the rate frames are produced at,
+/// to decide on the [Pacing] to use.
+pub struct Pacer {
+    /// Time between the last display refreshes.
+    refreshes: History<f32>,
+    /// Amount of frames produced by the system at each refresh.
+    frames: History<f32>,
+    /// Time of the last refresh.
+    last_refresh: Option<f64>,
+}
+
+impl Pacer {
+    /// Register a display refresh at the given time, along with the amount of
+    /// frames the system produced since the last one.
+    pub fn refresh(&mut self, now: f64, frames: u32) {
+        if let Some(last) = self.last_refresh {
+            self.refreshes.add(now, (now - last) as f32);
+        }
+        self.last_refresh = Some(now);
+        self.frames.add(now, frames as f32);
+    }
+
+    /// The display's refresh rate, if it was stable for long enough to be
+    /// measured. Refreshes that were delayed by the GUI stalling are ignored.
+    pub fn refresh_rate(&self) -> Option<f64> {
+        if self.refreshes.len() < MIN_REFRESHES {
+            return None;
+        }
+        let mut intervals = self.refreshes.values().collect::<Vec<_>>();
+        intervals.sort_by(|a, b| a.total_cmp(b));
+        let median = intervals[intervals.len() / 2];
+        let stable = intervals
+            .iter()
+            .filter(|i| (*i - median).abs() <= median * STALL_DEVIATION)
+            .collect::<Vec<_>>();
+        // Not vsynced if the intervals are all over the place
+        if stable.len() < intervals.len() * 9 / 10 {
+            return None;
+        }
+        let average = stable.iter().copied().sum::<f32>() / stable.len() as f32;
+        Some(1.0 / average as f64)
+    }
+
+    /// Decide on the pacing to use for a system with the given frame rate.
+    pub fn pacing(&self, frame_rate: f64) -> Pacing {
+        let refresh_rate = match self.refresh_rate() {
+            Some(rate) => rate,
+            None => return Pacing::Audio,
+        };
+        let every = (refresh_rate / frame_rate).round().max(1.0);
+        let deviation = (refresh_rate / every - frame_rate).abs() / frame_rate;
+        if deviation <= MAX_DEVIATION {
+            Pacing::Display {
+                every: every as u32,
+                refresh_rate,
+            }
+        } else {
+            Pacing::Audio
+        }
+    }
+
+    /// Frames produced by the system per second, recently.
+    pub fn frame_rate(&self) -> f32 {
+        self.frames.bandwidth().unwrap_or(0.0)
+    }
+}
+
+impl Default for Pacer {
+    fn default() -> Self {
+        Self {
+            refreshes: History::new(0..240, 4.0),
+            frames: History::new(0..240, 1.0),
+            last_refresh: None,
+        }
+    }
+}
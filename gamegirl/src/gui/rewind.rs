@@ -5,17 +5,15 @@
 // obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::{
-    iter,
+    collections::VecDeque,
     sync::{Arc, Mutex},
 };
 
+/// Amount of entries between keyframes in the rewind buffer.
+const KEYFRAME_INTERVAL: usize = 60;
+
 /// Struct for storing rewind state.
 pub struct Rewinding {
-    /// Save states that the user can store/load at any time.
-    pub save_states: [Option<Vec<u8>>; 10],
-    /// Save state created before the last load, to allow the user
-    /// to undo a load.
-    pub before_last_ss_load: Option<Vec<u8>>,
     /// Rewind buffer.
     pub rewind_buffer: Arc<Mutex<RWBuffer>>,
     /// If the emulation is currently rewinding.
@@ -29,61 +27,281 @@ pub struct Rewinding {
 }
 
 impl Rewinding {
-    /// Set the size of the rewind buffer in seconds.
-    pub fn set_rw_buf_size(&mut self, secs: usize) {
-        *self.rewind_buffer.lock().unwrap() = RWBuffer::new(secs);
+    /// Set the size of the rewind buffer in seconds, and the amount of frames
+    /// between each stored state.
+    pub fn set_rw_buf_size(&mut self, secs: usize, granularity: usize) {
+        *self.rewind_buffer.lock().unwrap() = RWBuffer::new(secs, granularity);
     }
 }
 
 impl Default for Rewinding {
     fn default() -> Self {
         Self {
-            save_states: [None, None, None, None, None, None, None, None, None, None],
-            before_last_ss_load: None,
-            rewind_buffer: Arc::new(Mutex::new(RWBuffer::new(60 * 10))),
+            rewind_buffer: Arc::new(Mutex::new(RWBuffer::new(10, 1))),
             rewinding: false,
         }
     }
 }
 
-/// Rewind buffer. Implemented as a simple LILO buffer on top of a `Vec`.
+/// Rewind buffer. Only the newest state is kept as-is; all older states
+/// are stored as the difference to the state after them (XORed, then
+/// run-length encoded), since only little of the system changes between
+/// frames.
+/// Every [KEYFRAME_INTERVAL] entries, as well as when the state size changes,
+/// a full (still run-length encoded) keyframe is stored instead, which does
+/// not depend on any newer entries.
 pub struct RWBuffer {
-    vec: Vec<Vec<u8>>,
-    cur_idx: usize,
-    stop_idx: usize,
+    /// Older states, oldest first.
+    entries: VecDeque<Entry>,
+    /// The newest state, uncompressed.
+    newest: Option<Vec<u8>>,
+    /// If [Self::newest] was already returned by [Self::pop], in which case
+    /// the next pop steps back to the state before it.
+    newest_popped: bool,
+    /// Maximum amount of entries.
+    capacity: usize,
+    /// Amount of frames between stored states.
+    granularity: usize,
+    /// Frames since the last stored state.
+    frames: usize,
 }
 
 impl RWBuffer {
-    /// Pop a state off the buffer. Will return None if the buffer is empty.
+    /// Pop a state off the buffer, newest first. Will return None if the
+    /// buffer is empty.
     pub fn pop(&mut self) -> Option<&[u8]> {
-        if self.cur_idx == self.stop_idx {
-            return None;
+        if self.newest_popped {
+            let entry = self.entries.pop_back()?;
+            let state = self.newest.as_mut()?;
+            if entry.keyframe {
+                *state = rle::decode(&entry.data);
+            } else {
+                rle::decode_xor(&entry.data, state);
+            }
+        }
+        self.newest_popped = true;
+        self.newest.as_deref()
+    }
+
+    /// Call at the end of every frame. Pushes the state returned by
+    /// the given function if enough frames passed since the last one.
+    pub fn frame_finished(&mut self, state: impl FnOnce() -> Vec<u8>) {
+        self.frames += 1;
+        if self.frames >= self.granularity {
+            self.frames = 0;
+            self.push(state());
         }
-        if self.cur_idx == 0 {
-            self.cur_idx = self.vec.len() - 1;
-        } else {
-            self.cur_idx -= 1;
-        };
-        Some(&self.vec[self.cur_idx])
     }
 
     /// Push a new state to the buffer.
     pub fn push(&mut self, val: Vec<u8>) {
-        if self.cur_idx == self.vec.len() - 1 {
-            self.cur_idx = 0;
+        self.newest_popped = false;
+        let old = match self.newest.replace(val) {
+            Some(old) => old,
+            None => return,
+        };
+        let new = self.newest.as_ref().unwrap();
+
+        let since_keyframe = self
+            .entries
+            .iter()
+            .rev()
+            .take_while(|e| !e.keyframe)
+            .count();
+        let entry = if since_keyframe >= KEYFRAME_INTERVAL || old.len() != new.len() {
+            Entry {
+                keyframe: true,
+                data: rle::encode(old.iter().copied()),
+            }
         } else {
-            self.cur_idx += 1;
+            Entry {
+                keyframe: false,
+                data: rle::encode(old.iter().zip(new).map(|(a, b)| a ^ b)),
+            }
+        };
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
         }
-        self.stop_idx = self.cur_idx + 1;
-        self.vec[self.cur_idx] = val;
+        self.entries.push_back(entry);
     }
 
-    /// Create a new buffer with the given seconds of rewind storage.
-    fn new(secs: usize) -> Self {
+    /// Size of all stored states in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.entries.iter().map(|e| e.data.len()).sum::<usize>()
+            + self.newest.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Create a new buffer with the given seconds of rewind storage,
+    /// storing a state every `granularity` frames.
+    fn new(secs: usize, granularity: usize) -> Self {
+        let granularity = granularity.max(1);
+        let capacity = (60 * secs / granularity).max(1);
         Self {
-            vec: iter::repeat(vec![]).take(60 * secs).collect(),
-            cur_idx: 0,
-            stop_idx: 0,
+            entries: VecDeque::with_capacity(capacity),
+            newest: None,
+            newest_popped: false,
+            capacity,
+            granularity,
+            frames: 0,
+        }
+    }
+}
+
+/// A state in the rewind buffer.
+struct Entry {
+    /// If this is a full state, instead of a difference to the
+    /// next newer state.
+    keyframe: bool,
+    /// Run-length encoded data.
+    data: Vec<u8>,
+}
+
+/// A simple run-length encoding optimized for data with long runs of zeroes,
+/// like XORed states or mostly empty RAM.
+/// The encoded data is a sequence of (zero run length, literal length,
+/// literal bytes), with lengths encoded as LEB128.
+mod rle {
+    /// Encode the given bytes.
+    pub fn encode(data: impl Iterator<Item = u8>) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut literal = Vec::new();
+        let mut data = data.peekable();
+        while data.peek().is_some() {
+            let mut zeroes = 0;
+            while data.next_if_eq(&0).is_some() {
+                zeroes += 1;
+            }
+            literal.clear();
+            while let Some(byte) = data.next_if(|b| *b != 0) {
+                literal.push(byte);
+            }
+
+            write_len(&mut out, zeroes);
+            write_len(&mut out, literal.len());
+            out.extend_from_slice(&literal);
+        }
+        out
+    }
+
+    /// Decode the given data.
+    pub fn decode(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut data = data.iter().copied();
+        while let Some(zeroes) = read_len(&mut data) {
+            out.resize(out.len() + zeroes, 0);
+            let literal = read_len(&mut data).unwrap_or(0);
+            out.extend(data.by_ref().take(literal));
+        }
+        out
+    }
+
+    /// Decode the given data, XORing it onto the given buffer.
+    pub fn decode_xor(data: &[u8], buf: &mut [u8]) {
+        let mut data = data.iter().copied();
+        let mut idx = 0;
+        while let Some(zeroes) = read_len(&mut data) {
+            idx += zeroes;
+            let literal = read_len(&mut data).unwrap_or(0);
+            for (dst, src) in buf[idx..].iter_mut().zip(data.by_ref().take(literal)) {
+                *dst ^= src;
+            }
+            idx += literal;
+        }
+    }
+
+    fn write_len(out: &mut Vec<u8>, mut len: usize) {
+        loop {
+            let byte = (len & 0x7F) as u8;
+            len >>= 7;
+            if len == 0 {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    fn read_len(data: &mut impl Iterator<Item = u8>) -> Option<usize> {
+        let mut len = 0;
+        let mut shift = 0;
+        loop {
+            let byte = data.next()?;
+            len |= ((byte & 0x7F) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Some(len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gui::rewind::{rle, RWBuffer, KEYFRAME_INTERVAL};
+
+    #[test]
+    fn rle_round_trip() {
+        let data = [0, 0, 0, 1, 2, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 4];
+        let encoded = rle::encode(data.iter().copied());
+        assert_eq!(rle::decode(&encoded), data);
+
+        let long = vec![0; 1000];
+        let encoded = rle::encode(long.iter().copied());
+        assert_eq!(rle::decode(&encoded), long);
+        assert!(rle::decode(&rle::encode([].into_iter())).is_empty());
+    }
+
+    #[test]
+    fn rle_decode_xor() {
+        let old = [1, 2, 3, 4, 5, 6];
+        let new = [1, 0, 3, 4, 7, 6];
+        let diff = rle::encode(old.iter().zip(&new).map(|(a, b)| a ^ b));
+        let mut buf = new;
+        rle::decode_xor(&diff, &mut buf);
+        assert_eq!(buf, old);
+    }
+
+    #[test]
+    fn pop_newest_first() {
+        let mut buffer = RWBuffer::new(10, 1);
+        assert!(buffer.pop().is_none());
+        for i in 0..5 {
+            buffer.push(vec![i, 0, i * 2]);
+        }
+        for i in (0..5).rev() {
+            assert_eq!(buffer.pop(), Some(&[i, 0, i * 2][..]));
+        }
+        assert!(buffer.pop().is_none());
+    }
+
+    #[test]
+    fn pop_across_keyframes() {
+        let mut buffer = RWBuffer::new(10, 1);
+        let count = KEYFRAME_INTERVAL * 2 + 5;
+        for i in 0..count {
+            // Change the size at some point, which also forces a keyframe
+            let len = if i < 10 { 2 } else { 3 };
+            buffer.push(vec![i as u8; len]);
+        }
+        for i in (0..count).rev() {
+            let len = if i < 10 { 2 } else { 3 };
+            assert_eq!(buffer.pop(), Some(&vec![i as u8; len][..]));
+        }
+    }
+
+    #[test]
+    fn push_after_pop() {
+        let mut buffer = RWBuffer::new(10, 1);
+        for i in 0..3 {
+            buffer.push(vec![i]);
         }
+        assert_eq!(buffer.pop(), Some(&[2][..]));
+        assert_eq!(buffer.pop(), Some(&[1][..]));
+        buffer.push(vec![5]);
+        assert_eq!(buffer.pop(), Some(&[5][..]));
+        assert_eq!(buffer.pop(), Some(&[1][..]));
+        assert_eq!(buffer.pop(), Some(&[0][..]));
+        assert!(buffer.pop().is_none());
     }
 }
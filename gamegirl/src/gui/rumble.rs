@@ -0,0 +1,112 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use core::common::System;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use gilrs::{
+    ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder},
+    Axis, Gilrs,
+};
+
+/// Strength of the force feedback effect.
+const MAGNITUDE: u16 = 0xC000;
+/// Stick deflection below which a stick is considered centered.
+const DEAD_ZONE: f32 = 0.15;
+
+/// Forwards the cartridge's rumble motor to force feedback
+/// on all connected gamepads. Also reads their sticks for tilting the
+/// cart, since it owns the gamepad context.
+pub struct Rumble {
+    /// Gamepad context, `None` if it failed to initialize.
+    gilrs: Option<Gilrs>,
+    /// Rumble state set by the system.
+    active: Arc<AtomicBool>,
+    /// Rumble state at the last update.
+    rumbling: bool,
+    /// Effect currently playing, if any.
+    effect: Option<Effect>,
+}
+
+impl Rumble {
+    /// Register the rumble callback on the given system.
+    pub fn setup(&self, gg: &mut System) {
+        let active = self.active.clone();
+        gg.options().rumble = Box::new(move |on| active.store(on, Ordering::Relaxed));
+    }
+
+    /// Start or stop force feedback, if the rumble state changed.
+    /// Should be called once per frame.
+    pub fn update(&mut self) {
+        let gilrs = match &mut self.gilrs {
+            Some(gilrs) => gilrs,
+            None => return,
+        };
+        // Keep the list of connected gamepads up to date
+        while gilrs.next_event().is_some() {}
+
+        let active = self.active.load(Ordering::Relaxed);
+        if active == self.rumbling {
+            return;
+        }
+        self.rumbling = active;
+
+        if active {
+            let gamepads = gilrs
+                .gamepads()
+                .filter(|(_, pad)| pad.is_ff_supported())
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
+            let effect = EffectBuilder::new()
+                .add_effect(BaseEffect {
+                    kind: BaseEffectType::Strong {
+                        magnitude: MAGNITUDE,
+                    },
+                    ..Default::default()
+                })
+                .gamepads(&gamepads)
+                .finish(gilrs)
+                .and_then(|effect| effect.play().map(|_| effect));
+            match effect {
+                Ok(effect) => self.effect = Some(effect),
+                Err(err) => log::warn!("Failed to start rumble: {err}"),
+            }
+        } else if let Some(effect) = self.effect.take() {
+            effect.stop().ok();
+        }
+    }
+}
+
+impl Rumble {
+    /// Position of the left stick of the first gamepad that has it moved,
+    /// with up being negative Y. Zero if none do.
+    /// Call after [Self::update], which processes gamepad events.
+    pub fn left_stick(&self) -> [f32; 2] {
+        let gilrs = match &self.gilrs {
+            Some(gilrs) => gilrs,
+            None => return [0.0; 2],
+        };
+        gilrs
+            .gamepads()
+            .map(|(_, pad)| [pad.value(Axis::LeftStickX), -pad.value(Axis::LeftStickY)])
+            .find(|stick| stick.iter().any(|axis| axis.abs() > DEAD_ZONE))
+            .unwrap_or([0.0; 2])
+    }
+}
+
+impl Default for Rumble {
+    fn default() -> Self {
+        Self {
+            gilrs: Gilrs::new().ok(),
+            active: Arc::new(AtomicBool::new(false)),
+            rumbling: false,
+            effect: None,
+        }
+    }
+}
@@ -0,0 +1,416 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use core::{
+    common::{self, InvalidState, System},
+    Colour,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use eframe::{
+    egui::{
+        vec2, Button, Color32, ColorImage, Context, Grid, ImageData, TextureFilter, TextureId, Ui,
+    },
+    epaint::ImageDelta,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::gui::{screen, App};
+
+/// Amount of save state slots.
+pub const SLOTS: usize = 10;
+/// Maximum size of thumbnails shown in the slot browser.
+const THUMBNAIL_SIZE: [f32; 2] = [96.0, 72.0];
+
+/// Save state slots for the current ROM. If the ROM was loaded from a path,
+/// they are stored on disk as `game.gb.ss1`, `game.gb.ss2` etc., either next
+/// to the ROM or in the configured state directory, together with the states
+/// needed to undo the last load/save. The full file name is used, since ROMs
+/// for different systems often share the same name.
+#[derive(Default)]
+pub struct SaveStates {
+    /// The slots.
+    slots: [Option<Slot>; SLOTS],
+    /// State created before the last load, to allow the user
+    /// to undo a load.
+    undo_load: Option<Slot>,
+    /// Slot overwritten by the last save, to allow the user to undo a save.
+    undo_save: Option<UndoSave>,
+    /// Base path of the state files; the extension is added to it.
+    /// `None` if states are only kept in memory.
+    base: Option<PathBuf>,
+    /// Time the current game was played for. Restored when loading a state.
+    pub play_time: Duration,
+    /// Textures for slot thumbnails.
+    textures: [Option<TextureId>; SLOTS],
+    /// If the thumbnail textures need to be uploaded again.
+    textures_dirty: bool,
+}
+
+impl SaveStates {
+    /// Load the slots of the ROM at the given path, storing them in `dir`
+    /// if given, otherwise next to the ROM.
+    pub fn open(&mut self, rom: Option<&Path>, dir: Option<&Path>) {
+        self.base = rom.and_then(|rom| {
+            let name = rom.file_name()?;
+            Some(dir.or_else(|| rom.parent())?.join(name))
+        });
+        for idx in 0..SLOTS {
+            self.slots[idx] = self.read(&format!("ss{}", idx + 1));
+        }
+        self.undo_load = self.read("ss-undo-load");
+        self.undo_save = self.read("ss-undo-save");
+        self.play_time = Duration::ZERO;
+        self.textures_dirty = true;
+    }
+
+    /// Save the system's current state to the given slot.
+    pub fn save(
+        &mut self,
+        idx: usize,
+        gg: &mut System,
+        screen: Option<(&ColorImage, [usize; 2])>,
+        compress: bool,
+    ) {
+        let slot = Slot::new(gg, screen, self.play_time);
+        self.write(&format!("ss{}", idx + 1), Some(&slot), compress);
+        let previous = self.slots[idx].replace(slot);
+        self.undo_save = Some(UndoSave {
+            slot: idx,
+            previous,
+        });
+        self.write("ss-undo-save", self.undo_save.as_ref(), compress);
+        self.textures_dirty = true;
+    }
+
+    /// Load the state in the given slot, if there is one.
+    /// Returns if there was a state to load, or an error if it is invalid,
+    /// in which case the system is left unchanged.
+    pub fn load(
+        &mut self,
+        idx: usize,
+        gg: &mut System,
+        screen: Option<(&ColorImage, [usize; 2])>,
+        compress: bool,
+    ) -> Result<bool, InvalidState> {
+        match self.slots[idx].take() {
+            Some(slot) => {
+                let result = self.load_slot(&slot, gg, screen, compress);
+                self.slots[idx] = Some(slot);
+                result.map(|_| true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Load a state from the contents of a state file, which might be for
+    /// another ROM or stored elsewhere.
+    pub fn load_file(
+        &mut self,
+        content: &[u8],
+        gg: &mut System,
+        screen: Option<(&ColorImage, [usize; 2])>,
+        compress: bool,
+    ) -> Result<(), InvalidState> {
+        let slot = common::try_deserialize::<Slot>(content).ok_or(InvalidState)?;
+        self.load_slot(&slot, gg, screen, compress)
+    }
+
+    /// Load the given state, keeping the current one to allow undoing.
+    fn load_slot(
+        &mut self,
+        slot: &Slot,
+        gg: &mut System,
+        screen: Option<(&ColorImage, [usize; 2])>,
+        compress: bool,
+    ) -> Result<(), InvalidState> {
+        let before = Slot::new(gg, screen, self.play_time);
+        gg.try_load_raw_state(&slot.state)?;
+        self.play_time = slot.play_time;
+        self.write("ss-undo-load", Some(&before), compress);
+        self.undo_load = Some(before);
+        Ok(())
+    }
+
+    /// Load the state from before the last load.
+    pub fn undo_load(&mut self, gg: &mut System) -> Result<(), InvalidState> {
+        if let Some(slot) = self.undo_load.take() {
+            gg.try_load_raw_state(&slot.state)?;
+            self.play_time = slot.play_time;
+            self.write::<Slot>("ss-undo-load", None, false);
+        }
+        Ok(())
+    }
+
+    /// Restore the slot overwritten by the last save.
+    pub fn undo_save(&mut self, compress: bool) {
+        if let Some(UndoSave { slot, previous }) = self.undo_save.take() {
+            self.write(&format!("ss{}", slot + 1), previous.as_ref(), compress);
+            self.slots[slot] = previous;
+            self.write::<UndoSave>("ss-undo-save", None, false);
+            self.textures_dirty = true;
+        }
+    }
+
+    /// Path of the state file with the given extension.
+    /// The ROM's name might contain dots, so the extension is appended.
+    fn path(&self, ext: &str) -> Option<PathBuf> {
+        let mut path = self.base.clone()?.into_os_string();
+        path.push(".");
+        path.push(ext);
+        Some(path.into())
+    }
+
+    /// Read a state file with the given extension.
+    fn read<T: DeserializeOwned>(&self, ext: &str) -> Option<T> {
+        let path = self.path(ext)?;
+        let content = fs::read(&path).ok()?;
+        let thing = common::try_deserialize(&content);
+        if thing.is_none() {
+            log::error!("Failed to read save state '{}'", path.display());
+        }
+        thing
+    }
+
+    /// Write a state file with the given extension, or delete it if `None`.
+    fn write<T: Serialize>(&self, ext: &str, thing: Option<&T>, compress: bool) {
+        if let Some(path) = self.path(ext) {
+            let result = match thing {
+                Some(thing) => fs::write(&path, common::serialize(thing, compress)),
+                None if path.exists() => fs::remove_file(&path),
+                None => Ok(()),
+            };
+            if let Err(err) = result {
+                log::error!("Failed to write save state '{}': {err}", path.display());
+            }
+        }
+    }
+
+    /// Upload the thumbnails of all slots to their textures, if they changed.
+    fn update_textures(&mut self, ctx: &Context) {
+        if !self.textures_dirty {
+            return;
+        }
+        self.textures_dirty = false;
+        for (slot, texture) in self.slots.iter().zip(self.textures.iter_mut()) {
+            if let Some(thumbnail) = slot.as_ref().and_then(|s| s.thumbnail.as_ref()) {
+                let id = *texture.get_or_insert_with(|| {
+                    App::make_screen_texture(ctx, [0, 0], TextureFilter::Linear)
+                });
+                let pixels = thumbnail
+                    .pixels
+                    .iter()
+                    .map(|[r, g, b, a]| Color32::from_rgba_premultiplied(*r, *g, *b, *a))
+                    .collect();
+                let img = ImageDelta::full(
+                    ImageData::Color(ColorImage {
+                        size: thumbnail.size,
+                        pixels,
+                    }),
+                    TextureFilter::Linear,
+                );
+                ctx.tex_manager().write().set(id, img);
+            }
+        }
+    }
+}
+
+/// A save state, with information shown to the user.
+#[derive(Serialize, Deserialize)]
+struct Slot {
+    /// The state itself, from [System::save_raw_state].
+    state: Vec<u8>,
+    /// The screen at the time of saving.
+    thumbnail: Option<Thumbnail>,
+    /// Time of saving, in seconds since the UNIX epoch.
+    timestamp: u64,
+    /// Time the game was played for when saving.
+    play_time: Duration,
+}
+
+impl Slot {
+    /// Make a slot of the system's current state. The screen is given
+    /// along with its size before post-processing.
+    fn new(
+        gg: &mut System,
+        screen: Option<(&ColorImage, [usize; 2])>,
+        play_time: Duration,
+    ) -> Self {
+        Self {
+            state: gg.save_raw_state(),
+            thumbnail: screen.map(|(screen, size)| Thumbnail::new(screen, size)),
+            timestamp: now(),
+            play_time,
+        }
+    }
+}
+
+/// Screenshot stored with a save state.
+#[derive(Serialize, Deserialize)]
+struct Thumbnail {
+    size: [usize; 2],
+    pixels: Vec<Colour>,
+}
+
+impl Thumbnail {
+    /// Make a thumbnail of the screen, scaled back down to the given
+    /// size if post-processing scaled it up.
+    fn new(screen: &ColorImage, size: [usize; 2]) -> Self {
+        let scale = (screen.size[0] / size[0]).max(1);
+        let size = screen.size.map(|s| s / scale);
+        let pixels = (0..size[1])
+            .flat_map(|y| (0..size[0]).map(move |x| (x, y)))
+            .map(|(x, y)| screen.pixels[y * scale * screen.size[0] + x * scale].to_array())
+            .collect();
+        Self { size, pixels }
+    }
+}
+
+/// Contents of a slot before the last save.
+#[derive(Serialize, Deserialize)]
+struct UndoSave {
+    slot: usize,
+    previous: Option<Slot>,
+}
+
+/// If the file at the given path is a save state, going by its extension.
+pub fn is_state_file(path: &Path) -> bool {
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    match ext.strip_prefix("ss") {
+        Some("-undo-load") => true,
+        Some(num) => !num.is_empty() && num.bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// Show the savestate slot browser.
+pub(super) fn menu(app: &mut App, ui: &mut Ui) {
+    app.save_states.update_textures(ui.ctx());
+    let compress = app.state.options.gg.compress_savestates;
+    let rom_loaded = app.gg.lock().unwrap().options().rom_loaded;
+
+    Grid::new("savestates").spacing([16.0, 8.0]).show(ui, |ui| {
+        for idx in 0..SLOTS {
+            let states = &mut app.save_states;
+            let slot = states.slots[idx].as_ref();
+            let info = slot.map(|s| (s.timestamp, s.play_time));
+            match (
+                slot.and_then(|s| s.thumbnail.as_ref()),
+                states.textures[idx],
+            ) {
+                (Some(thumbnail), Some(texture)) => {
+                    let [w, h] = thumbnail.size.map(|s| s as f32);
+                    let scale = (THUMBNAIL_SIZE[0] / w).min(THUMBNAIL_SIZE[1] / h);
+                    ui.image(texture, vec2(w * scale, h * scale));
+                }
+                _ => {
+                    ui.allocate_space(THUMBNAIL_SIZE.into());
+                }
+            }
+
+            ui.vertical(|ui| {
+                ui.strong(format!("Slot {}", idx + 1));
+                if let Some((timestamp, play_time)) = info {
+                    if timestamp != 0 {
+                        ui.label(format!("Saved {}", time_ago(timestamp)));
+                    }
+                    ui.label(format!("Played {}", format_duration(play_time)));
+                } else {
+                    ui.label("Empty");
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(rom_loaded, Button::new("Save")).clicked() {
+                        let mut gg = app.gg.lock().unwrap();
+                        let size = screen::screen_size(&gg, app.state.options.nds_layout);
+                        let screen = app.last_screen.as_ref().map(|s| (s, size));
+                        states.save(idx, &mut gg, screen, compress);
+                        app.osd.push(format!("Saved state to slot {}", idx + 1));
+                        ui.close_menu();
+                    }
+                    let loadable = rom_loaded && info.is_some();
+                    if ui.add_enabled(loadable, Button::new("Load")).clicked() {
+                        let mut gg = app.gg.lock().unwrap();
+                        let size = screen::screen_size(&gg, app.state.options.nds_layout);
+                        let screen = app.last_screen.as_ref().map(|s| (s, size));
+                        match states.load(idx, &mut gg, screen, compress) {
+                            Ok(_) => app.osd.push(format!("Loaded state from slot {}", idx + 1)),
+                            Err(err) => app
+                                .osd
+                                .push(format!("Failed to load slot {}: {err}", idx + 1)),
+                        }
+                        ui.close_menu();
+                    }
+                });
+            });
+
+            if idx % 2 == 1 {
+                ui.end_row();
+            }
+        }
+    });
+    ui.separator();
+
+    let states = &mut app.save_states;
+    let can_undo_load = rom_loaded && states.undo_load.is_some();
+    if ui
+        .add_enabled(can_undo_load, Button::new("Undo last load"))
+        .clicked()
+    {
+        match states.undo_load(&mut app.gg.lock().unwrap()) {
+            Ok(()) => app.osd.push("Undid last state load"),
+            Err(err) => app.osd.push(format!("Failed to undo last load: {err}")),
+        }
+        ui.close_menu();
+    }
+    let undo_save_text = match &states.undo_save {
+        Some(undo) => format!("Undo last save (Slot {})", undo.slot + 1),
+        None => "Undo last save".to_string(),
+    };
+    if ui
+        .add_enabled(states.undo_save.is_some(), Button::new(undo_save_text))
+        .clicked()
+    {
+        states.undo_save(compress);
+        app.osd.push("Undid last state save");
+        ui.close_menu();
+    }
+}
+
+/// Format a timestamp relative to now, like "5 min ago".
+fn time_ago(timestamp: u64) -> String {
+    let secs = now().saturating_sub(timestamp);
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86399 => format!("{} h ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
+
+/// Format a duration as "h:mm:ss".
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+/// Current time in seconds since the UNIX epoch.
+/// Always 0 on WASM, where the system time is not available.
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now() -> u64 {
+    0
+}
@@ -0,0 +1,129 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+use core::{
+    common::{System, SystemKind},
+    Colour,
+};
+use std::fmt::{Display, Formatter};
+
+use eframe::egui::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// Height of one of the NDS screens.
+const NDS_SCREEN_HEIGHT: usize = 192;
+
+/// Size of the frames shown for the given system, before post-processing.
+pub fn screen_size(gg: &System, layout: NdsLayout) -> [usize; 2] {
+    let size = gg.screen_size();
+    if gg.kind() == SystemKind::Nds {
+        layout.size(size)
+    } else {
+        size
+    }
+}
+
+/// How the screen is scaled to the space it is shown in.
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scaling {
+    /// Scaled by the screen scale option, in a window that is not resizable.
+    Fixed,
+    /// Largest whole multiple of the screen size that fits.
+    Integer,
+    /// As large as fits, keeping the aspect ratio.
+    Aspect,
+    /// Filling all space, ignoring the aspect ratio.
+    Stretch,
+}
+
+impl Scaling {
+    pub const ALL: [Self; 4] = [Self::Fixed, Self::Integer, Self::Aspect, Self::Stretch];
+
+    /// Size to show a screen of the given size at, given the space available
+    /// and the scale to use if fixed.
+    pub fn size(self, screen: [usize; 2], fixed_scale: usize, available: Vec2) -> Vec2 {
+        let screen = vec2(screen[0] as f32, screen[1] as f32);
+        let fit = (available / screen).min_elem();
+        match self {
+            Self::Fixed => screen * fixed_scale as f32,
+            Self::Integer => screen * fit.floor().max(1.0),
+            Self::Aspect => screen * fit,
+            Self::Stretch => available,
+        }
+    }
+}
+
+impl Display for Scaling {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Fixed => "Fixed",
+            Self::Integer => "Integer",
+            Self::Aspect => "Keep aspect ratio",
+            Self::Stretch => "Stretch",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// How the two screens of the NDS are arranged.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NdsLayout {
+    /// Top screen above the bottom one, like on the system.
+    #[default]
+    Vertical,
+    /// Top screen left of the bottom one.
+    Horizontal,
+    /// Only the top screen.
+    Single,
+}
+
+impl NdsLayout {
+    pub const ALL: [Self; 3] = [Self::Vertical, Self::Horizontal, Self::Single];
+
+    /// Size of frames arranged in this layout, given the size the
+    /// system produces them at.
+    pub fn size(self, size: [usize; 2]) -> [usize; 2] {
+        match self {
+            Self::Vertical => size,
+            Self::Horizontal => [size[0] * 2, NDS_SCREEN_HEIGHT],
+            Self::Single => [size[0], NDS_SCREEN_HEIGHT],
+        }
+    }
+
+    /// Arrange a frame as produced by the system, with the top screen above
+    /// the bottom one, in this layout. If `swap`, the screens trade places.
+    pub fn arrange(self, frame: Vec<Colour>, swap: bool) -> Vec<Colour> {
+        if self == Self::Vertical && !swap {
+            return frame;
+        }
+        let (top, bottom) = frame.split_at(frame.len() / 2);
+        let (first, second) = if swap { (bottom, top) } else { (top, bottom) };
+        match self {
+            Self::Vertical => [first, second].concat(),
+            Self::Horizontal => {
+                let width = first.len() / NDS_SCREEN_HEIGHT;
+                first
+                    .chunks(width)
+                    .zip(second.chunks(width))
+                    .flat_map(|(a, b)| a.iter().chain(b))
+                    .copied()
+                    .collect()
+            }
+            Self::Single => first.to_vec(),
+        }
+    }
+}
+
+impl Display for NdsLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Vertical => "Vertical",
+            Self::Horizontal => "Horizontal",
+            Self::Single => "Single screen",
+        };
+        write!(f, "{name}")
+    }
+}
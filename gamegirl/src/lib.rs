@@ -8,9 +8,14 @@
 #![feature(exclusive_range_pattern)]
 #![feature(mixed_integer_ops)]
 
+pub mod args;
 pub mod gui;
+pub mod queue;
 
-use core::{common::SAMPLE_RATE, System};
+use core::common::SAMPLE_RATE;
+#[cfg(target_arch = "wasm32")]
+use core::System;
+#[cfg(target_arch = "wasm32")]
 use std::sync::{Arc, Mutex};
 
 use cpal::{
@@ -21,12 +26,20 @@ use eframe::egui::Color32;
 #[cfg(target_arch = "wasm32")]
 use eframe::wasm_bindgen::{self, prelude::*};
 
+#[cfg(target_arch = "wasm32")]
+use crate::args::Args;
+use crate::queue::{RingConsumer, RingProducer};
+
 /// Colour type used by the PPU for display output.
 pub type Colour = Color32;
 
+/// Capacity of the audio buffer between emulation and audio playback,
+/// in samples: Half a second of stereo audio.
+const AUDIO_BUFFER: usize = SAMPLE_RATE as usize;
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
-pub struct Handle(Stream);
+pub struct Handle(Option<Stream>);
 
 /// Start the emulator on WASM. See web/index.html for usage.
 #[cfg(target_arch = "wasm32")]
@@ -37,18 +50,29 @@ pub fn start(canvas_id: &str) -> Result<Handle, eframe::wasm_bindgen::JsValue> {
 
     let gg = System::default();
     let gg = Arc::new(Mutex::new(gg));
-    let stream = setup_cpal(gg.clone());
-    gui::start(gg, canvas_id).map(|_| Handle(stream))
+    let (audio, stream) = setup_audio();
+    gui::start(gg, audio, canvas_id, Args::default()).map(|_| Handle(stream))
 }
 
 /// Setup audio playback on the default audio device using CPAL.
-/// Will automatically poll the gg for audio when needed on a separate thread.
-/// *NOT* used for synchronization, since audio samples are requested less than
-/// 60 times per second, which would lead to choppy display.
-/// Make sure to keep the returned Stream around to prevent the audio playback
-/// thread from closing.
-pub fn setup_cpal(gg: Arc<Mutex<System>>) -> Stream {
-    let device = cpal::default_host().default_output_device().unwrap();
+/// Returns the buffer to push samples to, which is played on a separate
+/// thread, and the stream playing it. Make sure to keep the stream
+/// around to prevent the audio playback thread from closing.
+/// The stream is `None` if no audio device could be opened, in which case
+/// samples pushed to the buffer are dropped.
+pub fn setup_audio() -> (RingProducer, Option<Stream>) {
+    let (producer, consumer) = queue::ring_buffer(AUDIO_BUFFER);
+    let stream = setup_cpal(consumer);
+    if let Err(err) = &stream {
+        log::error!("Failed to open audio device, playing without audio: {err}");
+    }
+    (producer, stream.ok())
+}
+
+fn setup_cpal(audio: RingConsumer) -> Result<Stream, String> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or("No output device available")?;
     let stream = device
         .build_output_stream(
             &StreamConfig {
@@ -56,13 +80,10 @@ pub fn setup_cpal(gg: Arc<Mutex<System>>) -> Stream {
                 sample_rate: SampleRate(SAMPLE_RATE),
                 buffer_size: BufferSize::Default,
             },
-            move |data: &mut [f32], _| {
-                let mut gg = gg.lock().unwrap();
-                gg.produce_samples(data)
-            },
-            move |err| panic!("{err}"),
+            move |data: &mut [f32], _| audio.pop(data),
+            move |err| log::error!("Audio playback failed: {err}"),
         )
-        .unwrap();
-    stream.play().unwrap();
-    stream
+        .map_err(|e| e.to_string())?;
+    stream.play().map_err(|e| e.to_string())?;
+    Ok(stream)
 }
@@ -5,15 +5,33 @@
 // obtain one at https://mozilla.org/MPL/2.0/.
 
 use core::common::System;
-use std::sync::{Arc, Mutex};
+use std::{
+    env, process,
+    sync::{Arc, Mutex},
+};
 
-use gamegirl::gui;
+use gamegirl::{
+    args::{Args, USAGE},
+    gui,
+};
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() {
     env_logger::init();
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
     let gg = System::default();
     let gg = Arc::new(Mutex::new(gg));
-    let _stream = gamegirl::setup_cpal(gg.clone());
-    gui::start(gg);
+    let (audio, stream) = gamegirl::setup_audio();
+    gui::start(gg, audio, stream.is_some(), args);
 }
@@ -0,0 +1,186 @@
+// Unless otherwise noted, this file is released and thus subject to the
+// terms of the Mozilla Public License Version 2.0 (MPL2). Also, it is
+// "Incompatible With Secondary Licenses", as defined by the MPL2.
+// If a copy of the MPL2 was not distributed with this file, you can
+// obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Lock-free queues used to pass output from the emulation thread
+//! to the GUI and audio threads, without either side ever waiting
+//! on the other.
+
+use std::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicU32, AtomicU8, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Flag set in [TripleShared::middle] when the middle slot holds a value
+/// the reader has not seen yet.
+const FRESH: u8 = 4;
+
+/// Create a triple buffer, which allows a writer to publish values that a
+/// reader can pick up whenever it wants, always getting the newest one.
+/// Used for passing finished frames to the GUI.
+pub fn triple_buffer<T: Default>() -> (TripleWriter<T>, TripleReader<T>) {
+    let shared = Arc::new(TripleShared {
+        slots: Default::default(),
+        middle: AtomicU8::new(1),
+    });
+    (
+        TripleWriter {
+            shared: shared.clone(),
+            back: 0,
+        },
+        TripleReader { shared, front: 2 },
+    )
+}
+
+/// Three slots: One owned by the writer, one by the reader and one in
+/// the middle, which gets swapped with the other two when publishing or
+/// reading a value.
+struct TripleShared<T> {
+    slots: [UnsafeCell<T>; 3],
+    /// Index of the middle slot, with [FRESH] set if it holds a new value.
+    middle: AtomicU8,
+}
+
+// SAFETY: Each slot is only ever accessed by the side that currently owns it,
+// ownership is passed between sides with atomic swaps.
+unsafe impl<T: Send> Send for TripleShared<T> {}
+unsafe impl<T: Send> Sync for TripleShared<T> {}
+
+/// Writing side of a triple buffer.
+pub struct TripleWriter<T> {
+    shared: Arc<TripleShared<T>>,
+    back: u8,
+}
+
+impl<T> TripleWriter<T> {
+    /// Publish a new value, replacing any value the reader did not pick up.
+    pub fn write(&mut self, value: T) {
+        // SAFETY: The back slot is owned by the writer.
+        unsafe { *self.shared.slots[self.back as usize].get() = value };
+        let middle = self.shared.middle.swap(self.back | FRESH, Ordering::AcqRel);
+        self.back = middle & !FRESH;
+    }
+}
+
+/// Reading side of a triple buffer.
+pub struct TripleReader<T> {
+    shared: Arc<TripleShared<T>>,
+    front: u8,
+}
+
+impl<T> TripleReader<T> {
+    /// Get the newest value, if one was published since the last read.
+    pub fn read(&mut self) -> Option<&mut T> {
+        if self.shared.middle.load(Ordering::Relaxed) & FRESH == 0 {
+            return None;
+        }
+        let middle = self.shared.middle.swap(self.front, Ordering::AcqRel);
+        self.front = middle & !FRESH;
+        // SAFETY: The front slot is owned by the reader.
+        Some(unsafe { &mut *self.shared.slots[self.front as usize].get() })
+    }
+}
+
+/// Create a ring buffer with the given capacity in samples, for passing
+/// audio from the emulation thread to the audio thread.
+pub fn ring_buffer(capacity: usize) -> (RingProducer, RingConsumer) {
+    let shared = Arc::new(RingShared {
+        samples: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+        last_request: AtomicUsize::new(0),
+    });
+    (
+        RingProducer {
+            shared: shared.clone(),
+        },
+        RingConsumer { shared },
+    )
+}
+
+/// Samples of a ring buffer. Samples are stored as the bits of the `f32`
+/// in atomics, which avoids needing any unsafe code.
+struct RingShared {
+    samples: Box<[AtomicU32]>,
+    /// Total amount of samples read so far.
+    read: AtomicUsize,
+    /// Total amount of samples written so far.
+    write: AtomicUsize,
+    /// Amount of samples the consumer asked for last time.
+    last_request: AtomicUsize,
+}
+
+impl RingShared {
+    fn len(&self) -> usize {
+        let write = self.write.load(Ordering::Acquire);
+        write.wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+}
+
+/// Writing side of a ring buffer.
+pub struct RingProducer {
+    shared: Arc<RingShared>,
+}
+
+impl RingProducer {
+    /// Push samples to the buffer. Samples that do not fit anymore
+    /// are dropped.
+    pub fn push(&self, samples: &[f32]) {
+        let shared = &*self.shared;
+        let free = shared.samples.len() - shared.len();
+        let write = shared.write.load(Ordering::Relaxed);
+        for (idx, sample) in samples.iter().take(free).enumerate() {
+            let slot = write.wrapping_add(idx) % shared.samples.len();
+            shared.samples[slot].store(sample.to_bits(), Ordering::Relaxed);
+        }
+        shared.write.store(
+            write.wrapping_add(samples.len().min(free)),
+            Ordering::Release,
+        );
+    }
+
+    /// Amount of samples in the buffer that were not played yet.
+    pub fn buffered(&self) -> usize {
+        self.shared.len()
+    }
+
+    /// Maximum amount of samples in the buffer.
+    pub fn capacity(&self) -> usize {
+        self.shared.samples.len()
+    }
+
+    /// Amount of samples the consumer asked for the last time it
+    /// read from the buffer.
+    pub fn last_request(&self) -> usize {
+        self.shared.last_request.load(Ordering::Relaxed)
+    }
+}
+
+/// Reading side of a ring buffer.
+pub struct RingConsumer {
+    shared: Arc<RingShared>,
+}
+
+impl RingConsumer {
+    /// Fill the given buffer with samples. If there are not enough, the rest
+    /// is filled with silence.
+    pub fn pop(&self, out: &mut [f32]) {
+        let shared = &*self.shared;
+        shared.last_request.store(out.len(), Ordering::Relaxed);
+        let available = shared.len().min(out.len());
+        let read = shared.read.load(Ordering::Relaxed);
+        for (idx, dst) in out.iter_mut().enumerate().take(available) {
+            let slot = read.wrapping_add(idx) % shared.samples.len();
+            *dst = f32::from_bits(shared.samples[slot].load(Ordering::Relaxed));
+        }
+        out[available..].fill(0.0);
+        shared
+            .read
+            .store(read.wrapping_add(available), Ordering::Release);
+    }
+}
@@ -4,7 +4,13 @@
 // If a copy of the MPL2 was not distributed with this file, you can
 // obtain one at https://mozilla.org/MPL/2.0/.
 
-use core::ggc::cpu::{DReg::*, Reg::A};
+use core::{
+    common::{CgbMode, SystemConfig},
+    ggc::{
+        cpu::{DReg::*, Reg::A},
+        model::GgcModel,
+    },
+};
 use std::ops::ControlFlow::{Break, Continue};
 
 use crate::Status;
@@ -52,3 +58,25 @@ pub fn mooneye(subdir: &str) {
 pub fn acid2() {
     crate::run_dir::<true, true>("acid2", |_| Continue(()));
 }
+
+/// Set the hardware model a test is meant for, using the suffix
+/// mooneye's tests use in their file name (`boot_regs-dmg0`, ...).
+pub fn configure_model(name: &str, config: &mut SystemConfig) {
+    let suffix = name.rsplit('-').next().unwrap();
+    let (model, cgb) = match suffix {
+        "dmg0" => (GgcModel::Dmg0, false),
+        "mgb" => (GgcModel::Mgb, false),
+        "S" | "sgb" => (GgcModel::Sgb, false),
+        "sgb2" => (GgcModel::Sgb2, false),
+        "cgb0" => (GgcModel::Cgb0, true),
+        "C" | "cgb" | "cgbABCDE" => (GgcModel::Cgb, true),
+        "A" => (GgcModel::Agb, true),
+        _ => return,
+    };
+    if cgb {
+        config.mode = CgbMode::Always;
+        config.cgb_model = model;
+    } else {
+        config.dmg_model = model;
+    }
+}
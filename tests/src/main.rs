@@ -9,8 +9,9 @@
 mod gb;
 mod gba;
 
-use core::{common::SystemConfig, System};
+use core::{common::SystemConfig, components::firmware::Firmware, System};
 use std::{
+    cell::RefCell,
     env, fs,
     fs::File,
     io::BufWriter,
@@ -26,14 +27,26 @@ use seahorse::{App, Command, Flag, FlagType};
 
 const TIMEOUT: usize = 30;
 
+thread_local! {
+    /// Config used for running tests. Uses the HLE BIOS unless a BIOS is given.
+    static CONFIG: RefCell<SystemConfig> = RefCell::new(SystemConfig {
+        gga_hle_bios: true,
+        ..SystemConfig::default()
+    });
+}
+
 fn main() {
     let args = env::args().collect();
     App::new("GameGirl tests")
         .description("Automated test runner for GameGirl")
-        .usage("tests [good] [bench] [--gg]")
+        .usage("tests [good] [bench] [--gg] [--bios path]")
         .flag(
             Flag::new("gg", FlagType::Bool).description("Also run GG tests (only GGA by default)"),
         )
+        .flag(
+            Flag::new("bios", FlagType::String)
+                .description("GGA BIOS to use for tests (uses the HLE BIOS if not given)"),
+        )
         .command(
             Command::new("good")
                 .description("Mark the given test as good by making a comparison image for it")
@@ -43,8 +56,11 @@ fn main() {
                         let test_rom = fs::read(path).unwrap();
                         let img_path = format!("{}.png", path);
                         let img_path = PathBuf::from(img_path);
-                        let img = run::<false, true>(test_rom, None, |_| ControlFlow::Continue(()))
-                            .unwrap();
+                        let config = test_config(Path::new(path));
+                        let img = run::<false, true>(test_rom, &config, None, |_| {
+                            ControlFlow::Continue(())
+                        })
+                        .unwrap();
                         save_png(&img_path, img);
                     }
                 }),
@@ -62,8 +78,10 @@ fn main() {
                     gg.load_cart(
                         include_bytes!("../../bench.gb").to_vec(),
                         None,
+                        None,
                         &SystemConfig::default(),
-                    );
+                    )
+                    .unwrap();
 
                     if c.bool_flag("measure") {
                         let mut times = Vec::new();
@@ -90,6 +108,13 @@ fn main() {
                 }),
         )
         .action(|c| {
+            if let Ok(bios) = c.string_flag("bios") {
+                CONFIG.with(|conf| {
+                    let mut conf = conf.borrow_mut();
+                    conf.gga_hle_bios = false;
+                    conf.firmware.insert(Firmware::GgaBios, PathBuf::from(bios));
+                });
+            }
             if c.bool_flag("gg") {
                 println!("Executing blargg tests");
                 gb::blargg();
@@ -97,6 +122,7 @@ fn main() {
                 println!("\nExecuting mooneye tests");
                 gb::mooneye("acceptance");
                 gb::mooneye("emulator-only");
+                gb::mooneye("misc");
                 println!("\nExecuting acid2 tests");
                 gb::acid2();
             }
@@ -164,6 +190,7 @@ fn run_inner<const SKIP_BOOTROM: bool, const IMG_COMPARE: bool>(
         })
         .for_each(|entry| {
             let test_rom = fs::read(entry.path()).unwrap();
+            let config = test_config(&entry.path());
             let img_path = format!("{}.png", entry.path().to_str().unwrap());
             let img_path = PathBuf::from(img_path);
             let img = if IMG_COMPARE {
@@ -174,7 +201,7 @@ fn run_inner<const SKIP_BOOTROM: bool, const IMG_COMPARE: bool>(
 
             let rn = Instant::now();
             total.fetch_add(1, Ordering::Relaxed);
-            match run::<SKIP_BOOTROM, false>(test_rom, img, cond) {
+            match run::<SKIP_BOOTROM, false>(test_rom, &config, img, cond) {
                 Ok(frame) => {
                     println!(
                         "Ran {name}/{}... {} in {}ms",
@@ -199,13 +226,27 @@ fn run_inner<const SKIP_BOOTROM: bool, const IMG_COMPARE: bool>(
         });
 }
 
+/// Config to run the given test file with.
+fn test_config(path: &Path) -> SystemConfig {
+    let mut config = CONFIG.with(|conf| conf.borrow().clone());
+    if path
+        .extension()
+        .is_some_and(|ext| ext.to_str().unwrap() == "gb")
+    {
+        gb::configure_model(path.file_stem().unwrap().to_str().unwrap(), &mut config);
+    }
+    config
+}
+
 fn run<const SKIP_BOOTROM: bool, const TIMEOUT_GOOD: bool>(
     test: Vec<u8>,
+    config: &SystemConfig,
     image: Option<Vec<core::Colour>>,
     cond: fn(&System) -> ControlFlow<Status>,
 ) -> Result<Vec<core::Colour>, String> {
     let mut gg = System::default();
-    gg.load_cart(test, None, &SystemConfig::default());
+    gg.load_cart(test, None, None, config)
+        .map_err(|err| err.to_string())?;
     if SKIP_BOOTROM {
         gg.skip_bootrom();
     }
@@ -7,15 +7,20 @@ fn main() {
     let mut cached = System::default();
 
     let rom = include_bytes!("../../../bench.gb").to_vec();
-    cached.load_cart(rom.clone(), None, &SystemConfig::default());
-    non_cached.load_cart(
-        rom,
-        None,
-        &SystemConfig {
-            cached_interpreter: false,
-            ..SystemConfig::default()
-        },
-    );
+    cached
+        .load_cart(rom.clone(), None, None, &SystemConfig::default())
+        .unwrap();
+    non_cached
+        .load_cart(
+            rom,
+            None,
+            None,
+            &SystemConfig {
+                cached_interpreter: false,
+                ..SystemConfig::default()
+            },
+        )
+        .unwrap();
 
     let (c_tx, c_rx) = mpsc::channel();
     let (n_tx, n_rx) = mpsc::channel();